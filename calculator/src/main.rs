@@ -1,4 +1,54 @@
-use std::io;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// Default (English) text for every user-facing message. `{name}`
+/// placeholders are filled in by `Messages::format`.
+const ENGLISH: &[(&str, &str)] = &[
+    (
+        "welcome",
+        "Enter an expression (e.g. 2 * sin(30)), 'mode deg|rad', 'status', 'history', '!N', 'explain', or 'quit'. 'ans' is the last result; start with + * or / to continue from it.",
+    ),
+    ("prompt", "[{mode}] > "),
+    ("result", "Result: {value}"),
+    ("error", "Error: {error}"),
+    ("mode_set", "Angle mode set to {mode}"),
+    ("mode_usage", "Usage: mode deg | mode rad"),
+    ("precision_set", "Precision set to {precision}"),
+    (
+        "precision_usage",
+        "Usage: precision <digits> | precision auto",
+    ),
+    ("base_set", "Number base set to {base}"),
+    ("base_usage", "Usage: base 2 | base 8 | base 10 | base 16"),
+    ("stored", "Stored {value} in memory"),
+    ("nothing_to_store", "No result to store yet"),
+    ("assigned", "{name} = {value}"),
+    (
+        "shadow_warning",
+        "Warning: '{name}' now shadows the built-in constant",
+    ),
+    (
+        "ans_reserved",
+        "'ans' always holds the last result and cannot be assigned",
+    ),
+    ("history_empty", "History is empty"),
+    ("explain_set", "Explain mode {state}"),
+    ("explain_usage", "Usage: explain | explain on | explain off"),
+    (
+        "history_range",
+        "No history entry {index} (history has {count} entries)",
+    ),
+];
+
+/// Named constants available in expressions. A user variable with the same
+/// name takes precedence.
+const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
+    ("tau", std::f64::consts::TAU),
+];
 
 enum Operation {
     Add(f64, f64),
@@ -7,48 +57,1501 @@ enum Operation {
     Divide(f64, f64),
 }
 
-fn calculate(op: Operation) -> f64 {
+fn calculate(op: Operation) -> Result<f64, CalcError> {
     match op {
-        Operation::Add(a, b) => a + b,
-        Operation::Subtract(a, b) => a - b,
-        Operation::Multiply(a, b) => a * b,
-        Operation::Divide(a, b) => {
-            if b == 0.0 {
-                println!("Error: Division by zero!");
-                0.0
+        Operation::Add(a, b) => Ok(a + b),
+        Operation::Subtract(a, b) => Ok(a - b),
+        Operation::Multiply(a, b) => Ok(a * b),
+        Operation::Divide(_, 0.0) => Err(CalcError::DivisionByZero),
+        Operation::Divide(a, b) => Ok(a / b),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl BinOp {
+    fn operation(self, a: f64, b: f64) -> Operation {
+        match self {
+            BinOp::Add => Operation::Add(a, b),
+            BinOp::Subtract => Operation::Subtract(a, b),
+            BinOp::Multiply => Operation::Multiply(a, b),
+            BinOp::Divide => Operation::Divide(a, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AngleMode {
+    Degrees,
+    Radians,
+}
+
+impl AngleMode {
+    fn parse(s: &str) -> Option<AngleMode> {
+        match s.to_ascii_lowercase().as_str() {
+            "deg" | "degrees" => Some(AngleMode::Degrees),
+            "rad" | "radians" => Some(AngleMode::Radians),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AngleMode::Degrees => "DEG",
+            AngleMode::Radians => "RAD",
+        }
+    }
+
+    /// Converts an angle in this mode to radians.
+    fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Degrees => angle.to_radians(),
+            AngleMode::Radians => angle,
+        }
+    }
+
+    /// Converts an angle in radians to this mode.
+    fn radians_to_mode(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Degrees => angle.to_degrees(),
+            AngleMode::Radians => angle,
+        }
+    }
+}
+
+/// All REPL state that persists between lines.
+#[derive(Debug, Clone, PartialEq)]
+struct CalcState {
+    /// Digits after the decimal point, or `None` to print the shortest form.
+    precision: Option<usize>,
+    mode: AngleMode,
+    /// Base used to print integer results (2, 8, 10 or 16).
+    base: u32,
+    memory: f64,
+    /// The last result, available as `ans`; 0 before the first one.
+    ans: f64,
+    /// Values assigned with `name = expr`.
+    variables: HashMap<String, f64>,
+    /// Every successfully evaluated expression with its result.
+    history: Vec<(String, f64)>,
+    /// Show each reduction step before the result.
+    explain: bool,
+}
+
+impl CalcState {
+    fn new() -> Self {
+        CalcState {
+            precision: None,
+            mode: AngleMode::Radians,
+            base: 10,
+            memory: 0.0,
+            ans: 0.0,
+            variables: HashMap::new(),
+            history: Vec::new(),
+            explain: false,
+        }
+    }
+
+    /// Formats a result using the current base and precision. Non-integer
+    /// values are always printed in decimal.
+    fn format_value(&self, value: f64) -> String {
+        if self.base != 10 && value.fract() == 0.0 && value.abs() < 2f64.powi(53) {
+            let magnitude = value.abs() as u64;
+            let digits = match self.base {
+                2 => format!("0b{:b}", magnitude),
+                8 => format!("0o{:o}", magnitude),
+                _ => format!("0x{:X}", magnitude),
+            };
+            return if value < 0.0 {
+                format!("-{}", digits)
+            } else {
+                digits
+            };
+        }
+        match self.precision {
+            Some(p) => format!("{:.*}", p, value),
+            None => value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CalcState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = match self.precision {
+            Some(p) => p.to_string(),
+            None => "auto".to_string(),
+        };
+        writeln!(f, "Precision:  {}", precision)?;
+        writeln!(f, "Angle mode: {}", self.mode.label())?;
+        writeln!(f, "Base:       {}", self.base)?;
+        writeln!(f, "Memory:     {}", self.format_value(self.memory))?;
+        writeln!(f, "Ans:        {}", self.format_value(self.ans))?;
+        writeln!(f, "Explain:    {}", if self.explain { "on" } else { "off" })?;
+        write!(f, "History:    {} entries", self.history.len())
+    }
+}
+
+/// Supported functions and the number of arguments each takes.
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("asin", 1),
+    ("acos", 1),
+    ("atan", 1),
+    ("atan2", 2),
+    ("sinh", 1),
+    ("cosh", 1),
+    ("tanh", 1),
+];
+
+/// Combinatoric operators, written infix as `n ncr k` / `n npr k`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ComboOp {
+    Ncr,
+    Npr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ast {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Ast>),
+    Factorial(Box<Ast>),
+    Binary(BinOp, Box<Ast>, Box<Ast>),
+    Combo(ComboOp, Box<Ast>, Box<Ast>),
+    Call(String, Vec<Ast>),
+}
+
+/// A syntax error at a 1-based character column of the input.
+#[derive(Debug, Clone, PartialEq)]
+struct ParseError {
+    column: usize,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CalcError {
+    Parse(ParseError),
+    DivisionByZero,
+    Domain(String),
+    Overflow(String),
+    UnknownVariable(String),
+    /// An operation produced infinity or NaN; the message names it.
+    NonFinite(String),
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalcError::Parse(e) => write!(f, "{}", e),
+            CalcError::DivisionByZero => write!(f, "division by zero"),
+            CalcError::Domain(msg) => write!(f, "domain error: {}", msg),
+            CalcError::Overflow(msg) => write!(f, "overflow: {}", msg),
+            CalcError::UnknownVariable(name) => write!(f, "unknown variable '{}'", name),
+            CalcError::NonFinite(msg) => write!(f, "result is not a finite number: {}", msg),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Bang,
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        let kind = match c {
+            ' ' | '\t' => {
+                i += 1;
+                continue;
+            }
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '!' => TokenKind::Bang,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ',' => TokenKind::Comma,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // Optional exponent, e.g. 1e308 or 2.5E-3. An `e` straight
+                // after a number always starts one, so `1.5e` is an error
+                // rather than 1.5 followed by the constant e.
+                let digit_at = |j: usize| chars.get(j).is_some_and(|c| c.is_ascii_digit());
+                if matches!(chars.get(i), Some('e' | 'E')) {
+                    let exponent_column = column + (i - start);
+                    i += 1;
+                    if matches!(chars.get(i), Some('+' | '-')) {
+                        i += 1;
+                    }
+                    if !digit_at(i) {
+                        let text: String = chars[start..i].iter().collect();
+                        return Err(ParseError {
+                            column: exponent_column,
+                            message: format!("missing exponent digits in '{}'", text),
+                        });
+                    }
+                    while digit_at(i) {
+                        i += 1;
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let value = text.parse::<f64>().map_err(|_| ParseError {
+                    column,
+                    message: format!("invalid number '{}'", text),
+                })?;
+                if !value.is_finite() {
+                    return Err(ParseError {
+                        column,
+                        message: format!("number '{}' is out of range", text),
+                    });
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Number(value),
+                    column,
+                });
+                continue;
+            }
+            c if c.is_ascii_alphabetic() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().collect();
+                tokens.push(Token {
+                    kind: TokenKind::Ident(name.to_ascii_lowercase()),
+                    column,
+                });
+                continue;
+            }
+            other => {
+                return Err(ParseError {
+                    column,
+                    message: format!("unexpected '{}'", other),
+                });
+            }
+        };
+        tokens.push(Token { kind, column });
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    end_column: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn error_here(&self) -> ParseError {
+        match self.tokens.get(self.pos) {
+            Some(t) => ParseError {
+                column: t.column,
+                message: format!("unexpected '{}'", token_text(&t.kind)),
+            },
+            None => ParseError {
+                column: self.end_column,
+                message: "unexpected end of input".to_string(),
+            },
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Ast, ParseError> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Plus) => BinOp::Add,
+                Some(TokenKind::Minus) => BinOp::Subtract,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.term()?;
+            left = Ast::Binary(op, Box::new(left), Box::new(right));
+        }
+    }
+
+    // term := unary (('*' | '/' | 'ncr' | 'npr') unary)*
+    fn term(&mut self) -> Result<Ast, ParseError> {
+        enum TermOp {
+            Arith(BinOp),
+            Combo(ComboOp),
+        }
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(TokenKind::Star) => TermOp::Arith(BinOp::Multiply),
+                Some(TokenKind::Slash) => TermOp::Arith(BinOp::Divide),
+                Some(TokenKind::Ident(name)) if name == "ncr" => TermOp::Combo(ComboOp::Ncr),
+                Some(TokenKind::Ident(name)) if name == "npr" => TermOp::Combo(ComboOp::Npr),
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = Box::new(self.unary()?);
+            left = match op {
+                TermOp::Arith(op) => Ast::Binary(op, Box::new(left), right),
+                TermOp::Combo(op) => Ast::Combo(op, Box::new(left), right),
+            };
+        }
+    }
+
+    // unary := '-' unary | postfix
+    fn unary(&mut self) -> Result<Ast, ParseError> {
+        if self.peek() == Some(&TokenKind::Minus) {
+            self.pos += 1;
+            return Ok(Ast::Negate(Box::new(self.unary()?)));
+        }
+        self.postfix()
+    }
+
+    // postfix := primary '!'*
+    fn postfix(&mut self) -> Result<Ast, ParseError> {
+        let mut ast = self.primary()?;
+        while self.peek() == Some(&TokenKind::Bang) {
+            self.pos += 1;
+            ast = Ast::Factorial(Box::new(ast));
+        }
+        Ok(ast)
+    }
+
+    // primary := number | name | function '(' expr (',' expr)* ')' | '(' expr ')'
+    fn primary(&mut self) -> Result<Ast, ParseError> {
+        match self.peek() {
+            Some(TokenKind::Number(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(Ast::Number(n))
+            }
+            Some(TokenKind::Ident(name))
+                if self.tokens.get(self.pos + 1).map(|t| &t.kind) != Some(&TokenKind::LParen) =>
+            {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(Ast::Variable(name))
+            }
+            Some(TokenKind::Ident(name)) => {
+                let column = self.tokens[self.pos].column;
+                let arity = match FUNCTIONS.iter().find(|(f, _)| f == name) {
+                    Some((_, arity)) => *arity,
+                    None => {
+                        return Err(ParseError {
+                            column,
+                            message: format!("unknown function '{}'", name),
+                        });
+                    }
+                };
+                let name = name.clone();
+                self.pos += 2;
+                let mut args = vec![self.expr()?];
+                while self.peek() == Some(&TokenKind::Comma) {
+                    self.pos += 1;
+                    args.push(self.expr()?);
+                }
+                if self.peek() != Some(&TokenKind::RParen) {
+                    return Err(self.error_here());
+                }
+                self.pos += 1;
+                if args.len() != arity {
+                    return Err(ParseError {
+                        column,
+                        message: format!(
+                            "{} takes {} argument(s), got {}",
+                            name,
+                            arity,
+                            args.len()
+                        ),
+                    });
+                }
+                Ok(Ast::Call(name, args))
+            }
+            Some(TokenKind::LParen) => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                if self.peek() != Some(&TokenKind::RParen) {
+                    return Err(self.error_here());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            _ => Err(self.error_here()),
+        }
+    }
+}
+
+fn token_text(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Number(n) => n.to_string(),
+        TokenKind::Ident(name) => name.clone(),
+        TokenKind::Plus => "+".to_string(),
+        TokenKind::Minus => "-".to_string(),
+        TokenKind::Star => "*".to_string(),
+        TokenKind::Slash => "/".to_string(),
+        TokenKind::Bang => "!".to_string(),
+        TokenKind::LParen => "(".to_string(),
+        TokenKind::RParen => ")".to_string(),
+        TokenKind::Comma => ",".to_string(),
+    }
+}
+
+fn parse(input: &str) -> Result<Ast, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        end_column: input.chars().count() + 1,
+    };
+    let ast = parser.expr()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.error_here());
+    }
+    Ok(ast)
+}
+
+/// Replaces every variable with its value, preferring user variables over
+/// the built-in constants.
+fn resolve(ast: Ast, variables: &HashMap<String, f64>) -> Result<Ast, CalcError> {
+    Ok(match ast {
+        Ast::Number(_) => ast,
+        Ast::Variable(name) => {
+            let constant = CONSTANTS.iter().find(|(c, _)| *c == name).map(|(_, v)| *v);
+            match variables.get(&name).copied().or(constant) {
+                Some(value) => Ast::Number(value),
+                None => return Err(CalcError::UnknownVariable(name)),
+            }
+        }
+        Ast::Negate(inner) => Ast::Negate(Box::new(resolve(*inner, variables)?)),
+        Ast::Factorial(inner) => Ast::Factorial(Box::new(resolve(*inner, variables)?)),
+        Ast::Binary(op, left, right) => Ast::Binary(
+            op,
+            Box::new(resolve(*left, variables)?),
+            Box::new(resolve(*right, variables)?),
+        ),
+        Ast::Combo(op, left, right) => Ast::Combo(
+            op,
+            Box::new(resolve(*left, variables)?),
+            Box::new(resolve(*right, variables)?),
+        ),
+        Ast::Call(name, args) => Ast::Call(
+            name,
+            args.into_iter()
+                .map(|a| resolve(a, variables))
+                .collect::<Result<Vec<Ast>, CalcError>>()?,
+        ),
+    })
+}
+
+/// Splits `name = expr` into the lowercased name, the expression and the
+/// expression's 0-based character offset in the line.
+fn split_assignment(line: &str) -> Option<(String, &str, usize)> {
+    let (name, expr) = line.split_once('=')?;
+    let name = name.trim();
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric());
+    if !valid {
+        return None;
+    }
+    let offset = line.len() - expr.len();
+    Some((
+        name.to_ascii_lowercase(),
+        expr,
+        line[..offset].chars().count(),
+    ))
+}
+
+/// Pre-evaluates every subtree whose operands are all numbers. Division by
+/// a literal zero and operations overflowing to infinity are left in place
+/// so the error is reported at evaluation,
+/// and function calls are kept because they depend on the angle mode.
+/// Factorials and combinatorics are kept so their errors surface the same
+/// way.
+fn fold(ast: Ast) -> Ast {
+    match ast {
+        Ast::Number(_) | Ast::Variable(_) => ast,
+        Ast::Negate(inner) => match fold(*inner) {
+            Ast::Number(n) => Ast::Number(-n),
+            other => Ast::Negate(Box::new(other)),
+        },
+        Ast::Binary(op, left, right) => match (fold(*left), fold(*right)) {
+            (Ast::Number(a), Ast::Number(b)) => match calculate(op.operation(a, b)) {
+                Ok(value) if value.is_finite() => Ast::Number(value),
+                _ => Ast::Binary(op, Box::new(Ast::Number(a)), Box::new(Ast::Number(b))),
+            },
+            (l, r) => Ast::Binary(op, Box::new(l), Box::new(r)),
+        },
+        Ast::Factorial(inner) => Ast::Factorial(Box::new(fold(*inner))),
+        Ast::Combo(op, left, right) => {
+            Ast::Combo(op, Box::new(fold(*left)), Box::new(fold(*right)))
+        }
+        Ast::Call(name, args) => Ast::Call(name, args.into_iter().map(fold).collect()),
+    }
+}
+
+fn eval(ast: &Ast, mode: AngleMode) -> Result<f64, CalcError> {
+    match ast {
+        Ast::Number(n) => Ok(*n),
+        Ast::Variable(name) => Err(CalcError::UnknownVariable(name.clone())),
+        Ast::Negate(inner) => Ok(-eval(inner, mode)?),
+        Ast::Factorial(inner) => Ok(factorial(to_count(eval(inner, mode)?)?)? as f64),
+        Ast::Binary(op, left, right) => {
+            let (a, b) = (eval(left, mode)?, eval(right, mode)?);
+            let operands = Ast::Binary(*op, Box::new(Ast::Number(a)), Box::new(Ast::Number(b)));
+            check_finite(calculate(op.operation(a, b))?, &operands)
+        }
+        Ast::Combo(op, left, right) => {
+            let n = to_count(eval(left, mode)?)?;
+            let k = to_count(eval(right, mode)?)?;
+            let value = match op {
+                ComboOp::Ncr => combinations(n, k)?,
+                ComboOp::Npr => permutations(n, k)?,
+            };
+            Ok(value as f64)
+        }
+        Ast::Call(name, args) => {
+            let values = args
+                .iter()
+                .map(|a| eval(a, mode))
+                .collect::<Result<Vec<f64>, CalcError>>()?;
+            let call = Ast::Call(
+                name.clone(),
+                values.iter().map(|v| Ast::Number(*v)).collect(),
+            );
+            check_finite(call_function(name, &values, mode)?, &call)
+        }
+    }
+}
+
+/// Evaluates an expression on its own: no variables beyond the constants,
+/// angles in radians and no output. Used by `--eval`; the interactive loop
+/// goes through `process_line`, which adds commands, assignments and history.
+fn evaluate(input: &str) -> Result<f64, CalcError> {
+    let ast = parse(input).map_err(CalcError::Parse)?;
+    let ast = resolve(ast, &HashMap::new())?;
+    eval(&fold(ast), AngleMode::Radians)
+}
+
+/// Passes finite values through; infinity and NaN become
+/// `CalcError::NonFinite` naming the operation `context` that produced them.
+fn check_finite(value: f64, context: &Ast) -> Result<f64, CalcError> {
+    if value.is_finite() {
+        return Ok(value);
+    }
+    let what = if value.is_nan() {
+        "is not a number"
+    } else {
+        "overflows to infinity"
+    };
+    Err(CalcError::NonFinite(format!(
+        "{} {}",
+        render(context),
+        what
+    )))
+}
+
+/// Binding strength of the operation at the root of `ast`, used by
+/// `render` to decide where parentheses are needed.
+fn precedence(ast: &Ast) -> u8 {
+    match ast {
+        Ast::Binary(BinOp::Add | BinOp::Subtract, _, _) => 1,
+        Ast::Binary(_, _, _) | Ast::Combo(_, _, _) => 2,
+        Ast::Negate(_) => 3,
+        Ast::Number(n) if *n < 0.0 => 3,
+        Ast::Factorial(_) => 4,
+        _ => 5,
+    }
+}
+
+/// Writes `ast` back as an expression with only the parentheses its
+/// structure needs.
+fn render(ast: &Ast) -> String {
+    let wrap = |child: &Ast, min: u8| {
+        let text = render(child);
+        if precedence(child) < min {
+            format!("({})", text)
+        } else {
+            text
+        }
+    };
+    match ast {
+        Ast::Number(n) if n.abs() >= 1e16 => format!("{:e}", n),
+        Ast::Number(n) => n.to_string(),
+        Ast::Variable(name) => name.clone(),
+        Ast::Negate(inner) => format!("-{}", wrap(inner, 4)),
+        Ast::Factorial(inner) => format!("{}!", wrap(inner, 4)),
+        Ast::Binary(op, left, right) => {
+            let symbol = match op {
+                BinOp::Add => "+",
+                BinOp::Subtract => "-",
+                BinOp::Multiply => "*",
+                BinOp::Divide => "/",
+            };
+            let p = precedence(ast);
+            format!("{} {} {}", wrap(left, p), symbol, wrap(right, p + 1))
+        }
+        Ast::Combo(op, left, right) => {
+            let name = match op {
+                ComboOp::Ncr => "ncr",
+                ComboOp::Npr => "npr",
+            };
+            format!("{} {} {}", wrap(left, 2), name, wrap(right, 3))
+        }
+        Ast::Call(name, args) => {
+            let args: Vec<String> = args.iter().map(render).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+    }
+}
+
+/// Evaluates the leftmost innermost operation whose operands are all
+/// numbers. Returns `None` once `ast` is a single number.
+fn reduce_step(ast: &Ast, mode: AngleMode) -> Result<Option<Ast>, CalcError> {
+    let is_number = |a: &Ast| matches!(a, Ast::Number(_));
+    let reduce = |child: &Ast| -> Result<Box<Ast>, CalcError> {
+        Ok(Box::new(
+            reduce_step(child, mode)?.unwrap_or_else(|| child.clone()),
+        ))
+    };
+    let reduced = match ast {
+        Ast::Number(_) => return Ok(None),
+        Ast::Negate(inner) if !is_number(inner) => Ast::Negate(reduce(inner)?),
+        Ast::Factorial(inner) if !is_number(inner) => Ast::Factorial(reduce(inner)?),
+        Ast::Binary(op, left, right) if !is_number(left) => {
+            Ast::Binary(*op, reduce(left)?, right.clone())
+        }
+        Ast::Binary(op, left, right) if !is_number(right) => {
+            Ast::Binary(*op, left.clone(), reduce(right)?)
+        }
+        Ast::Combo(op, left, right) if !is_number(left) => {
+            Ast::Combo(*op, reduce(left)?, right.clone())
+        }
+        Ast::Combo(op, left, right) if !is_number(right) => {
+            Ast::Combo(*op, left.clone(), reduce(right)?)
+        }
+        Ast::Call(name, args) if !args.iter().all(is_number) => {
+            let mut args = args.clone();
+            let i = args.iter().position(|a| !is_number(a)).unwrap_or(0);
+            args[i] = *reduce(&args[i])?;
+            Ast::Call(name.clone(), args)
+        }
+        _ => Ast::Number(eval(ast, mode)?),
+    };
+    Ok(Some(reduced))
+}
+
+/// The expression after each reduction step, starting with `ast` itself
+/// and ending with its value, e.g. `3 + 4 * 2`, `3 + 8`, `11`. Steps that
+/// render the same as the previous one (such as negating a literal) are
+/// skipped.
+fn explain_steps(ast: Ast, mode: AngleMode) -> Result<Vec<String>, CalcError> {
+    let mut steps = vec![render(&ast)];
+    let mut current = ast;
+    while let Some(next) = reduce_step(&current, mode)? {
+        let text = render(&next);
+        if steps.last() != Some(&text) {
+            steps.push(text);
+        }
+        current = next;
+    }
+    Ok(steps)
+}
+
+/// Converts an operand of `!`, `ncr` or `npr` to a non-negative integer.
+fn to_count(x: f64) -> Result<u64, CalcError> {
+    if x < 0.0 || x.fract() != 0.0 || !x.is_finite() {
+        return Err(CalcError::Domain(format!(
+            "factorial and combinatorics need non-negative integers, got {}",
+            x
+        )));
+    }
+    if x > u64::MAX as f64 {
+        return Err(CalcError::Overflow(format!("{} does not fit in u64", x)));
+    }
+    Ok(x as u64)
+}
+
+fn factorial(n: u64) -> Result<u64, CalcError> {
+    (2..=n).try_fold(1u64, |acc, i| {
+        acc.checked_mul(i)
+            .ok_or_else(|| CalcError::Overflow(format!("{}! does not fit in u64", n)))
+    })
+}
+
+/// n! / (n - k)!, or 0 when k > n.
+fn permutations(n: u64, k: u64) -> Result<u64, CalcError> {
+    if k > n {
+        return Ok(0);
+    }
+    (n - k + 1..=n).try_fold(1u64, |acc, i| {
+        acc.checked_mul(i)
+            .ok_or_else(|| CalcError::Overflow(format!("{} npr {} does not fit in u64", n, k)))
+    })
+}
+
+/// n! / (k! (n - k)!), or 0 when k > n. Each step stays an exact integer
+/// because the running product of i consecutive terms is divisible by i!.
+fn combinations(n: u64, k: u64) -> Result<u64, CalcError> {
+    if k > n {
+        return Ok(0);
+    }
+    let k = k.min(n - k);
+    let overflow = || CalcError::Overflow(format!("{} ncr {} does not fit in u64", n, k));
+    let mut result: u128 = 1;
+    for i in 1..=k as u128 {
+        result = result
+            .checked_mul(n as u128 - k as u128 + i)
+            .ok_or_else(overflow)?
+            / i;
+    }
+    u64::try_from(result).map_err(|_| overflow())
+}
+
+fn call_function(name: &str, args: &[f64], mode: AngleMode) -> Result<f64, CalcError> {
+    let x = args[0];
+    let unit_range = |x: f64| {
+        if (-1.0..=1.0).contains(&x) {
+            Ok(x)
+        } else {
+            Err(CalcError::Domain(format!(
+                "{} is only defined for -1 <= x <= 1, got {}",
+                name, x
+            )))
+        }
+    };
+    let value = match name {
+        "sin" => mode.to_radians(x).sin(),
+        "cos" => mode.to_radians(x).cos(),
+        "tan" => mode.to_radians(x).tan(),
+        "asin" => mode.radians_to_mode(unit_range(x)?.asin()),
+        "acos" => mode.radians_to_mode(unit_range(x)?.acos()),
+        "atan" => mode.radians_to_mode(x.atan()),
+        "atan2" => mode.radians_to_mode(x.atan2(args[1])),
+        "sinh" => x.sinh(),
+        "cosh" => x.cosh(),
+        "tanh" => x.tanh(),
+        _ => unreachable!("parser only accepts known functions"),
+    };
+    Ok(value)
+}
+
+/// User-facing strings, loaded from a `key=value` file per language. Keys
+/// missing from the file fall back to English.
+struct Messages {
+    table: HashMap<String, String>,
+}
+
+impl Messages {
+    fn english() -> Self {
+        Messages {
+            table: ENGLISH
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Parses `key=value` lines on top of the English defaults. Blank lines
+    /// and lines starting with `#` are ignored.
+    fn parse(content: &str) -> Self {
+        let mut messages = Messages::english();
+        for line in content.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                messages
+                    .table
+                    .insert(key.trim().to_string(), value.to_string());
+            }
+        }
+        messages
+    }
+
+    fn load(path: &str) -> io::Result<Self> {
+        Ok(Messages::parse(&fs::read_to_string(path)?))
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.table.get(key).map_or(key, |v| v.as_str())
+    }
+
+    fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut text = self.get(key).to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        text
+    }
+}
+
+fn prompt_text(mode: AngleMode, messages: &Messages) -> String {
+    messages.format("prompt", &[("mode", mode.label())])
+}
+
+/// Handles one line of REPL input (a command or an expression) and returns
+/// the text to show the user.
+fn process_line(line: &str, state: &mut CalcState, messages: &Messages) -> String {
+    if line == "status" {
+        return state.to_string();
+    }
+    if line == "history" {
+        if state.history.is_empty() {
+            return messages.get("history_empty").to_string();
+        }
+        let entries: Vec<String> = state
+            .history
+            .iter()
+            .enumerate()
+            .map(|(i, (input, value))| {
+                format!("{:>3}  {} = {}", i + 1, input, state.format_value(*value))
+            })
+            .collect();
+        return entries.join("\n");
+    }
+    if let Some(arg) = line.strip_prefix('!') {
+        let index = if arg == "!" {
+            Some(state.history.len())
+        } else {
+            arg.parse::<usize>().ok()
+        };
+        let entry = index
+            .filter(|i| *i >= 1)
+            .and_then(|i| state.history.get(i - 1));
+        return match entry {
+            Some((input, _)) => {
+                let input = input.clone();
+                format!("{}\n{}", input, process_line(&input, state, messages))
+            }
+            None if state.history.is_empty() => messages.get("history_empty").to_string(),
+            None => messages.format(
+                "history_range",
+                &[("index", arg), ("count", &state.history.len().to_string())],
+            ),
+        };
+    }
+    if line == "store" {
+        return match state.history.last() {
+            Some((_, value)) => {
+                state.memory = *value;
+                messages.format("stored", &[("value", &state.format_value(*value))])
+            }
+            None => messages.get("nothing_to_store").to_string(),
+        };
+    }
+    // Commands are matched on the whole first word so that variables such
+    // as `modest` or `basex` still reach the expression parser.
+    let (command, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if command == "mode" {
+        return match AngleMode::parse(arg.trim()) {
+            Some(m) => {
+                state.mode = m;
+                messages.format("mode_set", &[("mode", m.label())])
+            }
+            None => messages.get("mode_usage").to_string(),
+        };
+    }
+    if command == "precision" {
+        let precision = match arg.trim() {
+            "auto" => Some(None),
+            digits => digits.parse::<usize>().ok().filter(|p| *p <= 15).map(Some),
+        };
+        return match precision {
+            Some(p) => {
+                state.precision = p;
+                let label = p.map_or("auto".to_string(), |p| p.to_string());
+                messages.format("precision_set", &[("precision", &label)])
+            }
+            None => messages.get("precision_usage").to_string(),
+        };
+    }
+    if command == "explain" {
+        let explain = match arg.trim() {
+            "" => Some(!state.explain),
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        };
+        return match explain {
+            Some(on) => {
+                state.explain = on;
+                let label = if on { "on" } else { "off" };
+                messages.format("explain_set", &[("state", label)])
+            }
+            None => messages.get("explain_usage").to_string(),
+        };
+    }
+    if command == "base" {
+        return match arg.trim().parse::<u32>() {
+            Ok(base @ (2 | 8 | 10 | 16)) => {
+                state.base = base;
+                messages.format("base_set", &[("base", &base.to_string())])
+            }
+            _ => messages.get("base_usage").to_string(),
+        };
+    }
+    let (target, expr, offset) = match split_assignment(line) {
+        Some((name, expr, offset)) => (Some(name), expr, offset),
+        None => (None, line, 0),
+    };
+    if target.as_deref() == Some("ans") {
+        return messages.get("ans_reserved").to_string();
+    }
+    // A leading binary operator continues from the last result. A leading
+    // '-' is still a negative number.
+    let (expr, prefix) = if expr.trim_start().starts_with(['+', '*', '/']) {
+        (format!("ans {}", expr), "ans ".len())
+    } else {
+        (expr.to_string(), 0)
+    };
+    let ast = match parse(&expr) {
+        Ok(ast) => ast,
+        Err(mut e) => {
+            e.column = (e.column + offset).saturating_sub(prefix).max(1);
+            return format!(
+                "{}\n{:>width$}\n{}",
+                line,
+                "^",
+                messages.format("error", &[("error", &e.to_string())]),
+                width = e.column
+            );
+        }
+    };
+    let mut scope = state.variables.clone();
+    scope.insert("ans".to_string(), state.ans);
+    let resolved = resolve(ast, &scope);
+    let value = match resolved
+        .clone()
+        .and_then(|ast| eval(&fold(ast), state.mode))
+    {
+        Ok(value) => value,
+        Err(e) => return messages.format("error", &[("error", &e.to_string())]),
+    };
+    state.history.push((line.to_string(), value));
+    state.ans = value;
+    let shown = state.format_value(value);
+    let steps = match resolved {
+        Ok(ast) if state.explain => explain_steps(ast, state.mode).unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    let explanation: String = steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            if i == 0 {
+                format!("  {}\n", step)
             } else {
-                a / b
+                format!("= {}\n", step)
             }
+        })
+        .collect();
+    match target {
+        Some(name) => {
+            let mut out = messages.format("assigned", &[("name", &name), ("value", &shown)]);
+            if CONSTANTS.iter().any(|(c, _)| *c == name) {
+                out.push('\n');
+                out.push_str(&messages.format("shadow_warning", &[("name", &name)]));
+            }
+            state.variables.insert(name, value);
+            explanation + &out
+        }
+        None => explanation + &messages.format("result", &[("value", &shown)]),
+    }
+}
+
+/// Reads `--lang <code>` from the arguments and loads `lang/<code>.txt`,
+/// falling back to English if the flag is absent or the file is unreadable.
+fn messages_from_args(args: &[String]) -> Messages {
+    let lang = args
+        .iter()
+        .position(|a| a == "--lang")
+        .and_then(|i| args.get(i + 1));
+    match lang {
+        Some(code) => {
+            let path = format!("lang/{}.txt", code);
+            Messages::load(&path).unwrap_or_else(|e| {
+                eprintln!("Could not load {}: {}. Using English.", path, e);
+                Messages::english()
+            })
+        }
+        None => Messages::english(),
+    }
+}
+
+/// Reads one line, returning `None` at end of input or on a read error so
+/// callers can stop cleanly instead of panicking.
+fn read_line<R: BufRead>(input: &mut R) -> Option<String> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
+    }
+}
+
+/// Runs the REPL until `quit`/`exit` or end of input.
+fn run<R: BufRead, W: Write>(mut input: R, out: &mut W, messages: &Messages) -> io::Result<()> {
+    writeln!(out, "{}", messages.get("welcome"))?;
+    let mut state = CalcState::new();
+    loop {
+        write!(out, "{}", prompt_text(state.mode, messages))?;
+        out.flush()?;
+        let Some(line) = read_line(&mut input) else {
+            writeln!(out)?;
+            return Ok(());
+        };
+        match line.as_str() {
+            "" => continue,
+            "quit" | "exit" => return Ok(()),
+            _ => writeln!(out, "{}", process_line(&line, &mut state, messages))?,
         }
     }
 }
 
 fn main() {
-    println!("Enter the first number:");
-    let mut input1 = String::new();
-    io::stdin().read_line(&mut input1).expect("Failed to read line");
-    let num1: f64 = input1.trim().parse().expect("Please enter a valid number");
-
-    println!("Enter the operation (+, -, *, /):");
-    let mut op_input = String::new();
-    io::stdin().read_line(&mut op_input).expect("Failed to read line");
-    let op = op_input.trim();
-
-    println!("Enter the second number:");
-    let mut input2 = String::new();
-    io::stdin().read_line(&mut input2).expect("Failed to read line");
-    let num2: f64 = input2.trim().parse().expect("Please enter a valid number");
-
-    let operation = match op {
-        "+" => Operation::Add(num1, num2),
-        "-" => Operation::Subtract(num1, num2),
-        "*" => Operation::Multiply(num1, num2),
-        "/" => Operation::Divide(num1, num2),
-        _ => {
-            println!("Invalid operation entered.");
-            return;
-        }
-    };
-    let result = calculate(operation);
-    println!("Result: {}", result);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let messages = messages_from_args(&args);
+    if let Some(i) = args.iter().position(|a| a == "--eval") {
+        match args.get(i + 1).map(|expr| evaluate(expr)) {
+            Some(Ok(value)) => println!("{}", value),
+            Some(Err(e)) => {
+                eprintln!("{}", messages.format("error", &[("error", &e.to_string())]));
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("Usage: calculator --eval <expression>");
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    if let Err(e) = run(stdin.lock(), &mut stdout, &messages) {
+        eprintln!("I/O error: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_reports_column() {
+        let err = parse("(1 + 2))").unwrap_err();
+        assert_eq!(err.column, 8);
+        assert_eq!(err.to_string(), "unexpected ')' at column 8");
+
+        let err = parse("2 * (3 + )").unwrap_err();
+        assert_eq!(err.column, 10);
+
+        let err = parse("4 +").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected end of input at column 4");
+    }
+
+    #[test]
+    fn folding_collapses_numeric_subtrees() {
+        let ast = parse("2+3").unwrap();
+        assert!(matches!(ast, Ast::Binary(BinOp::Add, _, _)));
+        assert_eq!(fold(ast), Ast::Number(5.0));
+        let ast = fold(parse("2 + 3 * -(4 - 1)").unwrap());
+        assert_eq!(eval(&ast, AngleMode::Radians), Ok(-7.0));
+    }
+
+    #[test]
+    fn angle_mode_changes_trig_results() {
+        let ast = parse("sin(90)").unwrap();
+        assert!((eval(&ast, AngleMode::Degrees).unwrap() - 1.0).abs() < 1e-12);
+        assert!((eval(&ast, AngleMode::Radians).unwrap() - 90f64.sin()).abs() < 1e-12);
+        let err = parse("foo(1)").unwrap_err();
+        assert_eq!(err.to_string(), "unknown function 'foo' at column 1");
+    }
+
+    #[test]
+    fn mode_command_switches_state() {
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        assert_eq!(prompt_text(state.mode, &messages), "[RAD] > ");
+        let out = process_line("mode deg", &mut state, &messages);
+        assert_eq!(out, "Angle mode set to DEG");
+        assert_eq!(state.mode, AngleMode::Degrees);
+        assert_eq!(prompt_text(state.mode, &messages), "[DEG] > ");
+        assert_eq!(
+            process_line("cos(180)", &mut state, &messages),
+            "Result: -1"
+        );
+        process_line("mode bogus", &mut state, &messages);
+        assert_eq!(state.mode, AngleMode::Degrees);
+    }
+
+    #[test]
+    fn french_messages_replace_prompt() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/lang/fr.txt");
+        let messages = Messages::load(path).unwrap();
+        let prompt = prompt_text(AngleMode::Degrees, &messages);
+        assert_eq!(prompt, "[DEG] calcul > ");
+        let mut state = CalcState::new();
+        let out = process_line("1 + 1", &mut state, &messages);
+        assert_eq!(out, "Résultat : 2");
+    }
+
+    #[test]
+    fn missing_message_keys_fall_back_to_english() {
+        let messages = Messages::parse("# partial\nresult=Ergebnis: {value}\n");
+        assert_eq!(messages.format("result", &[("value", "3")]), "Ergebnis: 3");
+        assert_eq!(messages.get("mode_usage"), "Usage: mode deg | mode rad");
+    }
+
+    #[test]
+    fn read_line_returns_none_at_eof() {
+        let mut input = io::Cursor::new("  7 \n");
+        assert_eq!(read_line(&mut input), Some("7".to_string()));
+        assert_eq!(read_line(&mut input), None);
+    }
+
+    #[test]
+    fn run_recovers_from_bad_input_and_stops_at_eof() {
+        let input = io::Cursor::new("1 + 1\n2 * abc(1)\n3 * 3\n");
+        let mut out = Vec::new();
+        run(input, &mut out, &Messages::english()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Result: 2"));
+        assert!(out.contains("Error: unknown function 'abc' at column 5"));
+        assert!(out.contains("Result: 9"));
+    }
+
+    #[test]
+    fn inverse_trig_respects_angle_mode() {
+        let deg = AngleMode::Degrees;
+        let asin = eval(&parse("asin(1)").unwrap(), deg).unwrap();
+        assert!((asin - 90.0).abs() < 1e-9);
+        let atan2 = eval(&parse("atan2(1, 1)").unwrap(), deg).unwrap();
+        assert!((atan2 - 45.0).abs() < 1e-9);
+        let rad = eval(&parse("atan2(1, 1)").unwrap(), AngleMode::Radians).unwrap();
+        assert!((rad - std::f64::consts::FRAC_PI_4).abs() < 1e-12);
+        assert_eq!(eval(&parse("tanh(0)").unwrap(), deg), Ok(0.0));
+    }
+
+    #[test]
+    fn inverse_trig_domain_and_arity_errors() {
+        let err = eval(&parse("acos(2)").unwrap(), AngleMode::Radians).unwrap_err();
+        assert!(matches!(err, CalcError::Domain(_)));
+        let err = parse("atan2(1)").unwrap_err();
+        assert_eq!(err.message, "atan2 takes 2 argument(s), got 1");
+    }
+
+    #[test]
+    fn status_reports_every_state_field() {
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        for line in [
+            "mode deg",
+            "precision 3",
+            "2 * 21",
+            "store",
+            "base 16",
+            "1 / 4",
+        ] {
+            process_line(line, &mut state, &messages);
+        }
+        let status = process_line("status", &mut state, &messages);
+        assert!(status.contains("Precision:  3"));
+        assert!(status.contains("Angle mode: DEG"));
+        assert!(status.contains("Base:       16"));
+        assert!(status.contains("Memory:     0x2A"));
+        assert!(status.contains("History:    2 entries"));
+        assert_eq!(state.format_value(0.25), "0.250");
+    }
+
+    #[test]
+    fn assignment_errors_point_into_the_expression() {
+        let mut state = CalcState::new();
+        let out = process_line("x = 1 +", &mut state, &Messages::english());
+        assert!(out.ends_with("unexpected end of input at column 8"));
+        assert_eq!(out.lines().nth(1), Some("       ^"));
+    }
+
+    #[test]
+    fn constants_resolve_and_can_be_shadowed() {
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        let tau = resolve(parse("pi * 2").unwrap(), &state.variables).unwrap();
+        assert_eq!(eval(&fold(tau), state.mode), Ok(std::f64::consts::TAU));
+
+        let out = process_line("pi = 3", &mut state, &messages);
+        assert_eq!(
+            out,
+            "pi = 3\nWarning: 'pi' now shadows the built-in constant"
+        );
+        assert_eq!(process_line("pi * 2", &mut state, &messages), "Result: 6");
+        assert_eq!(
+            process_line("x + 1", &mut state, &messages),
+            "Error: unknown variable 'x'"
+        );
+    }
+
+    #[test]
+    fn factorial_and_combinatorics() {
+        let run = |input: &str| eval(&fold(parse(input).unwrap()), AngleMode::Radians);
+        assert_eq!(run("5!"), Ok(120.0));
+        assert_eq!(run("5 nCr 2"), Ok(10.0));
+        assert_eq!(run("5 nPr 2"), Ok(20.0));
+        assert_eq!(run("2 * 3! + 1"), Ok(13.0));
+        assert!(matches!(run("(-3)!"), Err(CalcError::Domain(_))));
+        assert!(matches!(run("2.5 ncr 1"), Err(CalcError::Domain(_))));
+        assert!(matches!(run("21!"), Err(CalcError::Overflow(_))));
+    }
+
+    #[test]
+    fn bang_commands_rerun_history() {
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        assert_eq!(
+            process_line("!!", &mut state, &messages),
+            "History is empty"
+        );
+        process_line("x = 2", &mut state, &messages);
+        process_line("x * 10", &mut state, &messages);
+        process_line("x = 5", &mut state, &messages);
+
+        assert_eq!(
+            process_line("!2", &mut state, &messages),
+            "x * 10\nResult: 50"
+        );
+        assert_eq!(
+            process_line("!!", &mut state, &messages),
+            "x * 10\nResult: 50"
+        );
+        assert_eq!(process_line("!1", &mut state, &messages), "x = 2\nx = 2");
+        assert_eq!(state.history.len(), 6);
+        assert_eq!(
+            process_line("!9", &mut state, &messages),
+            "No history entry 9 (history has 6 entries)"
+        );
+        assert!(process_line("!0", &mut state, &messages).starts_with("No history entry 0"));
+    }
+
+    #[test]
+    fn explain_shows_reduction_steps() {
+        let steps = |input: &str| explain_steps(parse(input).unwrap(), AngleMode::Radians).unwrap();
+        assert_eq!(steps("3 + 4 * 2"), vec!["3 + 4 * 2", "3 + 8", "11"]);
+        assert_eq!(
+            steps("(1 + 2) * (10 - 3)"),
+            vec!["(1 + 2) * (10 - 3)", "3 * (10 - 3)", "3 * 7", "21"]
+        );
+        assert_eq!(steps("-3! + 1"), vec!["-3! + 1", "-6 + 1", "-5"]);
+        assert_eq!(steps("2 - (3 - 1)"), vec!["2 - (3 - 1)", "2 - 2", "0"]);
+
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        assert_eq!(
+            process_line("explain", &mut state, &messages),
+            "Explain mode on"
+        );
+        assert_eq!(
+            process_line("3 + 4 * 2", &mut state, &messages),
+            "  3 + 4 * 2\n= 3 + 8\n= 11\nResult: 11"
+        );
+        process_line("explain off", &mut state, &messages);
+        assert_eq!(
+            process_line("3 + 4 * 2", &mut state, &messages),
+            "Result: 11"
+        );
+    }
+
+    #[test]
+    fn non_finite_results_are_errors() {
+        let eval_line = |input: &str| eval(&fold(parse(input).unwrap()), AngleMode::Radians);
+        assert_eq!(
+            eval_line("1e308 * 10"),
+            Err(CalcError::NonFinite(
+                "1e308 * 10 overflows to infinity".to_string()
+            ))
+        );
+        assert_eq!(
+            eval_line("1e308 * 10 - 1e308 * 10"),
+            Err(CalcError::NonFinite(
+                "1e308 * 10 overflows to infinity".to_string()
+            ))
+        );
+        assert!(matches!(
+            eval_line("sinh(1000)"),
+            Err(CalcError::NonFinite(_))
+        ));
+        assert_eq!(eval_line("2.5e-1 * 4"), Ok(1.0));
+        assert!(parse("1e400").is_err());
+
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        let out = process_line("1e308 * 10", &mut state, &messages);
+        assert!(out.starts_with("Error: result is not a finite number"));
+        assert!(out.ends_with("overflows to infinity"), "{}", out);
+        assert!(state.history.is_empty());
+    }
+
+    #[test]
+    fn evaluate_applies_each_operation() {
+        assert_eq!(evaluate("7 + 5"), Ok(12.0));
+        assert_eq!(evaluate("7 - 5"), Ok(2.0));
+        assert_eq!(evaluate("7 * 5"), Ok(35.0));
+        assert_eq!(evaluate("7 / 2"), Ok(3.5));
+        assert_eq!(evaluate("-3 * (2 + 4)"), Ok(-18.0));
+        assert_eq!(evaluate("2 * pi"), Ok(std::f64::consts::TAU));
+    }
+
+    #[test]
+    fn evaluate_reports_errors() {
+        assert_eq!(evaluate("1 / 0"), Err(CalcError::DivisionByZero));
+        assert_eq!(evaluate("1 / (2 - 2)"), Err(CalcError::DivisionByZero));
+        assert_eq!(evaluate("0.0 / 0.0"), Err(CalcError::DivisionByZero));
+        let parse_column = |input: &str| match evaluate(input) {
+            Err(CalcError::Parse(e)) => e.column,
+            other => panic!("expected a parse error for {}, got {:?}", input, other),
+        };
+        assert_eq!(parse_column("2 % 3"), 3);
+        assert_eq!(parse_column("2 ^^ 3"), 3);
+        assert_eq!(parse_column("1.2.3 + 1"), 1);
+        assert_eq!(parse_column("4 +"), 4);
+        assert_eq!(
+            evaluate("x + 1"),
+            Err(CalcError::UnknownVariable("x".into()))
+        );
+
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        assert_eq!(
+            process_line("8 / 0", &mut state, &messages),
+            "Error: division by zero"
+        );
+    }
+
+    #[test]
+    fn scientific_notation_literals() {
+        assert_eq!(evaluate("1.5e3 + 1"), Ok(1501.0));
+        assert_eq!(evaluate("2.2E-4 * 1e4"), Ok(2.2));
+        assert_eq!(evaluate("1E+2"), Ok(100.0));
+        assert_eq!(evaluate("-4e0"), Ok(-4.0));
+        assert_eq!(evaluate(".5e1"), Ok(5.0));
+        assert_eq!(evaluate("atan2(0, 1e2)"), Ok(0.0));
+
+        let error = |input: &str| match evaluate(input) {
+            Err(CalcError::Parse(e)) => e,
+            other => panic!("expected a parse error for {}, got {:?}", input, other),
+        };
+        let e = error("1.5e");
+        assert_eq!(e.message, "missing exponent digits in '1.5e'");
+        assert_eq!(e.column, 4);
+        assert_eq!(
+            error("2 * 3e- 1").message,
+            "missing exponent digits in '3e-'"
+        );
+        assert_eq!(error("1e400").message, "number '1e400' is out of range");
+
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        state.variables.insert("x".into(), 2.0);
+        assert_eq!(
+            process_line("y = x * 1.5e3", &mut state, &messages),
+            "y = 3000"
+        );
+    }
+
+    #[test]
+    fn ans_chains_results() {
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        assert_eq!(state.ans, 0.0);
+        assert_eq!(process_line("ans + 1", &mut state, &messages), "Result: 1");
+        assert_eq!(process_line("5*2", &mut state, &messages), "Result: 10");
+        assert_eq!(process_line("+ 3", &mut state, &messages), "Result: 13");
+        assert_eq!(
+            process_line("ans / 2", &mut state, &messages),
+            "Result: 6.5"
+        );
+        assert_eq!(process_line("x = ans * 2", &mut state, &messages), "x = 13");
+        assert_eq!(process_line("-1", &mut state, &messages), "Result: -1");
+        assert!(state.to_string().contains("Ans:        -1"));
+
+        // Errors leave ans alone and point into what was typed.
+        let out = process_line("* (2 +", &mut state, &messages);
+        assert!(
+            out.ends_with("unexpected end of input at column 7"),
+            "{}",
+            out
+        );
+        assert_eq!(state.ans, -1.0);
+
+        // 'ans' cannot be taken over by a variable.
+        assert_eq!(
+            process_line("ans = 100", &mut state, &messages),
+            "'ans' always holds the last result and cannot be assigned"
+        );
+        assert!(!state.variables.contains_key("ans"));
+        assert_eq!(process_line("ans * 3", &mut state, &messages), "Result: -3");
+    }
+
+    #[test]
+    fn command_words_do_not_capture_variable_names() {
+        let messages = Messages::english();
+        let mut state = CalcState::new();
+        assert_eq!(
+            process_line("modest = 2", &mut state, &messages),
+            "modest = 2"
+        );
+        assert_eq!(
+            process_line("basex = 3", &mut state, &messages),
+            "basex = 3"
+        );
+        assert_eq!(
+            process_line("basex + 1", &mut state, &messages),
+            "Result: 4"
+        );
+        assert_eq!(
+            process_line("modest * basex", &mut state, &messages),
+            "Result: 6"
+        );
+        assert_eq!(state.base, 10);
+        assert_eq!(
+            process_line("base 16", &mut state, &messages),
+            messages.format("base_set", &[("base", "16")])
+        );
+    }
 }
@@ -1,9 +1,95 @@
-use std::io;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Player {
     name: String,
     position: String,
+    age: u8,
+    rating: u8,
+    #[serde(default = "default_available")]
+    available: bool,
+    #[serde(default)]
+    injury_note: String,
+    #[serde(default)]
+    team: String,
+    #[serde(default)]
+    pace: u8,
+    #[serde(default)]
+    shooting: u8,
+    #[serde(default)]
+    passing: u8,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+/// Label for a rating band: 90+ is world class, then one tier per ten
+/// points down to 60.
+fn rating_tier(rating: u8) -> &'static str {
+    match rating {
+        90.. => "World Class",
+        80..=89 => "Great",
+        70..=79 => "Good",
+        60..=69 => "Average",
+        _ => "Developing",
+    }
+}
+
+impl Player {
+    fn new(name: &str, position: &str, age: u8, rating: u8) -> Self {
+        Player {
+            name: name.to_string(),
+            position: position.to_string(),
+            age,
+            rating,
+            available: true,
+            injury_note: String::new(),
+            team: String::new(),
+            pace: 0,
+            shooting: 0,
+            passing: 0,
+        }
+    }
+
+    fn with_team(mut self, team: &str) -> Self {
+        self.team = team.to_string();
+        self
+    }
+
+    fn with_attributes(mut self, pace: u8, shooting: u8, passing: u8) -> Self {
+        self.pace = pace;
+        self.shooting = shooting;
+        self.passing = passing;
+        self
+    }
+
+    fn summary(&self) -> String {
+        if self.available {
+            format!("{} - {}", self.name, self.position)
+        } else if self.injury_note.is_empty() {
+            format!("{} - {} (unavailable)", self.name, self.position)
+        } else {
+            format!(
+                "{} - {} (unavailable: {})",
+                self.name, self.position, self.injury_note
+            )
+        }
+    }
+
+    /// `summary` followed by the rating and its tier, for player lists.
+    fn listing(&self) -> String {
+        format!(
+            "{} [{} {}]",
+            self.summary(),
+            self.rating,
+            rating_tier(self.rating)
+        )
+    }
 }
 
 struct FilterCondition<F>
@@ -24,6 +110,67 @@ where
     }
 }
 
+/// Chainable constraints on a player, all of which must hold, compiled into
+/// a single [`FilterCondition`] by [`PlayerQuery::build`].
+#[derive(Default)]
+struct PlayerQuery {
+    constraints: Vec<Constraint>,
+}
+
+type Constraint = Box<dyn Fn(&Player) -> bool>;
+
+/// A `PlayerQuery` method taking a numeric threshold, e.g. `min_pace`.
+type NumericConstraint = fn(PlayerQuery, u8) -> PlayerQuery;
+
+impl PlayerQuery {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with(mut self, constraint: impl Fn(&Player) -> bool + 'static) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
+
+    /// Players in `position` or one of its aliases, see [`plays_position`].
+    fn position(self, position: &str) -> Self {
+        let position = position.to_string();
+        self.with(move |p| plays_position(p, &position))
+    }
+
+    /// Players in a line from [`LINE_WEIGHTS`], e.g. "Attack".
+    fn line(self, line: &str) -> Self {
+        let line = line.to_string();
+        self.with(move |p| line_of(&p.position).eq_ignore_ascii_case(&line))
+    }
+
+    fn younger_than(self, age: u8) -> Self {
+        self.with(move |p| p.age < age)
+    }
+
+    fn min_rating(self, rating: u8) -> Self {
+        self.with(move |p| p.rating >= rating)
+    }
+
+    fn min_pace(self, pace: u8) -> Self {
+        self.with(move |p| p.pace >= pace)
+    }
+
+    fn min_shooting(self, shooting: u8) -> Self {
+        self.with(move |p| p.shooting >= shooting)
+    }
+
+    fn min_passing(self, passing: u8) -> Self {
+        self.with(move |p| p.passing >= passing)
+    }
+
+    fn build(self) -> FilterCondition<impl Fn(&Player) -> bool> {
+        FilterCondition {
+            condition: move |p: &Player| self.constraints.iter().all(|c| c(p)),
+        }
+    }
+}
+
 fn custom_filter<F>(collection: &[Player], filter: &FilterCondition<F>) -> Vec<Player>
 where
     F: Fn(&Player) -> bool,
@@ -35,41 +182,693 @@ where
         .collect()
 }
 
-fn main() {
-    let players = vec![
-        Player { name: "Neuer".to_string(), position: "GK".to_string() },
-        Player { name: "Ramos".to_string(), position: "CB".to_string() },
-        Player { name: "Modric".to_string(), position: "CMF".to_string() },
-        Player { name: "De Bruyne".to_string(), position: "AMF".to_string() },
-        Player { name: "Haaland".to_string(), position: "CF".to_string() },
-        Player { name: "Kane".to_string(), position: "CF".to_string() },
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Age,
+    Rating,
+    Pace,
+    Shooting,
+    Passing,
+}
+
+impl SortKey {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "name" => Some(SortKey::Name),
+            "age" => Some(SortKey::Age),
+            "rating" => Some(SortKey::Rating),
+            "pace" => Some(SortKey::Pace),
+            "shooting" => Some(SortKey::Shooting),
+            "passing" => Some(SortKey::Passing),
+            _ => None,
+        }
+    }
+
+    fn compare(self, a: &Player, b: &Player) -> Ordering {
+        match self {
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Age => a.age.cmp(&b.age),
+            SortKey::Rating => a.rating.cmp(&b.rating),
+            SortKey::Pace => a.pace.cmp(&b.pace),
+            SortKey::Shooting => a.shooting.cmp(&b.shooting),
+            SortKey::Passing => a.passing.cmp(&b.passing),
+        }
+    }
+}
+
+/// Stable sort by each (key, descending) pair in turn; later keys only
+/// break ties left by earlier ones. No keys leaves the order as it is.
+fn sort_players_multi(players: &mut [Player], keys: &[(SortKey, bool)]) {
+    players.sort_by(|a, b| {
+        keys.iter()
+            .fold(Ordering::Equal, |order, &(key, descending)| {
+                order.then_with(|| {
+                    let o = key.compare(a, b);
+                    if descending { o.reverse() } else { o }
+                })
+            })
+    });
+}
+
+/// Parses a sort spec like "rating desc, age" into sort keys; a key is
+/// ascending unless followed by "desc".
+fn parse_sort_keys(spec: &str) -> Result<Vec<(SortKey, bool)>, String> {
+    spec.split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| {
+            let words: Vec<&str> = part.split_whitespace().collect();
+            let key = SortKey::parse(words[0])
+                .ok_or_else(|| format!("Unknown sort key '{}'", words[0]))?;
+            match words[1..] {
+                [] => Ok((key, false)),
+                [dir] if dir.eq_ignore_ascii_case("asc") => Ok((key, false)),
+                [dir] if dir.eq_ignore_ascii_case("desc") => Ok((key, true)),
+                _ => Err(format!("Expected 'asc' or 'desc' after {}", words[0])),
+            }
+        })
+        .collect()
+}
+
+fn only_available(players: &[Player]) -> Vec<&Player> {
+    players.iter().filter(|p| p.available).collect()
+}
+
+fn find_player<'a>(players: &'a [Player], name: &str) -> Option<&'a Player> {
+    players.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+fn find_player_mut<'a>(players: &'a mut [Player], name: &str) -> Option<&'a mut Player> {
+    players
+        .iter_mut()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Side-by-side table of two players. For each numeric attribute the higher
+/// value is marked with `*`; equal values are both marked with `=`.
+fn compare_players(a: &Player, b: &Player) -> String {
+    let mut out = format!("{:<10} {:<16} {:<16}\n", "", a.name, b.name);
+    out.push_str(&format!(
+        "{:<10} {:<16} {:<16}\n",
+        "Position", a.position, b.position
+    ));
+    out.push_str(&format!("{:<10} {:<16} {:<16}\n", "Team", a.team, b.team));
+    let numeric = [
+        ("Age", a.age, b.age),
+        ("Rating", a.rating, b.rating),
+        ("Pace", a.pace, b.pace),
+        ("Shooting", a.shooting, b.shooting),
+        ("Passing", a.passing, b.passing),
     ];
+    for (label, x, y) in numeric {
+        let (mark_x, mark_y) = match x.cmp(&y) {
+            std::cmp::Ordering::Greater => ("*", ""),
+            std::cmp::Ordering::Less => ("", "*"),
+            std::cmp::Ordering::Equal => ("=", "="),
+        };
+        out.push_str(&format!(
+            "{:<10} {:<16} {:<16}\n",
+            label,
+            format!("{}{}", x, mark_x),
+            format!("{}{}", y, mark_y)
+        ));
+    }
+    out
+}
+
+/// Squad size, average rating and the position with the best average
+/// rating (ties go to the alphabetically first), for one team.
+fn team_profile<'a>(players: &'a [Player], team: &str) -> Option<(usize, f64, &'a str, f64)> {
+    let squad: Vec<&Player> = players
+        .iter()
+        .filter(|p| p.team.eq_ignore_ascii_case(team))
+        .collect();
+    if squad.is_empty() {
+        return None;
+    }
+    let average = |members: &[&Player]| {
+        members.iter().map(|p| f64::from(p.rating)).sum::<f64>() / members.len() as f64
+    };
+    let mut positions: Vec<&str> = squad.iter().map(|p| p.position.as_str()).collect();
+    positions.sort_unstable();
+    positions.dedup();
+    let (best_position, best_average) = positions
+        .into_iter()
+        .map(|pos| {
+            let members: Vec<&Player> = squad
+                .iter()
+                .copied()
+                .filter(|p| p.position == pos)
+                .collect();
+            (pos, average(&members))
+        })
+        .fold(("", f64::MIN), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+    Some((squad.len(), average(&squad), best_position, best_average))
+}
+
+/// Side-by-side squad size, average rating and strongest position of two
+/// teams. Errors if either team has no players.
+fn compare_teams(players: &[Player], team_a: &str, team_b: &str) -> Result<String, String> {
+    let profile =
+        |team: &str| team_profile(players, team).ok_or_else(|| format!("unknown team '{}'", team));
+    let (size_a, avg_a, pos_a, pos_avg_a) = profile(team_a)?;
+    let (size_b, avg_b, pos_b, pos_avg_b) = profile(team_b)?;
+    let mut out = format!("{:<12} {:<16} {:<16}\n", "", team_a, team_b);
+    out.push_str(&format!(
+        "{:<12} {:<16} {:<16}\n",
+        "Squad size", size_a, size_b
+    ));
+    out.push_str(&format!(
+        "{:<12} {:<16} {:<16}\n",
+        "Avg rating",
+        format!("{:.1}", avg_a),
+        format!("{:.1}", avg_b)
+    ));
+    out.push_str(&format!(
+        "{:<12} {:<16} {:<16}\n",
+        "Strongest",
+        format!("{} ({:.1})", pos_a, pos_avg_a),
+        format!("{} ({:.1})", pos_b, pos_avg_b)
+    ));
+    Ok(out)
+}
+
+/// Weight applied to a player's rating in [`team_strength`], by line.
+const LINE_WEIGHTS: [(&str, f64); 4] = [
+    ("Goalkeeper", 1.0),
+    ("Defence", 1.2),
+    ("Midfield", 1.0),
+    ("Attack", 1.3),
+];
+
+/// Canonical position codes and the other names users type for them.
+const POSITION_ALIASES: &[(&str, &[&str])] = &[
+    ("GK", &["goalkeeper", "keeper", "goalie"]),
+    ("CB", &["centre back", "center back", "defender"]),
+    ("LB", &["left back"]),
+    ("RB", &["right back"]),
+    ("LWB", &["left wing back"]),
+    ("RWB", &["right wing back"]),
+    ("DMF", &["dm", "cdm", "defensive midfielder"]),
+    ("CMF", &["cm", "midfielder", "central midfielder"]),
+    ("AMF", &["am", "cam", "attacking midfielder", "playmaker"]),
+    ("LMF", &["lm", "left midfielder"]),
+    ("RMF", &["rm", "right midfielder"]),
+    ("LWF", &["lw", "left winger"]),
+    ("RWF", &["rw", "right winger"]),
+    ("SS", &["second striker"]),
+    (
+        "CF",
+        &[
+            "st",
+            "striker",
+            "centre forward",
+            "center forward",
+            "forward",
+        ],
+    ),
+];
+
+/// The canonical code for a position or one of its aliases, ignoring case,
+/// hyphens and extra spaces: "striker", "st" and "CF" all give "CF".
+fn normalize_position(input: &str) -> Option<String> {
+    let key = input
+        .replace('-', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    POSITION_ALIASES
+        .iter()
+        .find(|(code, aliases)| code.eq_ignore_ascii_case(&key) || aliases.contains(&key.as_str()))
+        .map(|(code, _)| code.to_string())
+}
+
+/// Whether `player` plays `position`, comparing canonical codes so that a
+/// player stored as "ST" matches "CF" and "striker". Positions outside
+/// [`POSITION_ALIASES`] are compared as written, ignoring case.
+fn plays_position(player: &Player, position: &str) -> bool {
+    match (
+        normalize_position(&player.position),
+        normalize_position(position),
+    ) {
+        (Some(stored), Some(wanted)) => stored == wanted,
+        _ => player.position.eq_ignore_ascii_case(position),
+    }
+}
+
+fn unknown_position(input: &str) -> String {
+    format!(
+        "Unknown position '{}'. Use a code such as GK, CB, CMF, AMF or CF, or a name like striker.",
+        input
+    )
+}
+
+fn line_of(position: &str) -> &'static str {
+    match position.to_uppercase().as_str() {
+        "GK" => "Goalkeeper",
+        "CB" | "LB" | "RB" | "LWB" | "RWB" => "Defence",
+        "CF" | "ST" | "LWF" | "RWF" | "SS" => "Attack",
+        _ => "Midfield",
+    }
+}
+
+fn line_weight(position: &str) -> f64 {
+    let line = line_of(position);
+    LINE_WEIGHTS
+        .iter()
+        .find(|(name, _)| *name == line)
+        .map_or(1.0, |(_, w)| *w)
+}
+
+/// Players on the pitch in [`best_eleven`].
+const STARTING_SIZE: usize = 11;
+
+/// The strongest line-up from the available players: the best-rated
+/// goalkeeper plus the highest-rated outfielders, up to eleven in total.
+fn best_eleven(players: &[Player]) -> Vec<&Player> {
+    let mut available = only_available(players);
+    available.sort_by_key(|p| std::cmp::Reverse(p.rating));
+    let keeper = available
+        .iter()
+        .position(|p| line_of(&p.position) == "Goalkeeper");
+    let mut eleven: Vec<&Player> = keeper.map(|i| available.remove(i)).into_iter().collect();
+    eleven.extend(
+        available
+            .into_iter()
+            .filter(|p| line_of(&p.position) != "Goalkeeper")
+            .take(STARTING_SIZE - eleven.len()),
+    );
+    eleven
+}
+
+/// Weighted average of the [`best_eleven`] ratings, where each rating
+/// counts by its line's weight. Returns 0 when nobody is available.
+fn team_strength(players: &[Player]) -> f64 {
+    let eleven = best_eleven(players);
+    let total_weight: f64 = eleven.iter().map(|p| line_weight(&p.position)).sum();
+    if total_weight == 0.0 {
+        return 0.0;
+    }
+    let weighted: f64 = eleven
+        .iter()
+        .map(|p| line_weight(&p.position) * f64::from(p.rating))
+        .sum();
+    weighted / total_weight
+}
+
+/// Goals an average side scores against an equal opponent in
+/// [`simulate_match`].
+const MATCH_BASE_GOALS: f64 = 1.4;
+/// Most goals [`simulate_match`] gives one side.
+const MATCH_MAX_GOALS: u8 = 9;
+
+/// Small deterministic random generator (SplitMix64) so simulations can be
+/// replayed from a seed without extra dependencies.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Poisson-distributed count with mean `lambda`, capped at `max`.
+    fn poisson(&mut self, lambda: f64, max: u8) -> u8 {
+        let limit = (-lambda).exp();
+        let mut product = self.next_f64();
+        let mut count = 0;
+        while product > limit && count < max {
+            count += 1;
+            product *= self.next_f64();
+        }
+        count
+    }
+}
+
+/// Plays a match as two Poisson goal counts. Each side's expected goals is
+/// [`MATCH_BASE_GOALS`] scaled by the cube of its strength relative to the
+/// opponent's, so stronger squads win more often. The same seed always
+/// gives the same score.
+fn simulate_match(team_a: &[Player], team_b: &[Player], seed: u64) -> (u8, u8) {
+    let (a, b) = (team_strength(team_a), team_strength(team_b));
+    if a == 0.0 || b == 0.0 {
+        return (0, 0);
+    }
+    let mut rng = SplitMix64(seed);
+    let goals_a = rng.poisson(MATCH_BASE_GOALS * (a / b).powi(3), MATCH_MAX_GOALS);
+    let goals_b = rng.poisson(MATCH_BASE_GOALS * (b / a).powi(3), MATCH_MAX_GOALS);
+    (goals_a, goals_b)
+}
+
+/// Market value multiplier in [`estimate_value`], by line: attackers cost
+/// the most.
+const MARKET_LINE_FACTORS: [(&str, f64); 4] = [
+    ("Goalkeeper", 0.6),
+    ("Defence", 0.8),
+    ("Midfield", 1.0),
+    ("Attack", 1.25),
+];
+
+/// Multiplier in [`estimate_value`] for age: young players carry resale
+/// value, veterans little.
+fn age_factor(age: u8) -> f64 {
+    match age {
+        0..=21 => 1.4,
+        22..=24 => 1.2,
+        25..=27 => 1.0,
+        28..=30 => 0.7,
+        31..=33 => 0.4,
+        _ => 0.2,
+    }
+}
+
+/// Estimated transfer value in millions:
+/// `1.1^(rating - 60) * age_factor * line factor`.
+/// A 60-rated midfielder aged 25-27 is worth 1.0; each rating point adds
+/// 10%.
+fn estimate_value(player: &Player) -> f64 {
+    let line = line_of(&player.position);
+    let line_factor = MARKET_LINE_FACTORS
+        .iter()
+        .find(|(name, _)| *name == line)
+        .map_or(1.0, |(_, f)| *f);
+    1.1_f64.powf(f64::from(player.rating) - 60.0) * age_factor(player.age) * line_factor
+}
+
+fn escape_markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn roster_to_markdown(players: &[Player]) -> String {
+    let mut out = String::from(
+        "| Name | Position | Team | Age | Rating | Pace | Shooting | Passing | Available | Injury note |\n",
+    );
+    out.push_str("|---|---|---|---:|---:|---:|---:|---:|---|---|\n");
+    for p in players {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |\n",
+            escape_markdown_cell(&p.name),
+            escape_markdown_cell(&p.position),
+            escape_markdown_cell(&p.team),
+            p.age,
+            p.rating,
+            p.pace,
+            p.shooting,
+            p.passing,
+            if p.available { "yes" } else { "no" },
+            escape_markdown_cell(&p.injury_note)
+        ));
+    }
+    out
+}
+
+/// On-disk roster formats, chosen by file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RosterFormat {
+    Json,
+    Csv,
+}
+
+impl RosterFormat {
+    fn from_path(path: &Path) -> io::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(RosterFormat::Json),
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Ok(RosterFormat::Csv),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "unsupported roster file {} (use .json or .csv)",
+                    path.display()
+                ),
+            )),
+        }
+    }
+}
+
+const CSV_HEADER: &str =
+    "name,position,age,rating,available,injury_note,team,pace,shooting,passing";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Splits CSV text into rows of fields. Quoted fields may contain commas,
+/// line breaks and doubled quotes.
+fn parse_csv(data: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+fn roster_to_csv(players: &[Player]) -> String {
+    let mut out = format!("{}\n", CSV_HEADER);
+    for p in players {
+        let fields = [
+            csv_field(&p.name),
+            csv_field(&p.position),
+            p.age.to_string(),
+            p.rating.to_string(),
+            p.available.to_string(),
+            csv_field(&p.injury_note),
+            csv_field(&p.team),
+            p.pace.to_string(),
+            p.shooting.to_string(),
+            p.passing.to_string(),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn roster_from_csv(data: &str) -> io::Result<Vec<Player>> {
+    let invalid = |line: usize| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid roster CSV record {}", line),
+        )
+    };
+    let mut players = Vec::new();
+    for (i, row) in parse_csv(data).into_iter().enumerate().skip(1) {
+        let [
+            name,
+            position,
+            age,
+            rating,
+            available,
+            injury_note,
+            team,
+            pace,
+            shooting,
+            passing,
+        ] = <[String; 10]>::try_from(row).map_err(|_| invalid(i + 1))?;
+        let number = |s: &str| s.parse::<u8>().map_err(|_| invalid(i + 1));
+        let mut player = Player::new(&name, &position, number(&age)?, number(&rating)?)
+            .with_team(&team)
+            .with_attributes(number(&pace)?, number(&shooting)?, number(&passing)?);
+        player.available = available.parse().map_err(|_| invalid(i + 1))?;
+        player.injury_note = injury_note;
+        players.push(player);
+    }
+    Ok(players)
+}
+
+fn load_roster(path: &Path, format: RosterFormat) -> io::Result<Vec<Player>> {
+    let data = fs::read_to_string(path)?;
+    match format {
+        RosterFormat::Json => {
+            serde_json::from_str(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+        RosterFormat::Csv => roster_from_csv(&data),
+    }
+}
+
+fn save_roster(path: &Path, players: &[Player], format: RosterFormat) -> io::Result<()> {
+    let data = match format {
+        RosterFormat::Json => serde_json::to_string_pretty(players)?,
+        RosterFormat::Csv => roster_to_csv(players),
+    };
+    fs::write(path, data)
+}
+
+/// Where the roster lives on disk and whether edits are written back
+/// immediately.
+struct RosterFile {
+    path: Option<PathBuf>,
+    auto_save: bool,
+}
+
+impl RosterFile {
+    fn from_args(args: &[String]) -> Self {
+        let path = args
+            .iter()
+            .position(|a| a == "--roster")
+            .and_then(|i| args.get(i + 1))
+            .map(PathBuf::from);
+        RosterFile {
+            auto_save: path.is_some(),
+            path,
+        }
+    }
+
+    /// Writes the roster back after a change. A failed write only warns; the
+    /// in-memory roster keeps the change either way.
+    fn after_change(&self, players: &[Player]) {
+        if !self.auto_save {
+            return;
+        }
+        if let Some(path) = &self.path
+            && let Err(e) =
+                RosterFormat::from_path(path).and_then(|f| save_roster(path, players, f))
+        {
+            println!(
+                "Warning: could not save roster to {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+fn default_roster() -> Vec<Player> {
+    vec![
+        Player::new("Neuer", "GK", 38, 88)
+            .with_team("Bayern")
+            .with_attributes(45, 20, 75),
+        Player::new("Ramos", "CB", 38, 84)
+            .with_team("Real Madrid")
+            .with_attributes(68, 60, 75),
+        Player::new("Modric", "CMF", 38, 86)
+            .with_team("Real Madrid")
+            .with_attributes(70, 76, 90),
+        Player::new("De Bruyne", "AMF", 33, 91)
+            .with_team("Man City")
+            .with_attributes(72, 87, 94),
+        Player::new("Haaland", "CF", 24, 91)
+            .with_team("Man City")
+            .with_attributes(89, 93, 66),
+        Player::new("Kane", "CF", 31, 90)
+            .with_team("Bayern")
+            .with_attributes(68, 93, 84),
+    ]
+}
+
+fn remove_player(players: &mut Vec<Player>, name: &str) -> Option<Player> {
+    let index = players
+        .iter()
+        .position(|p| p.name.eq_ignore_ascii_case(name))?;
+    Some(players.remove(index))
+}
+
+fn prompt(msg: &str) -> String {
+    print!("{}", msg);
+    let _ = io::stdout().flush();
+    let mut buf = String::new();
+    io::stdin()
+        .read_line(&mut buf)
+        .expect("Failed to read input");
+    buf.trim().to_string()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut roster = RosterFile::from_args(&args);
+    let mut players = match &roster.path {
+        Some(path) if path.exists() => {
+            match RosterFormat::from_path(path).and_then(|f| load_roster(path, f)) {
+                Ok(players) => players,
+                Err(e) => {
+                    println!("Error: could not load {}: {}", path.display(), e);
+                    return;
+                }
+            }
+        }
+        _ => default_roster(),
+    };
 
     loop {
         println!("\n=== Player Filter Menu ===");
         println!("1. Show all players");
         println!("2. Filter by position (GK, CB, CMF, AMF, CF)");
-        println!("3. Exit");
-        print!("Enter choice: ");
+        println!("3. Show available players");
+        println!("4. Set player availability");
+        println!("5. Compare two players");
+        println!("6. Export roster as Markdown");
+        println!("7. Add player");
+        println!("8. Remove player");
+        println!(
+            "9. Toggle auto-save (currently {})",
+            if roster.auto_save { "on" } else { "off" }
+        );
+        println!("10. Team strength");
+        println!("11. Query players");
+        println!("12. Compare two teams");
+        println!("13. Show only world-class players");
+        println!("14. Squad market value");
+        println!("15. Simulate a match against another roster");
+        println!("16. Sort players");
+        println!("17. Exit");
+        let choice = prompt("Enter choice: ");
 
-        let mut choice = String::new();
-        io::stdin().read_line(&mut choice).expect("Failed to read input");
-
-        match choice.trim() {
+        match choice.as_str() {
             "1" => {
                 println!("\nAll Players:");
                 for player in &players {
-                    println!("{} - {}", player.name, player.position);
+                    println!("{}", player.listing());
                 }
             }
             "2" => {
-                print!("Enter position to filter (e.g. CF): ");
-                let mut pos = String::new();
-                io::stdin().read_line(&mut pos).expect("Failed to read input");
-                let pos = pos.trim().to_uppercase();
-                let pos_clone = pos.clone(); 
+                let input = prompt("Enter position to filter (e.g. CF or striker): ");
+                let Some(pos) = normalize_position(&input) else {
+                    println!("\n{}", unknown_position(&input));
+                    continue;
+                };
+                let pos_clone = pos.clone();
                 let filter = FilterCondition {
-                    condition: move |p: &Player| p.position.eq_ignore_ascii_case(&pos),
+                    condition: move |p: &Player| plays_position(p, &pos),
                 };
                 let result = custom_filter(&players, &filter);
                 if result.is_empty() {
@@ -77,11 +876,268 @@ fn main() {
                 } else {
                     println!("\nPlayers in position {}:", pos_clone);
                     for player in result {
-                        println!("{} - {}", player.name, player.position);
+                        println!("{}", player.listing());
                     }
                 }
             }
             "3" => {
+                println!("\nAvailable Players:");
+                for player in only_available(&players) {
+                    println!("{}", player.listing());
+                }
+            }
+            "4" => {
+                let name = prompt("Enter player name: ");
+                match find_player_mut(&mut players, &name) {
+                    Some(player) => {
+                        let answer = prompt(&format!("Is {} available? (y/n): ", player.name));
+                        if answer.eq_ignore_ascii_case("y") {
+                            player.available = true;
+                            player.injury_note.clear();
+                        } else {
+                            player.available = false;
+                            player.injury_note = prompt("Injury note (optional): ");
+                        }
+                        println!("Updated: {}", player.summary());
+                        roster.after_change(&players);
+                    }
+                    None => println!("\nNo player named {}", name),
+                }
+            }
+            "5" => {
+                let first = prompt("First player: ");
+                let second = prompt("Second player: ");
+                match (
+                    find_player(&players, &first),
+                    find_player(&players, &second),
+                ) {
+                    (Some(a), Some(b)) => println!("\n{}", compare_players(a, b)),
+                    (None, _) => println!("\nError: unknown player '{}'", first),
+                    (_, None) => println!("\nError: unknown player '{}'", second),
+                }
+            }
+            "6" => {
+                let markdown = roster_to_markdown(&players);
+                let path = prompt("Save to file (leave empty to print): ");
+                if path.is_empty() {
+                    println!("\n{}", markdown);
+                } else {
+                    match fs::write(&path, markdown) {
+                        Ok(_) => println!("\nRoster written to {}", path),
+                        Err(e) => println!("\nError: could not write {}: {}", path, e),
+                    }
+                }
+            }
+            "7" => {
+                let name = prompt("Name: ");
+                let position = prompt("Position: ");
+                if name.is_empty() || position.is_empty() {
+                    println!("\nError: name and position are required");
+                    continue;
+                }
+                let Some(position) = normalize_position(&position) else {
+                    println!("\nError: {}", unknown_position(&position));
+                    continue;
+                };
+                if find_player(&players, &name).is_some() {
+                    println!("\nError: {} is already in the roster", name);
+                    continue;
+                }
+                let team = prompt("Team (optional): ");
+                let age = prompt("Age: ").parse::<u8>();
+                let rating = prompt("Rating (0-100): ").parse::<u8>();
+                let attributes: Result<Vec<u8>, _> =
+                    prompt("Pace, shooting, passing (e.g. 80 75 70, empty to skip): ")
+                        .split_whitespace()
+                        .map(|a| a.parse::<u8>())
+                        .collect();
+                let attributes = match attributes.as_deref() {
+                    Ok([]) => Some((0, 0, 0)),
+                    Ok(&[pace, shooting, passing]) => Some((pace, shooting, passing)),
+                    _ => None,
+                };
+                match (age, rating, attributes) {
+                    (Ok(age), Ok(rating), Some((pace, shooting, passing))) if rating <= 100 => {
+                        players.push(
+                            Player::new(&name, &position, age, rating)
+                                .with_team(&team)
+                                .with_attributes(pace, shooting, passing),
+                        );
+                        println!("Added: {}", name);
+                        roster.after_change(&players);
+                    }
+                    _ => println!("\nError: invalid age, rating or attributes"),
+                }
+            }
+            "8" => {
+                let name = prompt("Enter player name: ");
+                match remove_player(&mut players, &name) {
+                    Some(player) => {
+                        println!("Removed: {}", player.name);
+                        roster.after_change(&players);
+                    }
+                    None => println!("\nNo player named {}", name),
+                }
+            }
+            "9" => {
+                if roster.path.is_none() {
+                    println!("No roster file configured; start with --roster <path>.");
+                } else {
+                    roster.auto_save = !roster.auto_save;
+                    println!(
+                        "Auto-save is now {}.",
+                        if roster.auto_save { "on" } else { "off" }
+                    );
+                }
+            }
+            "10" => {
+                println!("\nLine weights:");
+                for (line, weight) in LINE_WEIGHTS {
+                    let count = players
+                        .iter()
+                        .filter(|p| line_of(&p.position) == line)
+                        .count();
+                    println!("{:<12} x{:.1} ({} players)", line, weight, count);
+                }
+                println!("Team strength: {:.1}", team_strength(&players));
+            }
+            "11" => {
+                println!("Leave any constraint empty to skip it.");
+                let mut query = PlayerQuery::new();
+                let where_s = prompt("Position or line (e.g. CF or Attack): ");
+                if LINE_WEIGHTS
+                    .iter()
+                    .any(|(line, _)| line.eq_ignore_ascii_case(&where_s))
+                {
+                    query = query.line(&where_s);
+                } else if !where_s.is_empty() {
+                    match normalize_position(&where_s) {
+                        Some(position) => query = query.position(&position),
+                        None => {
+                            println!("\n{}", unknown_position(&where_s));
+                            continue;
+                        }
+                    }
+                }
+                let numeric: [(&str, NumericConstraint); 5] = [
+                    ("Younger than", PlayerQuery::younger_than),
+                    ("Minimum rating", PlayerQuery::min_rating),
+                    ("Minimum pace", PlayerQuery::min_pace),
+                    ("Minimum shooting", PlayerQuery::min_shooting),
+                    ("Minimum passing", PlayerQuery::min_passing),
+                ];
+                let mut valid = true;
+                for (label, constrain) in numeric {
+                    let value = prompt(&format!("{}: ", label));
+                    if value.is_empty() {
+                        continue;
+                    }
+                    match value.parse::<u8>() {
+                        Ok(v) => query = constrain(query, v),
+                        Err(_) => {
+                            println!("\nError: '{}' is not a number", value);
+                            valid = false;
+                            break;
+                        }
+                    }
+                }
+                if !valid {
+                    continue;
+                }
+                let result = custom_filter(&players, &query.build());
+                println!("\n{} matching player(s):", result.len());
+                for player in result {
+                    println!(
+                        "{} (age {}, pace {}, shooting {}, passing {})",
+                        player.listing(),
+                        player.age,
+                        player.pace,
+                        player.shooting,
+                        player.passing
+                    );
+                }
+            }
+            "12" => {
+                let first = prompt("First team: ");
+                let second = prompt("Second team: ");
+                match compare_teams(&players, &first, &second) {
+                    Ok(table) => println!("\n{}", table),
+                    Err(e) => println!("\nError: {}", e),
+                }
+            }
+            "13" => {
+                let filter = FilterCondition {
+                    condition: |p: &Player| rating_tier(p.rating) == "World Class",
+                };
+                let result = custom_filter(&players, &filter);
+                if result.is_empty() {
+                    println!("\nNo world-class players");
+                } else {
+                    println!("\nWorld-class players:");
+                    for player in result {
+                        println!("{}", player.listing());
+                    }
+                }
+            }
+            "14" => {
+                let mut values: Vec<(&Player, f64)> =
+                    players.iter().map(|p| (p, estimate_value(p))).collect();
+                values.sort_by(|a, b| b.1.total_cmp(&a.1));
+                println!("\nEstimated market values:");
+                for (player, value) in &values {
+                    println!("{:<20} {:>8.1}m", player.name, value);
+                }
+                let total: f64 = values.iter().map(|(_, v)| v).sum();
+                println!("{:<20} {:>8.1}m", "Squad total", total);
+            }
+            "15" => {
+                let path = PathBuf::from(prompt("Opponent roster file (.json or .csv): "));
+                let opponent =
+                    match RosterFormat::from_path(&path).and_then(|f| load_roster(&path, f)) {
+                        Ok(opponent) => opponent,
+                        Err(e) => {
+                            println!("\nError: could not load {}: {}", path.display(), e);
+                            continue;
+                        }
+                    };
+                let seed_input = prompt("Seed (leave empty for a random match): ");
+                let seed = if seed_input.is_empty() {
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |d| d.as_nanos() as u64)
+                } else {
+                    match seed_input.parse::<u64>() {
+                        Ok(seed) => seed,
+                        Err(_) => {
+                            println!("\nError: '{}' is not a number", seed_input);
+                            continue;
+                        }
+                    }
+                };
+                let (ours, theirs) = simulate_match(&players, &opponent, seed);
+                println!(
+                    "\nSquad ({:.1}) {} - {} Opponent ({:.1})   [seed {}]",
+                    team_strength(&players),
+                    ours,
+                    theirs,
+                    team_strength(&opponent),
+                    seed
+                );
+            }
+            "16" => {
+                let spec = prompt("Sort by (e.g. rating desc, age asc): ");
+                match parse_sort_keys(&spec) {
+                    Ok(keys) => {
+                        let mut sorted = players.clone();
+                        sort_players_multi(&mut sorted, &keys);
+                        for player in &sorted {
+                            println!("{}", player.listing());
+                        }
+                    }
+                    Err(e) => println!("\nError: {}", e),
+                }
+            }
+            "17" => {
                 println!("Exiting...");
                 break;
             }
@@ -89,3 +1145,345 @@ fn main() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn injured_players_are_not_available() {
+        let mut players = vec![
+            Player::new("Haaland", "CF", 24, 91),
+            Player::new("Kane", "CF", 31, 90),
+        ];
+        let kane = find_player_mut(&mut players, "kane").unwrap();
+        kane.available = false;
+        kane.injury_note = "hamstring".to_string();
+
+        let available = only_available(&players);
+        assert_eq!(available.len(), 1);
+        assert_eq!(available[0].name, "Haaland");
+        assert_eq!(players[1].summary(), "Kane - CF (unavailable: hamstring)");
+    }
+
+    #[test]
+    fn markdown_has_header_separator_and_rows() {
+        let players = vec![
+            Player::new("Haaland", "CF", 24, 91)
+                .with_team("Man City")
+                .with_attributes(89, 93, 66),
+            Player::new("Kane|Harry", "CF", 31, 90),
+        ];
+        let markdown = roster_to_markdown(&players);
+        let lines: Vec<&str> = markdown.lines().collect();
+        assert_eq!(lines.len(), 2 + players.len());
+        assert!(lines[0].starts_with("| Name | Position |"));
+        assert!(lines[1].starts_with("|---|"));
+        assert_eq!(
+            lines[2],
+            "| Haaland | CF | Man City | 24 | 91 | 89 | 93 | 66 | yes |  |"
+        );
+        assert_eq!(lines[0].matches('|').count(), lines[1].matches('|').count());
+        assert!(lines[3].contains("Kane\\|Harry"));
+    }
+
+    #[test]
+    fn added_player_is_persisted_and_reloaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("roster.json");
+        let roster = RosterFile {
+            path: Some(path.clone()),
+            auto_save: true,
+        };
+        let mut players = default_roster();
+        players.push(Player::new("Musiala", "AMF", 21, 87));
+        roster.after_change(&players);
+
+        let reloaded = load_roster(&path, RosterFormat::Json).unwrap();
+        assert_eq!(reloaded.len(), players.len());
+        let musiala = find_player(&reloaded, "musiala").unwrap();
+        assert_eq!(musiala.rating, 87);
+        assert!(musiala.available);
+    }
+
+    #[test]
+    fn failed_auto_save_keeps_in_memory_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let roster = RosterFile {
+            path: Some(dir.path().join("missing").join("roster.json")),
+            auto_save: true,
+        };
+        let mut players = default_roster();
+        remove_player(&mut players, "Kane").unwrap();
+        roster.after_change(&players);
+        assert!(find_player(&players, "Kane").is_none());
+        assert_eq!(players.len(), 5);
+    }
+
+    #[test]
+    fn team_strength_weights_ratings_by_line() {
+        let players = vec![
+            Player::new("Neuer", "GK", 38, 80),
+            Player::new("Ramos", "CB", 38, 90),
+            Player::new("Kane", "CF", 31, 70),
+        ];
+        // (1.0 * 80 + 1.2 * 90 + 1.3 * 70) / (1.0 + 1.2 + 1.3) = 279 / 3.5
+        let expected = 279.0 / 3.5;
+        assert!((team_strength(&players) - expected).abs() < 1e-9);
+        assert_eq!(team_strength(&[]), 0.0);
+    }
+
+    #[test]
+    fn team_strength_uses_best_available_eleven() {
+        let mut players = vec![
+            Player::new("Neuer", "GK", 38, 80),
+            Player::new("Backup", "GK", 30, 95),
+            Player::new("Kane", "CF", 31, 70),
+        ];
+        players[1].available = false;
+        let expected = (80.0 + 1.3 * 70.0) / (1.0 + 1.3);
+        assert!((team_strength(&players) - expected).abs() < 1e-9);
+
+        // Twelve outfielders: only the ten best play alongside the keeper.
+        for i in 0..12 {
+            players.push(Player::new(&format!("M{}", i), "CMF", 25, 60 + i));
+        }
+        let names: Vec<&str> = best_eleven(&players)
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names.len(), 11);
+        assert_eq!(names[0], "Neuer");
+        assert!(!names.contains(&"Backup"));
+        assert!(!names.contains(&"M0"));
+        assert!(!names.contains(&"M1"));
+
+        players.iter_mut().for_each(|p| p.available = false);
+        assert_eq!(team_strength(&players), 0.0);
+    }
+
+    #[test]
+    fn comparison_marks_leader_and_ties() {
+        let haaland = Player::new("Haaland", "CF", 24, 91);
+        let kane = Player::new("Kane", "CF", 31, 90);
+        let table = compare_players(&haaland, &kane);
+        let rating_row = table.lines().find(|l| l.starts_with("Rating")).unwrap();
+        assert!(rating_row.contains("91*"));
+        assert!(!rating_row.contains("90*"));
+
+        let twin = Player::new("Twin", "CF", 24, 91);
+        let table = compare_players(&haaland, &twin);
+        let rating_row = table.lines().find(|l| l.starts_with("Rating")).unwrap();
+        assert_eq!(rating_row.matches("91=").count(), 2);
+
+        let haaland = haaland.with_team("Man City").with_attributes(89, 93, 66);
+        let kane = kane.with_team("Bayern").with_attributes(68, 93, 84);
+        let table = compare_players(&haaland, &kane);
+        let row = |label: &str| {
+            table
+                .lines()
+                .find(|l| l.starts_with(label))
+                .unwrap()
+                .to_string()
+        };
+        assert!(row("Team").contains("Man City") && row("Team").contains("Bayern"));
+        assert!(row("Pace").contains("89*") && !row("Pace").contains("68*"));
+        assert_eq!(row("Shooting").matches("93=").count(), 2);
+        assert!(row("Passing").contains("84*"));
+    }
+
+    #[test]
+    fn query_combines_line_age_and_shooting() {
+        let players = vec![
+            Player::new("Haaland", "CF", 24, 91).with_attributes(89, 93, 66),
+            Player::new("Kane", "CF", 31, 90).with_attributes(68, 93, 84),
+            Player::new("Young CF", "ST", 19, 75).with_attributes(80, 70, 60),
+            Player::new("Young AMF", "AMF", 21, 88).with_attributes(85, 88, 90),
+            Player::new("Prospect", "LWF", 20, 86).with_attributes(92, 86, 78),
+        ];
+        let filter = PlayerQuery::new()
+            .line("attack")
+            .younger_than(25)
+            .min_shooting(85)
+            .build();
+        let names: Vec<String> = custom_filter(&players, &filter)
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["Haaland", "Prospect"]);
+
+        let filter = PlayerQuery::new().position("cf").min_passing(80).build();
+        assert_eq!(custom_filter(&players, &filter).len(), 1);
+        // "Young CF" is stored as ST, which is the same position as CF.
+        for wanted in ["CF", "st", "striker"] {
+            let filter = PlayerQuery::new().position(wanted).build();
+            assert_eq!(custom_filter(&players, &filter).len(), 3);
+        }
+        assert!(plays_position(&players[2], "CF"));
+        assert!(!plays_position(&players[2], "AMF"));
+        assert_eq!(
+            custom_filter(&players, &PlayerQuery::new().build()).len(),
+            5
+        );
+    }
+
+    #[test]
+    fn team_comparison_shows_size_average_and_strongest_position() {
+        let players = vec![
+            Player::new("A1", "GK", 30, 80).with_team("Alpha"),
+            Player::new("A2", "CF", 25, 90).with_team("Alpha"),
+            Player::new("A3", "CF", 22, 70).with_team("Alpha"),
+            Player::new("B1", "CB", 28, 85).with_team("Beta"),
+            Player::new("B2", "CMF", 27, 75).with_team("Beta"),
+            Player::new("Free", "CF", 20, 99),
+        ];
+        let table = compare_teams(&players, "Alpha", "beta").unwrap();
+        let row = |label: &str| -> Vec<String> {
+            let line = table.lines().find(|l| l.starts_with(label)).unwrap();
+            line[12..]
+                .split("  ")
+                .map(|c| c.trim().to_string())
+                .filter(|c| !c.is_empty())
+                .collect()
+        };
+        assert_eq!(row("Squad size"), vec!["3", "2"]);
+        assert_eq!(row("Avg rating"), vec!["80.0", "80.0"]);
+        assert_eq!(row("Strongest"), vec!["CF (80.0)", "CB (85.0)"]);
+
+        assert_eq!(
+            compare_teams(&players, "Alpha", "Gamma"),
+            Err("unknown team 'Gamma'".to_string())
+        );
+    }
+
+    #[test]
+    fn roster_round_trips_as_json_and_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut players = default_roster();
+        players[1].available = false;
+        players[1].injury_note = "knee, \"minor\"\nback soon".to_string();
+        for file in ["roster.json", "roster.CSV"] {
+            let path = dir.path().join(file);
+            let format = RosterFormat::from_path(&path).unwrap();
+            save_roster(&path, &players, format).unwrap();
+            let reloaded = load_roster(&path, format).unwrap();
+            assert_eq!(
+                format!("{:?}", reloaded),
+                format!("{:?}", players),
+                "{} did not round-trip",
+                file
+            );
+        }
+        assert!(
+            fs::read_to_string(dir.path().join("roster.CSV"))
+                .unwrap()
+                .starts_with(CSV_HEADER)
+        );
+        assert!(RosterFormat::from_path(Path::new("roster.txt")).is_err());
+        assert!(RosterFormat::from_path(Path::new("roster")).is_err());
+    }
+
+    #[test]
+    fn rating_tiers_change_at_boundaries() {
+        let cases = [
+            (99, "World Class"),
+            (90, "World Class"),
+            (89, "Great"),
+            (80, "Great"),
+            (79, "Good"),
+            (70, "Good"),
+            (69, "Average"),
+            (60, "Average"),
+            (59, "Developing"),
+            (0, "Developing"),
+        ];
+        for (rating, tier) in cases {
+            assert_eq!(rating_tier(rating), tier, "rating {}", rating);
+        }
+        assert_eq!(
+            Player::new("Kane", "CF", 31, 90).listing(),
+            "Kane - CF [90 World Class]"
+        );
+    }
+
+    #[test]
+    fn young_attackers_are_valued_highest() {
+        let striker = Player::new("Young", "CF", 21, 90);
+        let defender = Player::new("Veteran", "CB", 34, 90);
+        assert!(estimate_value(&striker) > estimate_value(&defender));
+
+        let baseline = Player::new("Mid", "CMF", 26, 60);
+        assert!((estimate_value(&baseline) - 1.0).abs() < 1e-9);
+        let better = Player::new("Mid", "CMF", 26, 61);
+        assert!((estimate_value(&better) - 1.1).abs() < 1e-9);
+        assert!(estimate_value(&Player::new("Old", "CMF", 31, 60)) < 1.0);
+    }
+
+    #[test]
+    fn match_simulation_is_seeded_and_favours_strength() {
+        let strong = vec![
+            Player::new("A", "CF", 25, 92),
+            Player::new("B", "CMF", 25, 90),
+            Player::new("C", "CB", 25, 91),
+        ];
+        let weak = vec![
+            Player::new("X", "CF", 25, 62),
+            Player::new("Y", "CMF", 25, 60),
+            Player::new("Z", "CB", 25, 61),
+        ];
+        assert_eq!(
+            simulate_match(&strong, &weak, 42),
+            simulate_match(&strong, &weak, 42)
+        );
+
+        let (mut wins, mut losses) = (0, 0);
+        for seed in 0..200 {
+            let (a, b) = simulate_match(&strong, &weak, seed);
+            if a > b {
+                wins += 1;
+            } else if a < b {
+                losses += 1;
+            }
+        }
+        assert!(wins > 150, "strong side won only {} of 200", wins);
+        assert!(losses < 10, "strong side lost {} of 200", losses);
+        assert_eq!(simulate_match(&strong, &[], 1), (0, 0));
+    }
+
+    #[test]
+    fn position_aliases_normalize_to_codes() {
+        for input in ["striker", "st", "CF", " Centre-Forward "] {
+            assert_eq!(normalize_position(input).as_deref(), Some("CF"));
+        }
+        assert_eq!(normalize_position("goalie").as_deref(), Some("GK"));
+        assert_eq!(normalize_position("cmf").as_deref(), Some("CMF"));
+        assert_eq!(normalize_position("sweeper"), None);
+        assert_eq!(normalize_position(""), None);
+    }
+
+    #[test]
+    fn multi_key_sort_breaks_ties_in_order() {
+        let mut players = vec![
+            Player::new("Kane", "CF", 31, 90),
+            Player::new("Haaland", "CF", 24, 91),
+            Player::new("Saka", "RWF", 23, 87),
+            Player::new("Rodri", "DMF", 28, 90),
+            Player::new("Foden", "AMF", 24, 87),
+        ];
+        let keys = parse_sort_keys("rating desc, age").unwrap();
+        assert_eq!(keys, vec![(SortKey::Rating, true), (SortKey::Age, false)]);
+        sort_players_multi(&mut players, &keys);
+        let names: Vec<&str> = players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Haaland", "Rodri", "Kane", "Saka", "Foden"]);
+
+        // Ties on every key keep the existing order.
+        sort_players_multi(&mut players, &[(SortKey::Rating, false)]);
+        let names: Vec<&str> = players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Saka", "Foden", "Rodri", "Kane", "Haaland"]);
+
+        sort_players_multi(&mut players, &[]);
+        assert_eq!(players[0].name, "Saka");
+        assert!(parse_sort_keys("height desc").is_err());
+        assert!(parse_sort_keys("age up").is_err());
+    }
+}
@@ -1,13 +1,54 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
 use rpassword::read_password;
+use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::OnceLock;
+use std::time::SystemTime;
 
 const DATA_FILE: &str = "store_data.json";
+const LOG_FILE: &str = "rusty_store.log";
 const DEFAULT_ADMIN_USER: &str = "admin";
 const DEFAULT_ADMIN_PASS: &str = "password";
+const LOW_STOCK_THRESHOLD: i32 = 5;
+const WARNING_STOCK_THRESHOLD: i32 = 10;
+const TOP_SELLERS_LIMIT: usize = 10;
+const MIN_PASSWORD_LEN: usize = 8;
+// A sale repeating the previous one this soon is probably a double submit.
+const DUPLICATE_SALE_WINDOW_SECS: i64 = 10;
+// What `prompt` returns once input has run out, e.g. at the end of a piped
+// script. Menus treat it as "Back" and the main menu as "Save & Exit".
+// Upper bounds (inclusive) of the stock histogram ranges: <= 0, 1-10,
+// 11-50 and 51+.
+const STOCK_HISTOGRAM_BUCKETS: &[i32] = &[0, 10, 50];
+// Width of the longest bar in the stock histogram.
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+const PAGINATED_REPORT_TITLE: &str = "Rusty Store Report";
+// Tags further than this many edits from the typed tag are not suggested.
+const TAG_SUGGEST_MAX_DISTANCE: usize = 3;
+// Most minor-unit digits any currency uses (e.g. 3 for BHD).
+const MAX_CURRENCY_DECIMALS: usize = 3;
+// Units of each currency per US dollar, used to show report totals in a
+// second currency.
+const EXCHANGE_RATES: &[(&str, f64)] = &[
+    ("USD", 1.0),
+    ("EUR", 0.92),
+    ("GBP", 0.79),
+    ("CHF", 0.88),
+    ("JPY", 150.0),
+    ("CAD", 1.36),
+];
+const RECEIPT_PLACEHOLDERS: &[&str] = &[
+    "id", "number", "product", "qty", "price", "total", "currency", "date",
+];
+const DEFAULT_RECEIPT_TEMPLATE: &str =
+    "Receipt {number} — {date}\\n{product} x{qty} @ {currency}{price}\\nTOTAL {currency}{total}";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Product {
@@ -16,6 +57,59 @@ struct Product {
     description: String,
     price: f64,
     quantity: i32,
+    #[serde(default)]
+    expiry: Option<DateTime<Local>>,
+    /// Stock not explained by purchases, sales or repackaging: the quantity
+    /// the product was created with plus any manual edits. `None` only for
+    /// data saved before this was tracked; see `Store::load_from_file`.
+    #[serde(default)]
+    initial_quantity: Option<i32>,
+    /// The product this is a variant of, e.g. a size of a shirt.
+    #[serde(default)]
+    parent_id: Option<u32>,
+    /// Attribute name/value pairs distinguishing a variant, e.g. size=M.
+    #[serde(default)]
+    variant_attrs: Vec<(String, String)>,
+    /// Free-form lowercase labels such as "clearance" or "seasonal".
+    #[serde(default)]
+    tags: Vec<String>,
+    /// What one unit of stock is, e.g. "kg" or "box". Unlabeled quantities
+    /// are plain counts.
+    #[serde(default)]
+    unit_label: Option<String>,
+    /// Stock keeping unit code. Codes of 13 digits are EAN-13 barcodes and
+    /// must have a valid check digit.
+    #[serde(default)]
+    sku: Option<String>,
+}
+
+impl Product {
+    /// The stock quantity followed by the unit label, if any: "5 kg".
+    fn quantity_with_unit(&self) -> String {
+        match &self.unit_label {
+            Some(unit) => format!("{} {}", self.quantity, unit),
+            None => self.quantity.to_string(),
+        }
+    }
+
+    /// Price excluding VAT at `rate_percent`. If `inclusive`, the stored
+    /// price already contains VAT; otherwise it is the net price itself.
+    fn net_price(&self, rate_percent: f64, inclusive: bool) -> f64 {
+        if inclusive {
+            self.price / (1.0 + rate_percent / 100.0)
+        } else {
+            self.price
+        }
+    }
+
+    /// VAT charged on one unit at `rate_percent`.
+    fn vat_component(&self, rate_percent: f64, inclusive: bool) -> f64 {
+        if inclusive {
+            self.price - self.net_price(rate_percent, inclusive)
+        } else {
+            self.price * rate_percent / 100.0
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -25,6 +119,46 @@ struct Sale {
     quantity: i32,
     sale_price: f64,
     time: DateTime<Local>,
+    /// Tax rate applied at the time of sale, in basis points (2000 = 20%).
+    #[serde(default)]
+    tax_rate_bp: u32,
+    /// Tax collected on the whole sale.
+    #[serde(default)]
+    tax: f64,
+    /// The unit price asked for before cash rounding; `None` when rounding
+    /// was off. `sale_price` is the rounded price actually charged.
+    #[serde(default)]
+    exact_price: Option<f64>,
+}
+
+impl Sale {
+    /// What cash rounding added to (or, if negative, took off) this sale.
+    fn rounding_difference(&self) -> f64 {
+        self.exact_price.map_or(0.0, |exact| {
+            (self.sale_price - exact) * self.quantity as f64
+        })
+    }
+
+    /// Receipt number shown to customers: prefix, year of sale and the id
+    /// padded to four digits, e.g. "INV-2024-0001". An empty prefix is
+    /// left out.
+    fn receipt_number(&self, prefix: &str) -> String {
+        let number = format!("{}-{:04}", self.time.year(), self.id);
+        if prefix.is_empty() {
+            number
+        } else {
+            format!("{}-{}", prefix, number)
+        }
+    }
+
+    /// Amount the tax was charged on: the line total net of tax.
+    fn taxable_base(&self) -> f64 {
+        if self.tax_rate_bp == 0 {
+            self.sale_price * self.quantity as f64
+        } else {
+            self.tax * 10_000.0 / self.tax_rate_bp as f64
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,12 +168,149 @@ struct Purchase {
     quantity: i32,
     purchase_price: f64,
     time: DateTime<Local>,
+    #[serde(default)]
+    lot_number: Option<String>,
+}
+
+/// A conversion of `from_qty` units of one product into `to_qty` units of
+/// another, e.g. breaking a case into single items.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Repackaging {
+    from_id: u32,
+    to_id: u32,
+    from_qty: i32,
+    to_qty: i32,
+    time: DateTime<Local>,
+}
+
+/// Several sales billed together to one customer.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Invoice {
+    id: u32,
+    sale_ids: Vec<u32>,
+    customer: Option<String>,
+}
+
+/// Stock held back for a customer, e.g. on layaway. It counts against
+/// available stock until fulfilled, released, or `expires` passes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Reservation {
+    id: u32,
+    product_id: u32,
+    quantity: i32,
+    expires: DateTime<Local>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Manager {
     username: String,
     password_hash: String,
+    /// Prepended to the password before hashing. Empty for managers saved
+    /// before salting, whose hash is of the bare password.
+    #[serde(default)]
+    salt: String,
+    /// Admins may reset other managers' passwords.
+    #[serde(default)]
+    is_admin: bool,
+    /// Time of the last successful `Store::authenticate`.
+    #[serde(default)]
+    last_login: Option<DateTime<Local>>,
+}
+
+impl Manager {
+    fn new(username: &str, password: &str, is_admin: bool) -> Self {
+        let salt = new_salt(username);
+        Manager {
+            username: username.to_string(),
+            password_hash: hash_password(&salt, password),
+            salt,
+            is_admin,
+            last_login: None,
+        }
+    }
+
+    fn set_password(&mut self, password: &str) {
+        self.salt = new_salt(&self.username);
+        self.password_hash = hash_password(&self.salt, password);
+    }
+}
+
+/// An administrative action, kept for auditing.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AdminLogEntry {
+    time: DateTime<Local>,
+    admin: String,
+    action: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+struct Settings {
+    description_width: usize,
+    tax_rate: f64,
+    prices_include_tax: bool,
+    /// Receipt layout. `{name}` placeholders from `RECEIPT_PLACEHOLDERS`
+    /// are substituted and a literal `\n` starts a new line.
+    receipt_template: String,
+    /// Start of every receipt number; see `Sale::receipt_number`.
+    receipt_prefix: String,
+    /// Save automatically after this many changes; 0 disables auto-save.
+    autosave_interval: u32,
+    /// Grouping character between thousands, e.g. ',' in 1,234.50.
+    thousands_separator: char,
+    /// Character before the cents, e.g. ',' for locales writing 1.234,50.
+    decimal_separator: char,
+    /// Symbol printed before money amounts.
+    currency: String,
+    /// Digits in the currency's minor unit: 2 for cents, 0 for JPY, 3 for BHD.
+    currency_decimals: usize,
+    /// Let sales exceed stock, leaving the product backordered.
+    allow_negative_stock: bool,
+    /// ISO code of the currency prices are kept in, e.g. "EUR".
+    base_currency: String,
+    /// Currency report totals are also shown in, if any.
+    display_currency: Option<String>,
+    /// Order of products in reports.
+    report_order: ProductOrder,
+    /// Quantities at or below this are shown in red.
+    critical_stock: i32,
+    /// Quantities below this (and above `critical_stock`) are shown in yellow.
+    warning_stock: i32,
+    /// Sales and purchases worth more than this need a typed "yes"; 0
+    /// turns the check off.
+    confirm_above: f64,
+    /// Give new products the lowest free id left by a deleted product,
+    /// unless sales, purchases or variants still refer to it.
+    reuse_ids: bool,
+    /// Sale prices are rounded to the nearest multiple of this, e.g. 0.05
+    /// for cash handling; 0 leaves them exact.
+    cash_rounding: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            description_width: 40,
+            tax_rate: 0.0,
+            prices_include_tax: false,
+            receipt_template: DEFAULT_RECEIPT_TEMPLATE.to_string(),
+            receipt_prefix: "INV".to_string(),
+            autosave_interval: 10,
+            thousands_separator: ',',
+            decimal_separator: '.',
+            currency: "$".to_string(),
+            currency_decimals: 2,
+            allow_negative_stock: false,
+            base_currency: "USD".to_string(),
+            display_currency: None,
+            report_order: ProductOrder::ById,
+            critical_stock: LOW_STOCK_THRESHOLD,
+            warning_stock: WARNING_STOCK_THRESHOLD,
+            confirm_above: 0.0,
+            reuse_ids: false,
+            cash_rounding: 0.0,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,6 +322,88 @@ struct Store {
     next_product_id: u32,
     next_sale_id: u32,
     next_purchase_id: u32,
+    #[serde(default)]
+    settings: Settings,
+    #[serde(default)]
+    repackagings: Vec<Repackaging>,
+    #[serde(default)]
+    reservations: Vec<Reservation>,
+    #[serde(default)]
+    admin_log: Vec<AdminLogEntry>,
+    #[serde(default)]
+    invoices: Vec<Invoice>,
+    #[serde(default)]
+    next_invoice_id: u32,
+    #[serde(default)]
+    next_reservation_id: u32,
+    /// Changes made since the last save, auto or explicit.
+    #[serde(skip)]
+    dirty_ops: u32,
+    /// Incremented on every save. If the file on disk has a different
+    /// revision, another manager saved since this store was loaded.
+    #[serde(default)]
+    revision: u64,
+    /// Modification time of the data file when it was loaded or last saved.
+    #[serde(skip)]
+    file_modified: Option<SystemTime>,
+    /// Set by `--dry-run`: changes stay in memory and are announced as they
+    /// happen instead of being saved.
+    #[serde(skip)]
+    dry_run: bool,
+    /// Changes announced in `--dry-run` mode, oldest first.
+    #[serde(skip)]
+    dry_run_changes: Vec<String>,
+    /// Set by `--read-only`: methods that change the store or save it fail.
+    #[serde(skip)]
+    read_only: bool,
+}
+
+/// How `Store::products_ordered` sorts products. Quantity and value
+/// (price times quantity) put the largest first.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+enum ProductOrder {
+    ById,
+    ByName,
+    ByQuantity,
+    ByValue,
+}
+
+impl ProductOrder {
+    const ALL: [ProductOrder; 4] = [
+        ProductOrder::ById,
+        ProductOrder::ByName,
+        ProductOrder::ByQuantity,
+        ProductOrder::ByValue,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ProductOrder::ById => "id",
+            ProductOrder::ByName => "name",
+            ProductOrder::ByQuantity => "quantity",
+            ProductOrder::ByValue => "stock value",
+        }
+    }
+}
+
+/// Report sections available as JSON via `Store::report_json`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportSection {
+    Summary,
+    Inventory,
+    TopSellers,
+}
+
+impl ReportSection {
+    fn parse(s: &str) -> Option<ReportSection> {
+        match s.to_lowercase().as_str() {
+            "summary" => Some(ReportSection::Summary),
+            "inventory" => Some(ReportSection::Inventory),
+            "top-sellers" | "topsellers" => Some(ReportSection::TopSellers),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -59,8 +412,23 @@ enum StoreError {
     InsufficientStock(String),
     InvalidInput(String),
     IoError(String),
+    Conflict(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::NotFound(msg) => write!(f, "{}", msg),
+            StoreError::InsufficientStock(msg) => write!(f, "Not enough stock: {}", msg),
+            StoreError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            StoreError::IoError(msg) => write!(f, "Could not read or write data: {}", msg),
+            StoreError::Conflict(msg) => write!(f, "Save conflict: {}", msg),
+        }
+    }
 }
 
+impl std::error::Error for StoreError {}
+
 impl Store {
     fn new() -> Self {
         let mut s = Store {
@@ -71,34 +439,223 @@ impl Store {
             next_product_id: 1,
             next_sale_id: 1,
             next_purchase_id: 1,
+            settings: Settings::default(),
+            repackagings: Vec::new(),
+            reservations: Vec::new(),
+            admin_log: Vec::new(),
+            invoices: Vec::new(),
+            next_invoice_id: 1,
+            next_reservation_id: 1,
+            dirty_ops: 0,
+            revision: 0,
+            file_modified: None,
+            dry_run: false,
+            dry_run_changes: Vec::new(),
+            read_only: false,
         };
         if s.managers.is_empty() {
-            let default_hash = hash_password(DEFAULT_ADMIN_PASS);
-            s.managers.push(Manager {
-                username: DEFAULT_ADMIN_USER.to_string(),
-                password_hash: default_hash,
-            });
+            s.managers
+                .push(Manager::new(DEFAULT_ADMIN_USER, DEFAULT_ADMIN_PASS, true));
         }
         s
     }
 
+    /// Id for a new product: the next unused one, or with `reuse_ids` the
+    /// lowest gap whose id no sale, purchase, repackaging or variant refers
+    /// to.
+    fn new_product_id(&mut self) -> u32 {
+        if self.settings.reuse_ids {
+            let free = (1..self.next_product_id).find(|&id| {
+                self.find_product(id).is_none()
+                    && !self.sales.iter().any(|s| s.product_id == id)
+                    && !self.purchases.iter().any(|p| p.product_id == id)
+                    && !self.products.iter().any(|p| p.parent_id == Some(id))
+                    && !self
+                        .repackagings
+                        .iter()
+                        .any(|r| r.from_id == id || r.to_id == id)
+            });
+            if let Some(id) = free {
+                return id;
+            }
+        }
+        self.next_product_id += 1;
+        self.next_product_id - 1
+    }
+
     fn add_product(
         &mut self,
         name: String,
         description: String,
         price: f64,
         quantity: i32,
-    ) -> Product {
+    ) -> Result<Product, StoreError> {
+        self.check_writable()?;
         let product = Product {
-            id: self.next_product_id,
+            id: self.new_product_id(),
             name,
             description,
             price,
             quantity,
+            expiry: None,
+            initial_quantity: Some(quantity),
+            parent_id: None,
+            variant_attrs: Vec::new(),
+            tags: Vec::new(),
+            unit_label: None,
+            sku: None,
         };
-        self.next_product_id += 1;
         self.products.push(product.clone());
-        product
+        self.mark_dirty(&format!("added product {} ({})", product.id, product.name));
+        Ok(product)
+    }
+
+    /// Copies a product's description, price, tags and unit label into a
+    /// new product called `new_name` with no stock. Expiry and variant links
+    /// belong to the original and are not copied.
+    fn clone_product(&mut self, id: u32, new_name: String) -> Result<Product, StoreError> {
+        self.check_writable()?;
+        let source = self
+            .find_product(id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", id)))?
+            .clone();
+        let mut product = self.add_product(new_name, source.description, source.price, 0)?;
+        product.tags = source.tags;
+        product.unit_label = source.unit_label;
+        if let Some(stored) = self.products.iter_mut().find(|p| p.id == product.id) {
+            *stored = product.clone();
+        }
+        Ok(product)
+    }
+
+    /// Adds a variant of `parent_id` named after the parent and its
+    /// attribute values. Variants cannot themselves have variants.
+    fn add_variant(
+        &mut self,
+        parent_id: u32,
+        attrs: Vec<(String, String)>,
+        price: f64,
+        quantity: i32,
+    ) -> Result<Product, StoreError> {
+        self.check_writable()?;
+        let parent = self
+            .find_product(parent_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", parent_id)))?;
+        if parent.parent_id.is_some() {
+            return Err(StoreError::InvalidInput(format!(
+                "{} is already a variant",
+                parent.name
+            )));
+        }
+        if attrs.is_empty() {
+            return Err(StoreError::InvalidInput(
+                "A variant needs at least one attribute".into(),
+            ));
+        }
+        let values: Vec<&str> = attrs.iter().map(|(_, v)| v.as_str()).collect();
+        let name = format!("{} ({})", parent.name, values.join(", "));
+        let description = parent.description.clone();
+        let mut variant = self.add_product(name, description, price, quantity)?;
+        if let Some(p) = self.products.iter_mut().find(|p| p.id == variant.id) {
+            p.parent_id = Some(parent_id);
+            p.variant_attrs = attrs;
+            variant = p.clone();
+        }
+        Ok(variant)
+    }
+
+    /// Adds `tag` (trimmed, lowercased) to a product. Adding a tag the
+    /// product already has is a no-op.
+    fn add_tag(&mut self, product_id: u32, tag: &str) -> Result<(), StoreError> {
+        self.check_writable()?;
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() {
+            return Err(StoreError::InvalidInput("Tag cannot be empty".into()));
+        }
+        let product = self
+            .products
+            .iter_mut()
+            .find(|p| p.id == product_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", product_id)))?;
+        if !product.tags.contains(&tag) {
+            product.tags.push(tag);
+            self.mark_dirty(&format!("tagged product {}", product_id));
+        }
+        Ok(())
+    }
+
+    /// Sets a product's SKU; an empty code clears it. A 13-digit code is
+    /// taken as an EAN-13 barcode and rejected if its check digit is wrong.
+    fn set_sku(&mut self, product_id: u32, sku: &str) -> Result<(), StoreError> {
+        self.check_writable()?;
+        let sku = sku.trim();
+        if sku.len() == 13 && sku.chars().all(|c| c.is_ascii_digit()) && !validate_ean13(sku) {
+            return Err(StoreError::InvalidInput(format!(
+                "{} is not a valid EAN-13 barcode",
+                sku
+            )));
+        }
+        let product = self
+            .products
+            .iter_mut()
+            .find(|p| p.id == product_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", product_id)))?;
+        product.sku = (!sku.is_empty()).then(|| sku.to_string());
+        self.mark_dirty(&format!("set the SKU of product {}", product_id));
+        Ok(())
+    }
+
+    /// Every tag used by any product.
+    fn all_tags(&self) -> BTreeSet<String> {
+        self.products
+            .iter()
+            .flat_map(|p| p.tags.iter().cloned())
+            .collect()
+    }
+
+    /// Existing tags close to `partial`: tags starting with it first, then
+    /// the rest by edit distance, ignoring anything too far off to be a typo.
+    fn suggest_tags(&self, partial: &str, max: usize) -> Vec<String> {
+        let partial = partial.trim().to_lowercase();
+        let mut ranked: Vec<(bool, usize, String)> = self
+            .all_tags()
+            .into_iter()
+            .map(|tag| {
+                let prefix = tag.starts_with(&partial);
+                let distance = edit_distance(&partial, &tag);
+                (!prefix, distance, tag)
+            })
+            .filter(|(not_prefix, distance, _)| {
+                !not_prefix || *distance <= TAG_SUGGEST_MAX_DISTANCE
+            })
+            .collect();
+        ranked.sort();
+        ranked
+            .into_iter()
+            .take(max)
+            .map(|(_, _, tag)| tag)
+            .collect()
+    }
+
+    fn variants_of(&self, parent_id: u32) -> Vec<&Product> {
+        self.products
+            .iter()
+            .filter(|p| p.parent_id == Some(parent_id))
+            .collect()
+    }
+
+    /// Top-level products, each with its variants and the combined stock
+    /// of the product and all its variants.
+    fn grouped_inventory(&self) -> Vec<(&Product, Vec<&Product>, i32)> {
+        self.products
+            .iter()
+            .filter(|p| p.parent_id.is_none())
+            .map(|p| {
+                let variants = self.variants_of(p.id);
+                let total = p.quantity + variants.iter().map(|v| v.quantity).sum::<i32>();
+                (p, variants, total)
+            })
+            .collect()
     }
 
     fn edit_product(
@@ -109,6 +666,7 @@ impl Store {
         price: Option<f64>,
         quantity: Option<i32>,
     ) -> Result<Product, StoreError> {
+        self.check_writable()?;
         match self.products.iter_mut().find(|p| p.id == id) {
             Some(p) => {
                 if let Some(n) = name {
@@ -121,18 +679,117 @@ impl Store {
                     p.price = pr;
                 }
                 if let Some(q) = quantity {
+                    p.initial_quantity = Some(p.initial_quantity.unwrap_or(0) + q - p.quantity);
                     p.quantity = q;
                 }
-                Ok(p.clone())
+                let p = p.clone();
+                self.mark_dirty(&format!("edited product {}", p.id));
+                Ok(p)
+            }
+            None => Err(StoreError::NotFound(format!("Product {} not found", id))),
+        }
+    }
+
+    /// Applies a stocktake from a CSV file of `id,counted` rows, setting each
+    /// product's quantity to the counted one as a manual edit. An optional
+    /// `id,...` header and blank lines are skipped. Nothing is changed if
+    /// any row is invalid or names an unknown product. Returns (id, before,
+    /// after) per row.
+    fn reconcile_counts(&mut self, path: &str) -> Result<Vec<(u32, i32, i32)>, StoreError> {
+        self.check_writable()?;
+        let content = fs::read_to_string(path)
+            .map_err(|e| StoreError::IoError(format!("Read error: {}", e)))?;
+        let mut counts = Vec::new();
+        let mut unknown = Vec::new();
+        for (i, row) in parse_csv(&content)?.iter().enumerate() {
+            let first = row[0].trim();
+            if (row.len() == 1 && first.is_empty()) || (i == 0 && first.eq_ignore_ascii_case("id"))
+            {
+                continue;
+            }
+            let counted = row.get(1).and_then(|c| c.trim().parse::<i32>().ok());
+            match (row.len(), first.parse::<u32>(), counted) {
+                (2, Ok(id), Some(counted)) if counted >= 0 => {
+                    if self.find_product(id).is_none() {
+                        unknown.push(id.to_string());
+                    }
+                    counts.push((id, counted));
+                }
+                _ => {
+                    return Err(StoreError::InvalidInput(format!(
+                        "Count line {}: {}",
+                        i + 1,
+                        row.join(",")
+                    )))
+                }
+            }
+        }
+        if !unknown.is_empty() {
+            return Err(StoreError::NotFound(format!(
+                "Unknown product ids in count file: {}",
+                unknown.join(", ")
+            )));
+        }
+        let mut changes = Vec::new();
+        for (id, counted) in counts {
+            let before = self.find_product(id).map_or(0, |p| p.quantity);
+            self.edit_product(id, None, None, None, Some(counted))?;
+            changes.push((id, before, counted));
+        }
+        Ok(changes)
+    }
+
+    fn set_expiry(
+        &mut self,
+        id: u32,
+        expiry: Option<DateTime<Local>>,
+    ) -> Result<Product, StoreError> {
+        self.check_writable()?;
+        match self.products.iter_mut().find(|p| p.id == id) {
+            Some(p) => {
+                p.expiry = expiry;
+                let p = p.clone();
+                self.mark_dirty(&format!("set the expiry of product {}", p.id));
+                Ok(p)
+            }
+            None => Err(StoreError::NotFound(format!("Product {} not found", id))),
+        }
+    }
+
+    /// Sets or, with `None` or a blank label, clears the unit label.
+    fn set_unit_label(
+        &mut self,
+        id: u32,
+        unit_label: Option<String>,
+    ) -> Result<Product, StoreError> {
+        self.check_writable()?;
+        let unit_label = unit_label
+            .map(|u| u.trim().to_string())
+            .filter(|u| !u.is_empty());
+        match self.products.iter_mut().find(|p| p.id == id) {
+            Some(p) => {
+                p.unit_label = unit_label;
+                let p = p.clone();
+                self.mark_dirty(&format!("set the unit label of product {}", p.id));
+                Ok(p)
             }
             None => Err(StoreError::NotFound(format!("Product {} not found", id))),
         }
     }
 
     fn delete_product(&mut self, id: u32) -> Result<(), StoreError> {
+        self.check_writable()?;
+        if !self.variants_of(id).is_empty() {
+            return Err(StoreError::InvalidInput(format!(
+                "Product {} has variants; delete them first",
+                id
+            )));
+        }
         let idx = self.products.iter().position(|p| p.id == id);
         if let Some(i) = idx {
             self.products.remove(i);
+            self.reservations.retain(|r| r.product_id != id);
+            self.mark_dirty(&format!("deleted product {}", id));
             Ok(())
         } else {
             Err(StoreError::NotFound(format!("Product {} not found", id)))
@@ -144,7 +801,9 @@ impl Store {
         product_id: u32,
         quantity: i32,
         purchase_price: f64,
+        lot_number: Option<String>,
     ) -> Result<Purchase, StoreError> {
+        self.check_writable()?;
         if quantity <= 0 {
             return Err(StoreError::InvalidInput("Quantity must be positive".into()));
         }
@@ -159,507 +818,5686 @@ impl Store {
             quantity,
             purchase_price,
             time: Local::now(),
+            lot_number,
         };
         self.next_purchase_id += 1;
         self.purchases.push(pur.clone());
+        self.mark_dirty(&format!("recorded purchase {}", pur.id));
         Ok(pur)
     }
 
+    /// Sells `quantity` units at `sale_price` each. Every check runs before
+    /// anything is changed, so a failed sale leaves stock, sales and
+    /// reservations as they were.
     fn record_sale(
         &mut self,
         product_id: u32,
         quantity: i32,
         sale_price: f64,
     ) -> Result<Sale, StoreError> {
+        self.check_writable()?;
         if quantity <= 0 {
             return Err(StoreError::InvalidInput("Quantity must be positive".into()));
         }
-        let product = match self.products.iter_mut().find(|p| p.id == product_id) {
-            Some(p) => p,
-            None => return Err(StoreError::NotFound(format!("Product {} not found", product_id))),
-        };
-        if product.quantity < quantity {
+        if !sale_price.is_finite() || sale_price < 0.0 {
+            return Err(StoreError::InvalidInput(
+                "Sale price must be zero or more".into(),
+            ));
+        }
+        let now = Local::now();
+        let product = self
+            .find_product(product_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", product_id)))?;
+        let available = product.quantity - self.reserved_quantity(product_id);
+        if available < quantity && !self.settings.allow_negative_stock {
             return Err(StoreError::InsufficientStock(format!(
-                "{} has only {} in stock",
-                product.name, product.quantity
+                "{} has only {} available",
+                product.name, available
             )));
         }
+
+        self.release_expired(now);
+        let product = self
+            .products
+            .iter_mut()
+            .find(|p| p.id == product_id)
+            .expect("product checked above");
         product.quantity -= quantity;
+        let exact_price = (self.settings.cash_rounding > 0.0).then_some(sale_price);
+        let sale_price = round_to_nearest(sale_price, self.settings.cash_rounding);
+        let rate = self.settings.tax_rate / 100.0;
+        let line_total = sale_price * quantity as f64;
+        let tax = if self.settings.prices_include_tax {
+            line_total - line_total / (1.0 + rate)
+        } else {
+            line_total * rate
+        };
         let sale = Sale {
             id: self.next_sale_id,
             product_id,
             quantity,
             sale_price,
             time: Local::now(),
+            tax_rate_bp: (self.settings.tax_rate * 100.0).round() as u32,
+            tax,
+            exact_price,
         };
         self.next_sale_id += 1;
         self.sales.push(sale.clone());
+        self.mark_dirty(&format!("recorded sale {}", sale.id));
         Ok(sale)
     }
 
-    fn total_sales(&self) -> f64 {
-        self.sales.iter().map(|s| s.sale_price * s.quantity as f64).sum()
+    /// Whether selling `quantity` of `product_id` at `price` at `now` looks
+    /// like an accidental repeat: the previous sale was the same product,
+    /// quantity and asked price less than `DUPLICATE_SALE_WINDOW_SECS` ago.
+    fn is_probable_duplicate_sale(
+        &self,
+        product_id: u32,
+        quantity: i32,
+        price: f64,
+        now: DateTime<Local>,
+    ) -> bool {
+        self.sales.last().is_some_and(|last| {
+            let elapsed = now - last.time;
+            last.product_id == product_id
+                && last.quantity == quantity
+                && last.exact_price.unwrap_or(last.sale_price) == price
+                && elapsed >= Duration::zero()
+                && elapsed < Duration::seconds(DUPLICATE_SALE_WINDOW_SECS)
+        })
     }
 
-    fn total_purchases_cost(&self) -> f64 {
-        self.purchases
+    /// Products sold beyond their stock, i.e. with a negative quantity.
+    /// Only possible with `allow_negative_stock`.
+    fn backordered(&self) -> Vec<&Product> {
+        self.products.iter().filter(|p| p.quantity < 0).collect()
+    }
+
+    /// Units held for a product by reservations that have not expired yet.
+    fn reserved_quantity(&self, product_id: u32) -> i32 {
+        let now = Local::now();
+        self.reservations
             .iter()
-            .map(|p| p.purchase_price * p.quantity as f64)
+            .filter(|r| r.product_id == product_id && r.expires > now)
+            .map(|r| r.quantity)
             .sum()
     }
 
-    fn profit(&self) -> f64 {
-        self.total_sales() - self.total_purchases_cost()
+    /// Stock that can be sold or reserved: on-hand quantity minus holds.
+    fn available_quantity(&self, product_id: u32) -> i32 {
+        self.find_product(product_id)
+            .map_or(0, |p| p.quantity - self.reserved_quantity(product_id))
     }
 
-    fn find_product(&self, id: u32) -> Option<&Product> {
-        self.products.iter().find(|p| p.id == id)
+    /// Holds `quantity` units of a product until `expires`.
+    fn reserve(
+        &mut self,
+        product_id: u32,
+        quantity: i32,
+        expires: DateTime<Local>,
+    ) -> Result<Reservation, StoreError> {
+        self.check_writable()?;
+        if quantity <= 0 {
+            return Err(StoreError::InvalidInput("Quantity must be positive".into()));
+        }
+        self.release_expired(Local::now());
+        let product = self
+            .find_product(product_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", product_id)))?;
+        let available = self.available_quantity(product_id);
+        if available < quantity {
+            return Err(StoreError::InsufficientStock(format!(
+                "{} has only {} available",
+                product.name, available
+            )));
+        }
+        let reservation = Reservation {
+            id: self.next_reservation_id,
+            product_id,
+            quantity,
+            expires,
+        };
+        self.next_reservation_id += 1;
+        self.reservations.push(reservation.clone());
+        self.mark_dirty(&format!("recorded reservation {}", reservation.id));
+        Ok(reservation)
     }
 
-    fn save_to_file(&self) -> Result<(), StoreError> {
-        let json = serde_json::to_string_pretty(self)
-            .map_err(|e| StoreError::IoError(format!("Serialize error: {}", e)))?;
-        fs::write(DATA_FILE, json)
-            .map_err(|e| StoreError::IoError(format!("Write error: {}", e)))?;
-        Ok(())
+    /// Cancels a reservation, making its stock available again.
+    fn release(&mut self, reservation_id: u32) -> Result<Reservation, StoreError> {
+        self.check_writable()?;
+        let idx = self
+            .reservations
+            .iter()
+            .position(|r| r.id == reservation_id)
+            .ok_or_else(|| {
+                StoreError::NotFound(format!("Reservation {} not found", reservation_id))
+            })?;
+        self.mark_dirty(&format!("released reservation {}", reservation_id));
+        Ok(self.reservations.remove(idx))
     }
 
-    fn load_from_file() -> Result<Self, StoreError> {
-        match fs::read_to_string(DATA_FILE) {
-            Ok(s) => serde_json::from_str(&s)
-                .map_err(|e| StoreError::IoError(format!("Deserialize error: {}", e))),
-            Err(_) => {
-                let mut st = Store::new();
-                if st.managers.is_empty() {
-                    st.managers.push(Manager {
-                        username: DEFAULT_ADMIN_USER.to_string(),
-                        password_hash: hash_password(DEFAULT_ADMIN_PASS),
-                    });
-                }
-                Ok(st)
+    /// Sells the reserved units at `sale_price` and removes the reservation.
+    /// If the sale fails the reservation is put back where it was.
+    fn fulfill(&mut self, reservation_id: u32, sale_price: f64) -> Result<Sale, StoreError> {
+        self.check_writable()?;
+        let idx = self
+            .reservations
+            .iter()
+            .position(|r| r.id == reservation_id)
+            .ok_or_else(|| {
+                StoreError::NotFound(format!("Reservation {} not found", reservation_id))
+            })?;
+        // The reserved units have to be available to the sale itself.
+        let reservation = self.reservations.remove(idx);
+        match self.record_sale(reservation.product_id, reservation.quantity, sale_price) {
+            Ok(sale) => Ok(sale),
+            Err(e) => {
+                self.reservations.insert(idx, reservation);
+                Err(e)
             }
         }
     }
 
-    fn add_manager(&mut self, username: &str, password: &str) {
-        let hash = hash_password(password);
-        self.managers.push(Manager {
-            username: username.to_string(),
-            password_hash: hash,
+    /// Drops reservations that expired before `now`. Returns how many.
+    fn release_expired(&mut self, now: DateTime<Local>) -> usize {
+        let before = self.reservations.len();
+        self.reservations.retain(|r| r.expires > now);
+        let released = before - self.reservations.len();
+        if released > 0 {
+            self.mark_dirty(&format!("released {} expired reservation(s)", released));
+        }
+        released
+    }
+
+    fn repackage(
+        &mut self,
+        from_id: u32,
+        to_id: u32,
+        from_qty: i32,
+        to_qty: i32,
+    ) -> Result<(), StoreError> {
+        self.check_writable()?;
+        if from_qty <= 0 || to_qty <= 0 {
+            return Err(StoreError::InvalidInput(
+                "Quantities must be positive".into(),
+            ));
+        }
+        if from_id == to_id {
+            return Err(StoreError::InvalidInput(
+                "Source and destination must differ".into(),
+            ));
+        }
+        if self.find_product(to_id).is_none() {
+            return Err(StoreError::NotFound(format!("Product {} not found", to_id)));
+        }
+        let source = self
+            .products
+            .iter_mut()
+            .find(|p| p.id == from_id)
+            .ok_or_else(|| StoreError::NotFound(format!("Product {} not found", from_id)))?;
+        if source.quantity < from_qty {
+            return Err(StoreError::InsufficientStock(format!(
+                "{} has only {} in stock",
+                source.name, source.quantity
+            )));
+        }
+        source.quantity -= from_qty;
+        if let Some(dest) = self.products.iter_mut().find(|p| p.id == to_id) {
+            dest.quantity += to_qty;
+        }
+        self.repackagings.push(Repackaging {
+            from_id,
+            to_id,
+            from_qty,
+            to_qty,
+            time: Local::now(),
         });
+        self.mark_dirty(&format!(
+            "repackaged product {} into product {}",
+            from_id, to_id
+        ));
+        Ok(())
     }
 
-    fn authenticate(&self, username: &str, password: &str) -> bool {
-        let hash = hash_password(password);
-        self.managers
+    /// Remaining purchased quantity per lot for a product, oldest lot first.
+    /// Units sold are taken from the oldest purchases first (FIFO); stock that
+    /// did not come from a purchase is not attributed to any lot. Purchases
+    /// without a lot number are grouped under "(none)".
+    fn stock_by_lot(&self, product_id: u32) -> Vec<(String, i32)> {
+        let mut unsold: i32 = self
+            .sales
             .iter()
-            .any(|m| m.username == username && m.password_hash == hash)
-    }
-}
+            .filter(|s| s.product_id == product_id)
+            .map(|s| s.quantity)
+            .sum();
+        let mut purchases: Vec<&Purchase> = self
+            .purchases
+            .iter()
+            .filter(|p| p.product_id == product_id)
+            .collect();
+        purchases.sort_by_key(|p| (p.time, p.id));
 
-fn hash_password(password: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(password.as_bytes());
-    let res = hasher.finalize();
-    format!("{:x}", res)
-}
+        let mut lots: Vec<(String, i32)> = Vec::new();
+        for p in purchases {
+            let consumed = unsold.min(p.quantity);
+            unsold -= consumed;
+            let remaining = p.quantity - consumed;
+            let lot = p.lot_number.clone().unwrap_or_else(|| "(none)".to_string());
+            match lots.iter_mut().find(|(name, _)| *name == lot) {
+                Some(entry) => entry.1 += remaining,
+                None => lots.push((lot, remaining)),
+            }
+        }
+        lots
+    }
 
-fn prompt(msg: &str) -> String {
-    print!("{}", msg);
-    let _ = io::stdout().flush();
-    let mut buf = String::new();
-    io::stdin()
-        .read_line(&mut buf)
-        .expect("Failed to read input");
-    buf.trim().to_string()
-}
+    /// Net stock change for a product from purchases, sales and repackaging.
+    fn stock_movements(&self, product_id: u32) -> i32 {
+        let purchased: i32 = self
+            .purchases
+            .iter()
+            .filter(|p| p.product_id == product_id)
+            .map(|p| p.quantity)
+            .sum();
+        let sold: i32 = self
+            .sales
+            .iter()
+            .filter(|s| s.product_id == product_id)
+            .map(|s| s.quantity)
+            .sum();
+        let repacked: i32 = self
+            .repackagings
+            .iter()
+            .map(|r| {
+                let mut delta = 0;
+                if r.from_id == product_id {
+                    delta -= r.from_qty;
+                }
+                if r.to_id == product_id {
+                    delta += r.to_qty;
+                }
+                delta
+            })
+            .sum();
+        purchased - sold + repacked
+    }
 
-fn pause() {
-    let _ = prompt("\nPress Enter to continue...");
-}
+    /// For every product: (id, recorded quantity, computed quantity), where
+    /// the computed quantity is the initial quantity plus purchases minus
+    /// sales, adjusted for repackaging. The two differ if the stored
+    /// quantity has drifted or been tampered with.
+    fn verify_stock(&self) -> Vec<(u32, i32, i32)> {
+        self.products
+            .iter()
+            .map(|p| {
+                let computed = p.initial_quantity.unwrap_or(0) + self.stock_movements(p.id);
+                (p.id, p.quantity, computed)
+            })
+            .collect()
+    }
 
-fn main_menu() {
-    println!("Welcome to Rusty Store Inventory Management System");
-    println!("Loading data...");
-}
+    /// Products with stock on hand but no purchase recorded for them, which
+    /// usually means stock was entered directly instead of purchased.
+    fn products_without_purchases(&self) -> Vec<&Product> {
+        self.products
+            .iter()
+            .filter(|p| p.quantity > 0)
+            .filter(|p| !self.purchases.iter().any(|pur| pur.product_id == p.id))
+            .collect()
+    }
 
-fn ui_loop(mut store: Store) {
-    loop {
-        println!("\n--- Main Menu ---");
-        println!("1. Inventory Management");
-        println!("2. Sales Management");
-        println!("3. Purchase Management");
-        println!("4. Reports");
-        println!("5. Save & Exit");
-        let choice = prompt("Select option: ");
-        match choice.as_str() {
-            "1" => inventory_menu(&mut store),
-            "2" => sales_menu(&mut store),
-            "3" => purchases_menu(&mut store),
-            "4" => reports_menu(&mut store),
-            "5" => {
-                match store.save_to_file() {
-                    Ok(_) => println!("Data saved to {}", DATA_FILE),
-                    Err(e) => eprintln!("Error saving: {:?}", e),
-                }
-                println!("Goodbye!");
-                break;
-            }
-            _ => println!("Invalid selection."),
+    /// Purchase cost per unit, averaged over all purchases weighted by
+    /// quantity. `None` if the product was never purchased.
+    fn average_cost(&self, product_id: u32) -> Option<f64> {
+        let (units, cost) = self
+            .purchases
+            .iter()
+            .filter(|p| p.product_id == product_id)
+            .fold((0, 0.0), |(units, cost), p| {
+                (
+                    units + p.quantity,
+                    cost + p.purchase_price * p.quantity as f64,
+                )
+            });
+        if units > 0 {
+            Some(cost / units as f64)
+        } else {
+            None
         }
     }
-}
 
-fn inventory_menu(store: &mut Store) {
-    loop {
-        println!("\n--- Inventory Menu ---");
-        println!("1. List products");
-        println!("2. Add product");
-        println!("3. Edit product");
-        println!("4. Delete product");
-        println!("5. Back");
-        let choice = prompt("Select option: ");
-        match choice.as_str() {
-            "1" => {
-                println!("\nInventory:");
-                for p in &store.products {
-                    println!(
-                        "[{}] {} - {} | ${:.2} | qty: {}",
-                        p.id, p.name, p.description, p.price, p.quantity
-                    );
-                }
-                pause();
+    /// Revenue minus cost basis (average purchase cost, zero if unknown) of
+    /// the product's sales from `start` up to but excluding `end`.
+    fn product_profit(&self, product_id: u32, start: DateTime<Local>, end: DateTime<Local>) -> f64 {
+        let unit_cost = self.average_cost(product_id).unwrap_or(0.0);
+        self.sales
+            .iter()
+            .filter(|s| s.product_id == product_id && s.time >= start && s.time < end)
+            .map(|s| (s.sale_price - unit_cost) * s.quantity as f64)
+            .sum()
+    }
+
+    /// Tax collected on sales in `[start, end)`, keyed by the tax rate in
+    /// basis points. Untaxed sales fall under 0.
+    fn tax_collected_by_rate(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> BTreeMap<u32, f64> {
+        self.sum_sales_by_rate(start, end, |s| s.tax)
+    }
+
+    /// Net amounts the tax in `tax_collected_by_rate` was charged on.
+    fn taxable_base_by_rate(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+    ) -> BTreeMap<u32, f64> {
+        self.sum_sales_by_rate(start, end, Sale::taxable_base)
+    }
+
+    fn sum_sales_by_rate(
+        &self,
+        start: DateTime<Local>,
+        end: DateTime<Local>,
+        amount: impl Fn(&Sale) -> f64,
+    ) -> BTreeMap<u32, f64> {
+        let mut totals: BTreeMap<u32, f64> = BTreeMap::new();
+        for s in self
+            .sales
+            .iter()
+            .filter(|s| s.time >= start && s.time < end)
+        {
+            *totals.entry(s.tax_rate_bp).or_default() += amount(s);
+        }
+        totals
+    }
+
+    /// `product_profit` for every product over the period, most profitable
+    /// first.
+    fn period_profits(&self, start: DateTime<Local>, end: DateTime<Local>) -> Vec<(u32, f64)> {
+        let mut profits: Vec<(u32, f64)> = self
+            .products
+            .iter()
+            .map(|p| (p.id, self.product_profit(p.id, start, end)))
+            .collect();
+        profits.sort_by(|a, b| b.1.total_cmp(&a.1));
+        profits
+    }
+
+    /// Units sold per product in two `[start, end)` periods as (product id,
+    /// units in A, units in B, percent change from A to B), biggest gain
+    /// first. A product missing from one period counts zero there; one that
+    /// only sold in B has an infinite change.
+    fn period_comparison(
+        &self,
+        period_a: (DateTime<Local>, DateTime<Local>),
+        period_b: (DateTime<Local>, DateTime<Local>),
+    ) -> Vec<(u32, i32, i32, f64)> {
+        let mut units: BTreeMap<u32, (i32, i32)> = BTreeMap::new();
+        for s in &self.sales {
+            if s.time >= period_a.0 && s.time < period_a.1 {
+                units.entry(s.product_id).or_default().0 += s.quantity;
             }
-            "2" => {
-                let name = prompt("Name: ");
-                let description = prompt("Description: ");
-                let price_s = prompt("Price: ");
-                let qty_s = prompt("Quantity: ");
-                match (price_s.parse::<f64>(), qty_s.parse::<i32>()) {
-                    (Ok(price), Ok(qty)) => {
-                        let pr = store.add_product(name, description, price, qty);
-                        println!("Product added: {:?}", pr);
-                    }
-                    _ => println!("Invalid price or quantity."),
-                }
-                pause();
+            if s.time >= period_b.0 && s.time < period_b.1 {
+                units.entry(s.product_id).or_default().1 += s.quantity;
             }
-            "3" => {
-                let id_s = prompt("Product id to edit: ");
-                if let Ok(id) = id_s.parse::<u32>() {
-                    let name = prompt("New name (or empty to skip): ");
-                    let desc = prompt("New description (or empty to skip): ");
-                    let price_s = prompt("New price (or empty to skip): ");
-                    let qty_s = prompt("New quantity (or empty to skip): ");
-                    let name_opt = if name.is_empty() { None } else { Some(name) };
-                    let desc_opt = if desc.is_empty() { None } else { Some(desc) };
-                    let price_opt = if price_s.is_empty() {
-                        None
-                    } else {
-                        match price_s.parse::<f64>() {
-                            Ok(v) => Some(v),
-                            Err(_) => {
-                                println!("Invalid price");
-                                None
-                            }
-                        }
-                    };
-                    let qty_opt = if qty_s.is_empty() {
-                        None
+        }
+        let mut rows: Vec<(u32, i32, i32, f64)> = units
+            .into_iter()
+            .map(|(id, (a, b))| {
+                let change = if a == 0 {
+                    if b == 0 {
+                        0.0
                     } else {
-                        match qty_s.parse::<i32>() {
-                            Ok(v) => Some(v),
-                            Err(_) => {
-                                println!("Invalid quantity");
-                                None
-                            }
-                        }
-                    };
-                    match store.edit_product(id, name_opt, desc_opt, price_opt, qty_opt) {
-                        Ok(p) => println!("Updated: {:?}", p),
-                        Err(e) => println!("Error: {:?}", e),
+                        f64::INFINITY
                     }
                 } else {
-                    println!("Invalid id");
-                }
-                pause();
+                    (b - a) as f64 / a as f64 * 100.0
+                };
+                (id, a, b, change)
+            })
+            .collect();
+        rows.sort_by(|x, y| y.3.total_cmp(&x.3));
+        rows
+    }
+
+    /// Margin of the current price (net of VAT) over the average cost, as a
+    /// percentage of that price. `None` without cost data or a positive
+    /// price.
+    fn margin_percent(&self, product: &Product) -> Option<f64> {
+        let price = product.net_price(self.settings.tax_rate, self.settings.prices_include_tax);
+        let cost = self.average_cost(product.id)?;
+        if price <= 0.0 {
+            return None;
+        }
+        Some((price - cost) / price * 100.0)
+    }
+
+    /// Price at which `margin_percent` would equal `target_margin_percent`,
+    /// given the average cost. Includes VAT when stored prices do. `None`
+    /// without cost data or for targets of 100% or more.
+    fn suggest_price(&self, product_id: u32, target_margin_percent: f64) -> Option<f64> {
+        let cost = self.average_cost(product_id)?;
+        if target_margin_percent >= 100.0 {
+            return None;
+        }
+        let net = cost / (1.0 - target_margin_percent / 100.0);
+        if self.settings.prices_include_tax {
+            Some(net * (1.0 + self.settings.tax_rate / 100.0))
+        } else {
+            Some(net)
+        }
+    }
+
+    /// Profit from selling `assumed_units` at `new_price` (net of VAT when
+    /// prices include it) over the current average cost, which counts as 0
+    /// without purchases. Nothing is changed.
+    fn simulate_price_change(&self, product_id: u32, new_price: f64, assumed_units: i32) -> f64 {
+        let net = if self.settings.prices_include_tax {
+            new_price / (1.0 + self.settings.tax_rate / 100.0)
+        } else {
+            new_price
+        };
+        let unit_cost = self.average_cost(product_id).unwrap_or(0.0);
+        (net - unit_cost) * assumed_units as f64
+    }
+
+    /// Products whose margin is below `threshold_percent`, lowest first.
+    /// Products without cost data are skipped.
+    fn low_margin_products(&self, threshold_percent: f64) -> Vec<(u32, f64)> {
+        let mut low: Vec<(u32, f64)> = self
+            .products
+            .iter()
+            .filter_map(|p| self.margin_percent(p).map(|m| (p.id, m)))
+            .filter(|(_, m)| *m < threshold_percent)
+            .collect();
+        low.sort_by(|a, b| a.1.total_cmp(&b.1));
+        low
+    }
+
+    /// Best-selling products as (product id, units sold, revenue), most
+    /// units first, at most `limit` entries.
+    fn top_sellers(&self, limit: usize) -> Vec<(u32, i32, f64)> {
+        let mut totals: BTreeMap<u32, (i32, f64)> = BTreeMap::new();
+        for s in &self.sales {
+            let entry = totals.entry(s.product_id).or_default();
+            entry.0 += s.quantity;
+            entry.1 += s.sale_price * s.quantity as f64;
+        }
+        let mut sellers: Vec<(u32, i32, f64)> = totals
+            .into_iter()
+            .map(|(id, (units, revenue))| (id, units, revenue))
+            .collect();
+        sellers.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.total_cmp(&a.2)));
+        sellers.truncate(limit);
+        sellers
+    }
+
+    /// The `n` products with the fewest units sold as (product id, units
+    /// sold), fewest first. Products never sold count as zero and lead;
+    /// ties keep product order.
+    fn slow_movers(&self, n: usize) -> Vec<(u32, i32)> {
+        let mut movers: Vec<(u32, i32)> = self
+            .products
+            .iter()
+            .map(|p| {
+                let sold = self
+                    .sales
+                    .iter()
+                    .filter(|s| s.product_id == p.id)
+                    .map(|s| s.quantity)
+                    .sum();
+                (p.id, sold)
+            })
+            .collect();
+        movers.sort_by_key(|&(_, sold)| sold);
+        movers.truncate(n);
+        movers
+    }
+
+    fn last_sold(&self, product_id: u32) -> Option<DateTime<Local>> {
+        self.sales
+            .iter()
+            .filter(|s| s.product_id == product_id)
+            .map(|s| s.time)
+            .max()
+    }
+
+    /// Every product with the whole days between its last sale and `now`,
+    /// `None` if never sold. Never-sold products come first, then the
+    /// longest unsold.
+    fn inventory_aging(&self, now: DateTime<Local>) -> Vec<(u32, Option<i64>)> {
+        let mut aging: Vec<(u32, Option<i64>)> = self
+            .products
+            .iter()
+            .map(|p| (p.id, self.last_sold(p.id).map(|t| (now - t).num_days())))
+            .collect();
+        aging.sort_by_key(|&(_, days)| std::cmp::Reverse(days.unwrap_or(i64::MAX)));
+        aging
+    }
+
+    /// Machine-readable report section. Field names are part of the output
+    /// format and covered by tests:
+    /// - `Summary`: `{ total_sales, total_cost, profit }`
+    /// - `Inventory`: `[{ id, name, price, quantity }]`
+    /// - `TopSellers`: `[{ product_id, name, units_sold, revenue }]`
+    fn report_json(&self, section: ReportSection) -> serde_json::Value {
+        match section {
+            ReportSection::Summary => json!({
+                "total_sales": self.total_sales(),
+                "total_cost": self.total_purchases_cost(),
+                "profit": self.profit(),
+            }),
+            ReportSection::Inventory => self
+                .products_ordered(self.settings.report_order)
+                .into_iter()
+                .map(|p| {
+                    json!({
+                        "id": p.id,
+                        "name": p.name,
+                        "price": p.price,
+                        "quantity": p.quantity,
+                    })
+                })
+                .collect(),
+            ReportSection::TopSellers => self
+                .top_sellers(TOP_SELLERS_LIMIT)
+                .into_iter()
+                .map(|(id, units, revenue)| {
+                    json!({
+                        "product_id": id,
+                        "name": self.find_product(id).map(|p| p.name.clone()),
+                        "units_sold": units,
+                        "revenue": revenue,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Net effect of cash rounding on all sales.
+    fn cash_rounding_total(&self) -> f64 {
+        self.sales.iter().map(Sale::rounding_difference).sum()
+    }
+
+    fn total_sales(&self) -> f64 {
+        self.sales.iter().map(|s| s.sale_price * s.quantity as f64).sum()
+    }
+
+    fn total_purchases_cost(&self) -> f64 {
+        self.purchases
+            .iter()
+            .map(|p| p.purchase_price * p.quantity as f64)
+            .sum()
+    }
+
+    fn profit(&self) -> f64 {
+        self.total_sales() - self.total_purchases_cost()
+    }
+
+    /// Sales revenue (inflow) and purchase cost (outflow) per calendar day.
+    fn daily_flows(&self) -> BTreeMap<NaiveDate, (f64, f64)> {
+        let mut days: BTreeMap<NaiveDate, (f64, f64)> = BTreeMap::new();
+        for s in &self.sales {
+            days.entry(s.time.date_naive()).or_default().0 += s.sale_price * s.quantity as f64;
+        }
+        for p in &self.purchases {
+            days.entry(p.time.date_naive()).or_default().1 += p.purchase_price * p.quantity as f64;
+        }
+        days
+    }
+
+    fn daily_cashflow(&self) -> BTreeMap<NaiveDate, f64> {
+        self.daily_flows()
+            .into_iter()
+            .map(|(day, (inflow, outflow))| (day, inflow - outflow))
+            .collect()
+    }
+
+    /// Gross profit of each day's sales over average cost, from the first
+    /// to the last day with a sale. Days without sales are 0.
+    fn daily_profit_series(&self) -> Vec<(NaiveDate, f64)> {
+        let mut days: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+        for s in &self.sales {
+            let unit_cost = self.average_cost(s.product_id).unwrap_or(0.0);
+            *days.entry(s.time.date_naive()).or_default() +=
+                (s.sale_price - unit_cost) * s.quantity as f64;
+        }
+        let (Some(&first), Some(&last)) = (days.keys().next(), days.keys().next_back()) else {
+            return Vec::new();
+        };
+        first
+            .iter_days()
+            .take_while(|day| *day <= last)
+            .map(|day| (day, days.get(&day).copied().unwrap_or(0.0)))
+            .collect()
+    }
+
+    /// Report color for a quantity under the configured stock thresholds.
+    fn stock_color(&self, quantity: i32) -> Color {
+        quantity_color(
+            quantity,
+            self.settings.critical_stock,
+            self.settings.warning_stock,
+        )
+    }
+
+    fn find_product(&self, id: u32) -> Option<&Product> {
+        self.products.iter().find(|p| p.id == id)
+    }
+
+    /// Counts products per quantity range. `buckets` are inclusive upper
+    /// bounds: `[0, 10, 50]` gives the ranges "<= 0", "1-10", "11-50" and
+    /// "51+", so every quantity falls in exactly one. Bounds are sorted and
+    /// duplicates dropped first.
+    fn stock_histogram(&self, buckets: &[i32]) -> Vec<(String, usize)> {
+        let mut bounds = buckets.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        let Some(&last) = bounds.last() else {
+            return vec![("any".to_string(), self.products.len())];
+        };
+        let mut labels = vec![format!("<= {}", bounds[0])];
+        for pair in bounds.windows(2) {
+            let (low, high) = (pair[0] + 1, pair[1]);
+            labels.push(if low == high {
+                high.to_string()
+            } else {
+                format!("{}-{}", low, high)
+            });
+        }
+        labels.push(format!("{}+", last + 1));
+        let mut counts = vec![0; labels.len()];
+        for p in &self.products {
+            let index = bounds.partition_point(|b| *b < p.quantity);
+            counts[index] += 1;
+        }
+        labels.into_iter().zip(counts).collect()
+    }
+
+    /// All products sorted by `order`; ties keep id order.
+    fn products_ordered(&self, order: ProductOrder) -> Vec<&Product> {
+        let mut products: Vec<&Product> = self.products.iter().collect();
+        products.sort_by_key(|p| p.id);
+        match order {
+            ProductOrder::ById => {}
+            ProductOrder::ByName => products.sort_by_key(|p| p.name.to_lowercase()),
+            ProductOrder::ByQuantity => products.sort_by_key(|p| std::cmp::Reverse(p.quantity)),
+            ProductOrder::ByValue => products.sort_by(|a, b| {
+                let value = |p: &Product| p.price * p.quantity as f64;
+                value(b).total_cmp(&value(a))
+            }),
+        }
+        products
+    }
+
+    /// Widths of the ID, Name, Price and Qty columns of the inventory report:
+    /// the widest value in each, but never narrower than its header.
+    fn inventory_column_widths(&self, products: &[&Product]) -> [usize; 4] {
+        let mut widths = ["ID".len(), "Name".len(), "Price".len(), "Qty".len()];
+        for p in products {
+            let cells = [
+                p.id.to_string(),
+                p.name.clone(),
+                self.money(p.price),
+                p.quantity_with_unit(),
+            ];
+            for (width, cell) in widths.iter_mut().zip(&cells) {
+                *width = (*width).max(cell.chars().count());
             }
-            "4" => {
-                let id_s = prompt("Product id to delete: ");
-                if let Ok(id) = id_s.parse::<u32>() {
-                    match store.delete_product(id) {
-                        Ok(_) => println!("Deleted product {}", id),
-                        Err(e) => println!("Error: {:?}", e),
+        }
+        widths
+    }
+
+    /// Products whose name contains `partial`, ignoring case. Names starting
+    /// with it come first, then alphabetical order.
+    fn match_products(&self, partial: &str) -> Vec<&Product> {
+        let needle = partial.trim().to_lowercase();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        let mut matches: Vec<&Product> = self
+            .products
+            .iter()
+            .filter(|p| p.name.to_lowercase().contains(&needle))
+            .collect();
+        matches.sort_by_key(|p| {
+            (
+                !p.name.to_lowercase().starts_with(&needle),
+                p.name.to_lowercase(),
+            )
+        });
+        matches
+    }
+
+    /// Products expiring before `when`, soonest first. Products without an
+    /// expiry date are never included.
+    fn expiring_before(&self, when: DateTime<Local>) -> Vec<&Product> {
+        let mut items: Vec<&Product> = self
+            .products
+            .iter()
+            .filter(|p| matches!(p.expiry, Some(e) if e < when))
+            .collect();
+        items.sort_by_key(|p| p.expiry);
+        items
+    }
+
+    /// Groups existing sales into a new invoice, optionally billed to
+    /// `customer`. Every sale must exist and may appear on only one invoice.
+    fn create_invoice(
+        &mut self,
+        sale_ids: &[u32],
+        customer: Option<String>,
+    ) -> Result<Invoice, StoreError> {
+        self.check_writable()?;
+        if sale_ids.is_empty() {
+            return Err(StoreError::InvalidInput(
+                "An invoice needs at least one sale".into(),
+            ));
+        }
+        for (i, id) in sale_ids.iter().enumerate() {
+            if !self.sales.iter().any(|s| s.id == *id) {
+                return Err(StoreError::NotFound(format!("Sale {} not found", id)));
+            }
+            if sale_ids[..i].contains(id) {
+                return Err(StoreError::InvalidInput(format!(
+                    "Sale {} listed twice",
+                    id
+                )));
+            }
+            if let Some(inv) = self.invoices.iter().find(|inv| inv.sale_ids.contains(id)) {
+                return Err(StoreError::InvalidInput(format!(
+                    "Sale {} is already on invoice {}",
+                    id, inv.id
+                )));
+            }
+        }
+        let invoice = Invoice {
+            id: self.next_invoice_id,
+            sale_ids: sale_ids.to_vec(),
+            customer,
+        };
+        self.next_invoice_id += 1;
+        self.invoices.push(invoice.clone());
+        self.mark_dirty(&format!("created invoice {}", invoice.id));
+        Ok(invoice)
+    }
+
+    fn invoice_total(&self, invoice: &Invoice) -> f64 {
+        self.sales
+            .iter()
+            .filter(|s| invoice.sale_ids.contains(&s.id))
+            .map(|s| s.sale_price * s.quantity as f64)
+            .sum()
+    }
+
+    /// One line per sale followed by the invoice total.
+    fn render_invoice(&self, invoice: &Invoice) -> String {
+        let mut lines = vec![format!("Invoice #{}", invoice.id)];
+        if let Some(customer) = &invoice.customer {
+            lines.push(format!("Customer: {}", customer));
+        }
+        for sale in self
+            .sales
+            .iter()
+            .filter(|s| invoice.sale_ids.contains(&s.id))
+        {
+            let product = self.find_product(sale.product_id).map_or_else(
+                || format!("Product {}", sale.product_id),
+                |p| p.name.clone(),
+            );
+            lines.push(format!(
+                "{} x{} @ {} = {}",
+                product,
+                sale.quantity,
+                self.money(sale.sale_price),
+                self.money(sale.sale_price * sale.quantity as f64)
+            ));
+        }
+        lines.push(format!("TOTAL {}", self.money(self.invoice_total(invoice))));
+        lines.join("\n")
+    }
+
+    /// The full report (inventory, sales, purchases, summary) as lines,
+    /// headings in bold and quantities colored when `color` is set.
+    fn full_report_lines(&self, color: bool) -> Vec<String> {
+        let mut lines = vec![
+            bold("--- FULL REPORT ---", color),
+            bold("Inventory:", color),
+        ];
+        for p in self.products_ordered(self.settings.report_order) {
+            let qty = paint(&p.quantity_with_unit(), self.stock_color(p.quantity), color);
+            lines.push(format!(
+                "[{}] {} — {} — qty {}",
+                p.id,
+                p.name,
+                self.money(p.price),
+                qty
+            ));
+            if self.settings.prices_include_tax {
+                let rate = self.settings.tax_rate;
+                lines.push(format!(
+                    "      net {} + VAT {}",
+                    self.money(p.net_price(rate, true)),
+                    self.money(p.vat_component(rate, true))
+                ));
+            }
+        }
+        lines.push(String::new());
+        lines.push(bold("Sales:", color));
+        for s in &self.sales {
+            lines.push(format!(
+                "[{}] product {} qty {} @ {} each — total {} — {}",
+                s.id,
+                s.product_id,
+                s.quantity,
+                self.money(s.sale_price),
+                self.money(s.sale_price * s.quantity as f64),
+                s.time
+            ));
+        }
+        lines.push(String::new());
+        lines.push(bold("Purchases:", color));
+        for p in &self.purchases {
+            lines.push(format!(
+                "[{}] product {} qty {} @ {} each — total {} — {}",
+                p.id,
+                p.product_id,
+                p.quantity,
+                self.money(p.purchase_price),
+                self.money(p.purchase_price * p.quantity as f64),
+                p.time
+            ));
+        }
+        lines.push(String::new());
+        lines.push(bold("Summary:", color));
+        lines.push(format!(
+            "Total Sales: {}",
+            self.total_money(self.total_sales())
+        ));
+        lines.push(format!(
+            "Total Purchases Cost: {}",
+            self.total_money(self.total_purchases_cost())
+        ));
+        lines.push(format!("Profit: {}", self.total_money(self.profit())));
+        if self.sales.iter().any(|s| s.exact_price.is_some()) {
+            lines.push(format!(
+                "Cash rounding: {}",
+                self.money(self.cash_rounding_total())
+            ));
+        }
+        lines
+    }
+
+    /// Writes the full report to `path` split into pages of
+    /// `lines_per_page` report lines, each page starting with a header and
+    /// ending with a "Page X of Y" footer. Pages are separated by a form feed.
+    fn write_paginated_report(&self, path: &str, lines_per_page: usize) -> Result<(), StoreError> {
+        if lines_per_page == 0 {
+            return Err(StoreError::InvalidInput(
+                "Lines per page must be positive".into(),
+            ));
+        }
+        let lines = self.full_report_lines(false);
+        let pages: Vec<&[String]> = lines.chunks(lines_per_page).collect();
+        let header = format!(
+            "{} — {}",
+            PAGINATED_REPORT_TITLE,
+            Local::now().format("%Y-%m-%d %H:%M")
+        );
+        let mut out = String::new();
+        for (i, page) in pages.iter().enumerate() {
+            if i > 0 {
+                out.push('\u{0C}');
+            }
+            out.push_str(&header);
+            out.push('\n');
+            out.push_str(&"=".repeat(header.chars().count()));
+            out.push_str("\n\n");
+            for line in page.iter() {
+                out.push_str(line);
+                out.push('\n');
+            }
+            out.push_str(&format!("\nPage {} of {}\n", i + 1, pages.len()));
+        }
+        fs::write(path, out).map_err(|e| StoreError::IoError(format!("{}: {}", path, e)))
+    }
+
+    /// One product in the inventory listing.
+    fn listing_line(&self, p: &Product) -> String {
+        format!(
+            "[{}] {} - {} | {} | qty: {}",
+            p.id,
+            p.name,
+            p.description,
+            self.money(p.price),
+            p.quantity_with_unit()
+        )
+    }
+
+    /// All products as CSV with a header row. Fields containing commas,
+    /// quotes or line breaks are quoted.
+    fn export_products_csv(&self) -> String {
+        let mut out = String::from("id,name,description,price,quantity,unit\n");
+        for p in &self.products {
+            let fields = [
+                p.id.to_string(),
+                csv_field(&p.name),
+                csv_field(&p.description),
+                p.price.to_string(),
+                p.quantity.to_string(),
+                csv_field(p.unit_label.as_deref().unwrap_or("")),
+            ];
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Checks that `export_products_csv` reads back to the same products.
+    /// Only exported fields are compared. On mismatch the error lists each
+    /// differing product.
+    fn verify_csv_roundtrip(&self) -> Result<(), StoreError> {
+        let rows = parse_csv(&self.export_products_csv())?;
+        let mut diffs = Vec::new();
+        if rows.len() != self.products.len() + 1 {
+            diffs.push(format!(
+                "expected {} rows, read {}",
+                self.products.len() + 1,
+                rows.len()
+            ));
+        }
+        for (p, row) in self.products.iter().zip(rows.iter().skip(1)) {
+            let expected = [
+                p.id.to_string(),
+                p.name.clone(),
+                p.description.clone(),
+                p.price.to_string(),
+                p.quantity.to_string(),
+                p.unit_label.clone().unwrap_or_default(),
+            ];
+            if row[..] != expected[..] {
+                diffs.push(format!(
+                    "product {}: expected {:?}, read {:?}",
+                    p.id, expected, row
+                ));
+            }
+        }
+        if diffs.is_empty() {
+            Ok(())
+        } else {
+            Err(StoreError::InvalidInput(format!(
+                "CSV round trip differs: {}",
+                diffs.join("; ")
+            )))
+        }
+    }
+
+    fn render_receipt(&self, sale: &Sale) -> String {
+        let product = self.find_product(sale.product_id).map_or_else(
+            || format!("Product {}", sale.product_id),
+            |p| p.name.clone(),
+        );
+        let total = sale.sale_price * sale.quantity as f64;
+        let values = [
+            ("id", sale.id.to_string()),
+            ("number", sale.receipt_number(&self.settings.receipt_prefix)),
+            ("product", product),
+            ("qty", sale.quantity.to_string()),
+            ("price", format_amount(sale.sale_price, &self.settings)),
+            ("total", format_amount(total, &self.settings)),
+            ("currency", self.settings.currency.clone()),
+            ("date", sale.time.format("%Y-%m-%d %H:%M").to_string()),
+        ];
+        fill_template(&self.settings.receipt_template, &values).0
+    }
+
+    fn money(&self, amount: f64) -> String {
+        format_money(amount, &self.settings)
+    }
+
+    /// `amount` in the base currency converted to the currency `to` using
+    /// `EXCHANGE_RATES`. `None` if either currency is not in the table.
+    fn convert_total(&self, amount: f64, to: &str) -> Option<f64> {
+        let from = exchange_rate(&self.settings.base_currency)?;
+        Some(amount / from * exchange_rate(to)?)
+    }
+
+    /// Like `money`, followed by the amount in `settings.display_currency`
+    /// when one is set: "€1,000.00 (1,086.96 USD)".
+    fn total_money(&self, amount: f64) -> String {
+        let converted = self.settings.display_currency.as_deref().and_then(|code| {
+            self.convert_total(amount, code)
+                .map(|c| format!(" ({} {})", format_amount(c, &self.settings), code))
+        });
+        format!("{}{}", self.money(amount), converted.unwrap_or_default())
+    }
+
+    /// Adds products from CSV lines of `name,description,price,quantity`.
+    /// A header line starting with `name,` and blank lines are skipped, and
+    /// the description may itself contain commas. Nothing is added if any
+    /// line is invalid. Returns the number of products added.
+    fn import_products_csv(&mut self, content: &str) -> Result<usize, StoreError> {
+        self.check_writable()?;
+        let mut rows = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (i == 0 && line.to_lowercase().starts_with("name,")) {
+                continue;
+            }
+            let invalid = || StoreError::InvalidInput(format!("CSV line {}: {}", i + 1, line));
+            let mut fields = line.rsplitn(3, ',');
+            let quantity = fields.next().and_then(|q| q.trim().parse::<i32>().ok());
+            let price = fields.next().and_then(|p| p.trim().parse::<f64>().ok());
+            let (name, description) = fields
+                .next()
+                .and_then(|rest| rest.split_once(','))
+                .ok_or_else(invalid)?;
+            match (price, quantity) {
+                (Some(price), Some(quantity)) if !name.trim().is_empty() => rows.push((
+                    name.trim().to_string(),
+                    description.trim().to_string(),
+                    price,
+                    quantity,
+                )),
+                _ => return Err(invalid()),
+            }
+        }
+        let count = rows.len();
+        for (name, description, price, quantity) in rows {
+            self.add_product(name, description, price, quantity)?;
+        }
+        Ok(count)
+    }
+
+    fn set_password(&mut self, username: &str, password: &str) -> Result<(), StoreError> {
+        self.check_writable()?;
+        let manager = self
+            .managers
+            .iter_mut()
+            .find(|m| m.username == username)
+            .ok_or_else(|| StoreError::NotFound(format!("Manager {} not found", username)))?;
+        manager.set_password(password);
+        self.mark_dirty(&format!("changed the password of {}", username));
+        Ok(())
+    }
+
+    /// Fails in `--read-only` mode; called first by methods that change
+    /// the store.
+    fn check_writable(&self) -> Result<(), StoreError> {
+        if self.read_only {
+            Err(StoreError::InvalidInput(
+                "The store is open read-only".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Counts a change towards auto-save and writes it to the log. In
+    /// `--dry-run` mode `change` (e.g. "recorded sale 3") is also announced,
+    /// since it will never be saved.
+    fn mark_dirty(&mut self, change: &str) {
+        self.dirty_ops += 1;
+        if self.dry_run {
+            println!("[dry-run] would have {}", change);
+            log(LogLevel::Info, &format!("[dry-run] {}", change));
+            self.dry_run_changes.push(change.to_string());
+        } else {
+            log(LogLevel::Info, change);
+        }
+    }
+
+    /// Calls `save` once `settings.autosave_interval` changes have piled up
+    /// and resets the counter, whether or not the save succeeds. Returns
+    /// `None` when no save was due.
+    fn autosave_if_due<F>(&mut self, save: F) -> Option<Result<(), StoreError>>
+    where
+        F: FnOnce(&mut Store) -> Result<(), StoreError>,
+    {
+        let interval = self.settings.autosave_interval;
+        if interval == 0 || self.dirty_ops < interval {
+            return None;
+        }
+        self.dirty_ops = 0;
+        Some(save(self))
+    }
+
+    fn save_to_file(&mut self) -> Result<(), StoreError> {
+        self.save_to_path(DATA_FILE)
+    }
+
+    /// Saves to `path` unless someone else has saved there since this store
+    /// was loaded, in which case nothing is written and `Conflict` is
+    /// returned.
+    fn save_to_path(&mut self, path: &str) -> Result<(), StoreError> {
+        self.check_writable()?;
+        if self.dry_run {
+            self.dirty_ops = 0;
+            return Ok(());
+        }
+        if let Some(on_disk) = Store::read_revision(path)? {
+            if on_disk != self.revision {
+                let changed = fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .map(|t| {
+                        DateTime::<Local>::from(t)
+                            .format(" at %H:%M:%S")
+                            .to_string()
+                    })
+                    .unwrap_or_default();
+                let loaded = self.file_modified.map_or_else(String::new, |t| {
+                    DateTime::<Local>::from(t)
+                        .format(", you loaded it at %H:%M:%S")
+                        .to_string()
+                });
+                return Err(StoreError::Conflict(format!(
+                    "{} was saved elsewhere{} (revision {} on disk, yours is {}{})",
+                    path, changed, on_disk, self.revision, loaded
+                )));
+            }
+        }
+        self.revision += 1;
+        let written = serde_json::to_string_pretty(self)
+            .map_err(|e| StoreError::IoError(format!("Serialize error: {}", e)))
+            .and_then(|json| {
+                fs::write(path, json)
+                    .map_err(|e| StoreError::IoError(format!("Write error: {}", e)))
+            });
+        if written.is_err() {
+            self.revision -= 1;
+            return written;
+        }
+        self.dirty_ops = 0;
+        self.file_modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        Ok(())
+    }
+
+    /// Revision stored in the data file at `path`, or `None` if there is no
+    /// file yet.
+    fn read_revision(path: &str) -> Result<Option<u64>, StoreError> {
+        #[derive(Deserialize)]
+        struct OnDisk {
+            #[serde(default)]
+            revision: u64,
+        }
+        match fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str::<OnDisk>(&s)
+                .map(|d| Some(d.revision))
+                .map_err(|e| StoreError::IoError(format!("Deserialize error: {}", e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StoreError::IoError(format!("Read error: {}", e))),
+        }
+    }
+
+    /// Writes products, sales and purchases to a fresh SQLite database at
+    /// `path`, replacing any existing file. Sales and purchases of products
+    /// that have since been deleted get a NULL `product_id`.
+    fn export_sqlite(&self, path: &str) -> Result<(), StoreError> {
+        let db_err = |e: rusqlite::Error| StoreError::IoError(format!("SQLite error: {}", e));
+        match fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(StoreError::IoError(format!("Remove error: {}", e))),
+        }
+        let mut conn = Connection::open(path).map_err(db_err)?;
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE products (
+                 id INTEGER PRIMARY KEY,
+                 name TEXT NOT NULL,
+                 description TEXT NOT NULL,
+                 price REAL NOT NULL,
+                 quantity INTEGER NOT NULL,
+                 expiry TEXT
+             );
+             CREATE TABLE sales (
+                 id INTEGER PRIMARY KEY,
+                 product_id INTEGER REFERENCES products(id),
+                 quantity INTEGER NOT NULL,
+                 sale_price REAL NOT NULL,
+                 time TEXT NOT NULL
+             );
+             CREATE TABLE purchases (
+                 id INTEGER PRIMARY KEY,
+                 product_id INTEGER REFERENCES products(id),
+                 quantity INTEGER NOT NULL,
+                 purchase_price REAL NOT NULL,
+                 time TEXT NOT NULL,
+                 lot_number TEXT
+             );",
+        )
+        .map_err(db_err)?;
+
+        let known = |id: u32| self.find_product(id).map(|p| p.id);
+        let tx = conn.transaction().map_err(db_err)?;
+        for p in &self.products {
+            tx.execute(
+                "INSERT INTO products (id, name, description, price, quantity, expiry)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    p.id,
+                    p.name,
+                    p.description,
+                    p.price,
+                    p.quantity,
+                    p.expiry.map(|e| e.to_rfc3339())
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        for s in &self.sales {
+            tx.execute(
+                "INSERT INTO sales (id, product_id, quantity, sale_price, time)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    s.id,
+                    known(s.product_id),
+                    s.quantity,
+                    s.sale_price,
+                    s.time.to_rfc3339()
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        for p in &self.purchases {
+            tx.execute(
+                "INSERT INTO purchases (id, product_id, quantity, purchase_price, time, lot_number)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    p.id,
+                    known(p.product_id),
+                    p.quantity,
+                    p.purchase_price,
+                    p.time.to_rfc3339(),
+                    p.lot_number
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        tx.commit().map_err(db_err)
+    }
+
+    fn load_from_file() -> Result<Self, StoreError> {
+        Store::load_from_path(DATA_FILE)
+    }
+
+    fn load_from_path(path: &str) -> Result<Self, StoreError> {
+        match fs::read_to_string(path) {
+            Ok(s) => {
+                let mut st: Store = serde_json::from_str(&s)
+                    .map_err(|e| StoreError::IoError(format!("Deserialize error: {}", e)))?;
+                // Older files lack initial quantities; assume their current
+                // stock is consistent with the recorded history.
+                let movements: Vec<i32> = st
+                    .products
+                    .iter()
+                    .map(|p| st.stock_movements(p.id))
+                    .collect();
+                for (p, moved) in st.products.iter_mut().zip(movements) {
+                    p.initial_quantity.get_or_insert(p.quantity - moved);
+                }
+                // Files from before roles: the default account is the admin.
+                if !st.managers.iter().any(|m| m.is_admin) {
+                    for m in st.managers.iter_mut() {
+                        m.is_admin = m.username == DEFAULT_ADMIN_USER;
                     }
-                } else {
-                    println!("Invalid id");
+                }
+                let last_reservation = st.reservations.iter().map(|r| r.id).max().unwrap_or(0);
+                st.next_reservation_id = st.next_reservation_id.max(last_reservation + 1);
+                let last_invoice = st.invoices.iter().map(|i| i.id).max().unwrap_or(0);
+                st.next_invoice_id = st.next_invoice_id.max(last_invoice + 1);
+                st.file_modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+                Ok(st)
+            }
+            Err(_) => {
+                let mut st = Store::new();
+                if st.managers.is_empty() {
+                    st.managers
+                        .push(Manager::new(DEFAULT_ADMIN_USER, DEFAULT_ADMIN_PASS, true));
+                }
+                Ok(st)
+            }
+        }
+    }
+
+    /// Adds a non-admin manager without any checks; only tests need this.
+    #[cfg(test)]
+    fn add_manager(&mut self, username: &str, password: &str) {
+        self.managers.push(Manager::new(username, password, false));
+    }
+
+    /// Checks a manager's password without changing anything, e.g. to
+    /// confirm an admin before a sensitive action.
+    fn verify_password(&self, username: &str, password: &str) -> bool {
+        self.managers
+            .iter()
+            .any(|m| m.username == username && m.password_hash == hash_password(&m.salt, password))
+    }
+
+    /// Logs a manager in: checks the password and records the time on
+    /// success.
+    fn authenticate(&mut self, username: &str, password: &str) -> bool {
+        if !self.verify_password(username, password) {
+            return false;
+        }
+        if let Some(m) = self.managers.iter_mut().find(|m| m.username == username) {
+            m.last_login = Some(Local::now());
+        }
+        self.mark_dirty(&format!("recorded the login of {}", username));
+        true
+    }
+
+    /// (username, last login) for every manager, most recent first; managers
+    /// who never logged in come last.
+    fn last_logins(&self) -> Vec<(&str, Option<DateTime<Local>>)> {
+        let mut logins: Vec<(&str, Option<DateTime<Local>>)> = self
+            .managers
+            .iter()
+            .map(|m| (m.username.as_str(), m.last_login))
+            .collect();
+        logins.sort_by_key(|(_, last_login)| std::cmp::Reverse(*last_login));
+        logins
+    }
+
+    /// Lets a manager replace their own password after confirming the old
+    /// one. The new password gets a fresh salt and must pass
+    /// `password_weakness`.
+    fn change_own_password(
+        &mut self,
+        username: &str,
+        old: &str,
+        new: &str,
+    ) -> Result<(), StoreError> {
+        self.check_writable()?;
+        let manager = self
+            .managers
+            .iter_mut()
+            .find(|m| m.username == username && m.password_hash == hash_password(&m.salt, old))
+            .ok_or_else(|| {
+                StoreError::InvalidInput("Username or current password is wrong".into())
+            })?;
+        if let Some(problem) = password_weakness(new) {
+            return Err(StoreError::InvalidInput(problem));
+        }
+        if new == old {
+            return Err(StoreError::InvalidInput(
+                "New password must differ from the current one".into(),
+            ));
+        }
+        manager.set_password(new);
+        self.mark_dirty(&format!("changed the password of {}", username));
+        Ok(())
+    }
+
+    /// Lets the admin `admin_user` set another manager's password, with a
+    /// fresh salt, and records the reset in `admin_log`. The new password
+    /// must pass `password_weakness`.
+    fn reset_password(
+        &mut self,
+        admin_user: &str,
+        target_user: &str,
+        new_password: &str,
+    ) -> Result<(), StoreError> {
+        self.check_writable()?;
+        if !self
+            .managers
+            .iter()
+            .any(|m| m.username == admin_user && m.is_admin)
+        {
+            return Err(StoreError::InvalidInput(format!(
+                "{} is not an admin",
+                admin_user
+            )));
+        }
+        if let Some(problem) = password_weakness(new_password) {
+            return Err(StoreError::InvalidInput(problem));
+        }
+        let target = self
+            .managers
+            .iter_mut()
+            .find(|m| m.username == target_user)
+            .ok_or_else(|| StoreError::NotFound(format!("Manager {} not found", target_user)))?;
+        target.set_password(new_password);
+        self.admin_log.push(AdminLogEntry {
+            time: Local::now(),
+            admin: admin_user.to_string(),
+            action: format!("reset password of {}", target_user),
+        });
+        self.mark_dirty(&format!("reset the password of {}", target_user));
+        Ok(())
+    }
+}
+
+/// Why `password` is too weak for a manager to choose, if it is: it needs
+/// `MIN_PASSWORD_LEN` characters including a letter and a digit.
+fn password_weakness(password: &str) -> Option<String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        Some(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        ))
+    } else if !password.chars().any(|c| c.is_alphabetic())
+        || !password.chars().any(|c| c.is_ascii_digit())
+    {
+        Some("Password must contain a letter and a digit".to_string())
+    } else {
+        None
+    }
+}
+
+fn hash_password(salt: &str, password: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(password.as_bytes());
+    let res = hasher.finalize();
+    format!("{:x}", res)
+}
+
+/// A salt unique enough for a handful of local accounts: a hash of the
+/// username and the current time.
+fn new_salt(username: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let mut hasher = Sha256::new();
+    hasher.update(username.as_bytes());
+    hasher.update(nanos.to_le_bytes());
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Color {
+    Red,
+    Yellow,
+    Green,
+}
+
+impl Color {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Color::Red => "31",
+            Color::Yellow => "33",
+            Color::Green => "32",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CliOptions {
+    color: bool,
+    log_level: LogLevel,
+    dry_run: bool,
+    read_only: bool,
+}
+
+impl CliOptions {
+    /// Colors are on unless `--no-color` is passed or stdout is not a terminal.
+    /// `--log-level error|info|debug` sets the log verbosity (default info).
+    /// `--dry-run` keeps every change in memory only. `--read-only` allows
+    /// browsing and reports but no changes.
+    fn from_args(args: &[String], stdout_is_tty: bool) -> Self {
+        let no_color = args.iter().any(|a| a == "--no-color");
+        let log_level = match args.iter().position(|a| a == "--log-level") {
+            Some(i) => match args.get(i + 1).and_then(|l| LogLevel::parse(l)) {
+                Some(level) => level,
+                None => {
+                    eprintln!("--log-level expects error, info or debug; using info");
+                    LogLevel::Info
+                }
+            },
+            None => LogLevel::Info,
+        };
+        CliOptions {
+            color: stdout_is_tty && !no_color,
+            log_level,
+            dry_run: args.iter().any(|a| a == "--dry-run"),
+            read_only: args.iter().any(|a| a == "--read-only"),
+        }
+    }
+}
+
+/// Log verbosity; each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LogLevel {
+    Error,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+}
+
+/// Appends timestamped lines at or below `level` to the file at `path`.
+struct Logger {
+    level: LogLevel,
+    path: String,
+}
+
+impl Logger {
+    fn log(&self, level: LogLevel, msg: &str) {
+        if level > self.level {
+            return;
+        }
+        // Logging must never interrupt the store, so write failures are dropped.
+        if let Ok(mut file) = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+        {
+            let _ = writeln!(
+                file,
+                "{} [{}] {}",
+                Local::now().format("%Y-%m-%d %H:%M:%S"),
+                level.label(),
+                msg
+            );
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Writes to the application log set up in `main`; a no-op before that.
+fn log(level: LogLevel, msg: &str) {
+    if let Some(logger) = LOGGER.get() {
+        logger.log(level, msg);
+    }
+}
+
+fn paint(text: &str, color: Color, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", color.ansi_code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn bold(text: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[1m{}\x1b[0m", text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Red at or below `critical`, yellow below `warning`, green otherwise.
+fn quantity_color(quantity: i32, critical: i32, warning: i32) -> Color {
+    if quantity <= critical {
+        Color::Red
+    } else if quantity < warning {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+thread_local! {
+    /// Where `prompt` reads from instead of stdin, if set. Lets tests script
+    /// a session.
+    static SCRIPTED_INPUT: RefCell<Option<Box<dyn BufRead>>> = const { RefCell::new(None) };
+}
+
+/// Reads one trimmed line, or `None` at end of input or on a read error,
+/// in which case the caller abandons what it was asking for.
+fn prompt(msg: &str) -> Option<String> {
+    print!("{}", msg);
+    let _ = io::stdout().flush();
+    let mut buf = String::new();
+    let read = SCRIPTED_INPUT.with(|input| match input.borrow_mut().as_mut() {
+        Some(reader) => reader.read_line(&mut buf),
+        None => io::stdin().read_line(&mut buf),
+    });
+    match read {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buf.trim().to_string()),
+    }
+}
+
+/// Asks for a product by id or part of its name, listing the matching
+/// names to pick from. Asks again when nothing matches; `None` if the user
+/// enters nothing.
+fn select_product(store: &Store) -> Option<u32> {
+    loop {
+        let input = prompt("Product id or name (empty to cancel): ")?;
+        if input.is_empty() {
+            return None;
+        }
+        if let Ok(id) = input.parse::<u32>() {
+            if store.find_product(id).is_some() {
+                return Some(id);
+            }
+        }
+        let matches = store.match_products(&input);
+        if matches.is_empty() {
+            println!("No products match '{}'", input);
+            continue;
+        }
+        for (i, p) in matches.iter().enumerate() {
+            println!(
+                "{}. {} (id {}, qty {})",
+                i + 1,
+                p.name,
+                p.id,
+                p.quantity_with_unit()
+            );
+        }
+        match prompt("Select product: ")?.parse::<usize>() {
+            Ok(n) if (1..=matches.len()).contains(&n) => return Some(matches[n - 1].id),
+            _ => println!("Invalid selection"),
+        }
+    }
+}
+
+/// Asks for an inclusive date range and returns it as `[start, end)`.
+fn prompt_period(label: &str) -> Option<(DateTime<Local>, DateTime<Local>)> {
+    let start = parse_date(&prompt(&format!("{} from date YYYY-MM-DD: ", label))?)?;
+    let end = parse_date(&prompt(&format!(
+        "{} to date YYYY-MM-DD (inclusive): ",
+        label
+    ))?)?;
+    (start <= end).then(|| (start, end + Duration::days(1)))
+}
+
+/// Whether an operation worth `amount` is over a confirmation `threshold`.
+/// A threshold of 0 or less never asks.
+fn needs_confirmation(amount: f64, threshold: f64) -> bool {
+    threshold > 0.0 && amount > threshold
+}
+
+/// Asks for a typed "yes" before a sale or purchase worth more than the
+/// configured threshold. True when the operation may go ahead.
+fn confirm_amount(store: &Store, amount: f64) -> bool {
+    if !needs_confirmation(amount, store.settings.confirm_above) {
+        return true;
+    }
+    prompt(&format!(
+        "This is worth {}. Type yes to continue: ",
+        store.money(amount)
+    ))
+    .is_some_and(|answer| answer.eq_ignore_ascii_case("yes"))
+}
+
+/// Like `prompt`, but without echoing where the terminal allows it.
+fn prompt_password(msg: &str) -> Option<String> {
+    print!("{}", msg);
+    let _ = io::stdout().flush();
+    match read_password() {
+        Ok(password) => Some(password),
+        Err(_) => prompt("Password (fallback): "),
+    }
+}
+
+/// Substitutes `{name}` placeholders in `template` and turns literal `\n`
+/// into line breaks. Placeholders without a value are left as written and
+/// returned alongside the result.
+fn fill_template(template: &str, values: &[(&str, String)]) -> (String, Vec<String>) {
+    let mut out = String::new();
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 1..start + len];
+        match values.iter().find(|(k, _)| *k == name) {
+            Some((_, v)) => out.push_str(v),
+            None => {
+                out.push_str(&rest[start..=start + len]);
+                unknown.push(name.to_string());
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    (out.replace("\\n", "\n"), unknown)
+}
+
+/// Placeholders in a receipt template that `render_receipt` cannot fill.
+fn unknown_receipt_placeholders(template: &str) -> Vec<String> {
+    let values: Vec<(&str, String)> = RECEIPT_PLACEHOLDERS
+        .iter()
+        .map(|k| (*k, String::new()))
+        .collect();
+    fill_template(template, &values).1
+}
+
+/// Splits `s` into lines of at most `width` characters, breaking on
+/// whitespace. Words longer than `width` are broken mid-word.
+fn wrap_text(s: &str, width: usize) -> Vec<String> {
+    if width == 0 {
+        return vec![s.to_string()];
+    }
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in s.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if word.is_empty() {
+            continue;
+        }
+        if current.is_empty() {
+            current = word;
+        } else if current.chars().count() + 1 + word.chars().count() <= width {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Quotes a CSV field if it contains a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Splits CSV text into rows of fields. Quoted fields may contain commas,
+/// line breaks and doubled quotes.
+fn parse_csv(content: &str) -> Result<Vec<Vec<String>>, StoreError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(StoreError::InvalidInput(
+            "Unterminated quoted CSV field".into(),
+        ));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// One block character per value, from ▁ for the minimum to █ for the
+/// maximum. A flat series is drawn at mid height.
+fn sparkline(values: &[f64]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if max == min {
+                BLOCKS[3]
+            } else {
+                BLOCKS[((v - min) / (max - min) * 7.0).round() as usize]
+            }
+        })
+        .collect()
+}
+
+/// Whether `code` is 13 digits whose last is the EAN-13 check digit:
+/// the other digits weighted 1, 3, 1, 3, ... from the left, summed, and
+/// the check digit brings the sum up to a multiple of 10.
+fn validate_ean13(code: &str) -> bool {
+    let digits: Vec<u32> = code.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() != 13 || code.len() != 13 {
+        return false;
+    }
+    let sum: u32 = digits[..12]
+        .iter()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+        .sum();
+    (10 - sum % 10) % 10 == digits[12]
+}
+
+/// `value` rounded to the nearest multiple of `step`, halves away from
+/// zero; a `step` of 0 or less returns `value` unchanged. The nudge and the
+/// final rounding keep floating-point noise out of results like 1.25.
+fn round_to_nearest(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    let steps = value / step;
+    let steps = (steps + steps.signum() * 1e-9).round();
+    (steps * step * 1e9).round() / 1e9
+}
+
+/// Levenshtein distance between two strings, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// `amount` in whole minor units (cents for 2 decimals), rounded.
+fn minor_units(amount: f64, decimals: usize) -> u64 {
+    (amount * 10f64.powi(decimals as i32)).round() as u64
+}
+
+/// Units of `code` per US dollar, ignoring case.
+fn exchange_rate(code: &str) -> Option<f64> {
+    EXCHANGE_RATES
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, rate)| *rate)
+}
+
+/// Formats `amount` with `settings.currency_decimals` decimals and the
+/// separators from `settings`, without a currency symbol, e.g.
+/// `-1,234,567.89`.
+fn format_amount(amount: f64, settings: &Settings) -> String {
+    let decimals = settings.currency_decimals.min(MAX_CURRENCY_DECIMALS);
+    let scale = 10u64.pow(decimals as u32);
+    let units = minor_units(amount.abs(), decimals);
+    let digits = (units / scale).to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(settings.thousands_separator);
+        }
+        grouped.push(c);
+    }
+    let sign = if amount < 0.0 && units > 0 { "-" } else { "" };
+    if decimals == 0 {
+        return format!("{}{}", sign, grouped);
+    }
+    format!(
+        "{}{}{}{:0width$}",
+        sign,
+        grouped,
+        settings.decimal_separator,
+        units % scale,
+        width = decimals
+    )
+}
+
+/// Formats `amount` as money in the configured currency, e.g.
+/// `$1,234,567.89` or `-$5.00`.
+fn format_money(amount: f64, settings: &Settings) -> String {
+    let formatted = format_amount(amount, settings);
+    match formatted.strip_prefix('-') {
+        Some(rest) => format!("-{}{}", settings.currency, rest),
+        None => format!("{}{}", settings.currency, formatted),
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date as local midnight.
+fn parse_date(s: &str) -> Option<DateTime<Local>> {
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Auto-saves to `DATA_FILE` if enough changes have accumulated. A failed
+/// save only prints a warning.
+fn autosave(store: &mut Store) {
+    match store.autosave_if_due(Store::save_to_file) {
+        Some(Ok(())) if store.dry_run => {}
+        Some(Ok(())) => {
+            println!("(auto-saved to {})", DATA_FILE);
+            log(LogLevel::Debug, &format!("auto-saved {}", DATA_FILE));
+        }
+        Some(Err(e)) => {
+            println!("Warning: auto-save failed: {}", e);
+            log(LogLevel::Error, &format!("auto-save failed: {}", e));
+        }
+        None => {}
+    }
+}
+
+fn pause() {
+    let _ = prompt("\nPress Enter to continue...");
+}
+
+fn main_menu() {
+    println!("Welcome to Rusty Store Inventory Management System");
+    println!("Loading data...");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MainAction {
+    Inventory,
+    Sales,
+    Purchases,
+    Reports,
+    Settings,
+    SaveAndExit,
+}
+
+/// Maps a main menu keypress to its action: the menu number or the
+/// shortcut letter shown in brackets, in either case.
+fn key_action(key: char) -> Option<MainAction> {
+    match key.to_ascii_lowercase() {
+        '1' | 'i' => Some(MainAction::Inventory),
+        '2' | 's' => Some(MainAction::Sales),
+        '3' | 'p' => Some(MainAction::Purchases),
+        '4' | 'r' => Some(MainAction::Reports),
+        '5' | 'o' => Some(MainAction::Settings),
+        '6' | 'q' => Some(MainAction::SaveAndExit),
+        _ => None,
+    }
+}
+
+/// Reads a single keypress in raw mode, or `None` if raw mode is not
+/// available so the caller can fall back to line input.
+#[cfg(feature = "single-key")]
+fn read_key() -> Option<char> {
+    use crossterm::event::{read, Event, KeyCode, KeyEventKind};
+    use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+    if !io::stdin().is_terminal() || enable_raw_mode().is_err() {
+        return None;
+    }
+    print!("Press a key: ");
+    let _ = io::stdout().flush();
+    let key = loop {
+        match read() {
+            Ok(Event::Key(k)) if k.kind == KeyEventKind::Press => {
+                if let KeyCode::Char(c) = k.code {
+                    break Some(c);
+                }
+            }
+            Ok(_) => {}
+            Err(_) => break None,
+        }
+    };
+    let _ = disable_raw_mode();
+    println!();
+    key
+}
+
+#[cfg(not(feature = "single-key"))]
+fn read_key() -> Option<char> {
+    None
+}
+
+fn ui_loop(mut store: Store, username: &str, opts: &CliOptions) {
+    if store.read_only {
+        kiosk_menu(&store, opts);
+        println!("Goodbye!");
+        return;
+    }
+    loop {
+        println!("\n--- Main Menu ---");
+        println!("1. [I]nventory Management");
+        println!("2. [S]ales Management");
+        println!("3. [P]urchase Management");
+        println!("4. [R]eports");
+        println!("5. Settings ([O]ptions)");
+        println!("6. Save & Exit ([Q])");
+        let action = match read_key() {
+            Some(key) => key_action(key),
+            None => match prompt("Select option: ") {
+                None => Some(MainAction::SaveAndExit),
+                Some(choice) => {
+                    let mut chars = choice.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => key_action(c),
+                        _ => None,
+                    }
+                }
+            },
+        };
+        match action {
+            Some(MainAction::Inventory) => inventory_menu(&mut store),
+            Some(MainAction::Sales) => sales_menu(&mut store),
+            Some(MainAction::Purchases) => purchases_menu(&mut store),
+            Some(MainAction::Reports) => reports_menu(&store, opts),
+            Some(MainAction::Settings) => settings_menu(&mut store, username),
+            Some(MainAction::SaveAndExit) => {
+                if save_on_exit(&mut store) {
+                    println!("Goodbye!");
+                    break;
+                }
+            }
+            None => println!("Invalid selection."),
+        }
+    }
+}
+
+/// The only menu in `--read-only` mode: listing, search and reports.
+fn kiosk_menu(store: &Store, opts: &CliOptions) {
+    loop {
+        println!("\n--- Main Menu (read-only) ---");
+        println!("1. List products");
+        println!("2. Search products");
+        println!("3. Reports");
+        println!("4. Exit");
+        let Some(choice) = prompt("Select option: ") else {
+            break;
+        };
+        match choice.as_str() {
+            "1" => {
+                println!("\nInventory:");
+                for p in &store.products {
+                    println!("{}", store.listing_line(p));
+                }
+                pause();
+            }
+            "2" => {
+                let Some(query) = prompt("Product name or part of it: ") else {
+                    continue;
+                };
+                let matches = store.match_products(&query);
+                if matches.is_empty() {
+                    println!("No products match '{}'", query);
+                }
+                for p in matches {
+                    println!("{}", store.listing_line(p));
                 }
                 pause();
             }
-            "5" => break,
+            "3" => reports_menu(store, opts),
+            "4" => break,
             _ => println!("Invalid selection"),
         }
     }
-}
+}
+
+/// Saves before exiting. If another manager saved in the meantime, lets the
+/// user reload their data, overwrite it, or exit without saving. Returns
+/// `false` if the user reloaded and wants to keep working.
+fn save_on_exit(store: &mut Store) -> bool {
+    loop {
+        match store.save_to_file() {
+            Ok(_) if store.dry_run => return true,
+            Ok(_) => {
+                println!("Data saved to {}", DATA_FILE);
+                log(LogLevel::Info, &format!("saved {}", DATA_FILE));
+                return true;
+            }
+            Err(StoreError::Conflict(msg)) => {
+                println!("Warning: {}", msg);
+                println!("1. Reload the latest data (discards your unsaved changes)");
+                println!("2. Overwrite it with your data");
+                println!("3. Exit without saving");
+                match prompt("Select option: ").as_deref() {
+                    Some("1") => {
+                        match Store::load_from_file() {
+                            Ok(latest) => {
+                                let dry_run = store.dry_run;
+                                *store = latest;
+                                store.dry_run = dry_run;
+                                println!(
+                                    "Reloaded {}. Redo your changes and save again.",
+                                    DATA_FILE
+                                );
+                            }
+                            Err(e) => {
+                                eprintln!("Error reloading: {}", e);
+                                log(LogLevel::Error, &format!("reload failed: {}", e));
+                            }
+                        }
+                        return false;
+                    }
+                    Some("2") => {
+                        if let Ok(Some(on_disk)) = Store::read_revision(DATA_FILE) {
+                            store.revision = on_disk;
+                        }
+                    }
+                    _ => return true,
+                }
+            }
+            Err(e) => {
+                eprintln!("Error saving: {}", e);
+                log(LogLevel::Error, &format!("save failed: {}", e));
+                return true;
+            }
+        }
+    }
+}
+
+fn inventory_menu(store: &mut Store) {
+    loop {
+        println!("\n--- Inventory Menu ---");
+        println!("1. List products");
+        println!("2. Add product");
+        println!("3. Edit product");
+        println!("4. Delete product");
+        println!("5. Repackage stock");
+        println!("6. Add variant");
+        println!("7. List products grouped by parent");
+        println!("8. Tag product");
+        println!("9. Duplicate product");
+        println!("10. Reconcile stocktake counts");
+        println!("11. Suggest price");
+        println!("12. What-if price change");
+        println!("13. Set SKU / barcode");
+        println!("14. Back");
+        let Some(choice) = prompt("Select option: ") else {
+            break;
+        };
+        match choice.as_str() {
+            "1" => {
+                println!("\nInventory:");
+                for p in &store.products {
+                    println!("{}", store.listing_line(p));
+                }
+                pause();
+            }
+            "2" => {
+                let Some(name) = prompt("Name: ") else {
+                    continue;
+                };
+                let Some(description) = prompt("Description: ") else {
+                    continue;
+                };
+                let Some(price_s) = prompt("Price: ") else {
+                    continue;
+                };
+                let Some(qty_s) = prompt("Quantity: ") else {
+                    continue;
+                };
+                let Some(unit) = prompt("Unit label, e.g. kg or box (or empty for none): ") else {
+                    continue;
+                };
+                let Some(expiry_s) = prompt("Expiry date YYYY-MM-DD (or empty for none): ") else {
+                    continue;
+                };
+                let expiry = if expiry_s.is_empty() {
+                    Ok(None)
+                } else {
+                    parse_date(&expiry_s).map(Some).ok_or(())
+                };
+                match (price_s.parse::<f64>(), qty_s.parse::<i32>(), expiry) {
+                    (Ok(price), Ok(qty), Ok(expiry)) => {
+                        let mut pr = match store.add_product(name, description, price, qty) {
+                            Ok(pr) => pr,
+                            Err(e) => {
+                                println!("Error: {}", e);
+                                pause();
+                                continue;
+                            }
+                        };
+                        if !unit.is_empty() {
+                            if let Ok(p) = store.set_unit_label(pr.id, Some(unit)) {
+                                pr = p;
+                            }
+                        }
+                        if expiry.is_some() {
+                            if let Ok(p) = store.set_expiry(pr.id, expiry) {
+                                pr = p;
+                            }
+                        }
+                        println!("Product added: {:?}", pr);
+                    }
+                    _ => println!("Invalid price, quantity or expiry date."),
+                }
+                pause();
+            }
+            "3" => {
+                let Some(id_s) = prompt("Product id to edit: ") else {
+                    continue;
+                };
+                if let Ok(id) = id_s.parse::<u32>() {
+                    let Some(name) = prompt("New name (or empty to skip): ") else {
+                        continue;
+                    };
+                    let Some(desc) = prompt("New description (or empty to skip): ") else {
+                        continue;
+                    };
+                    let Some(price_s) = prompt("New price (or empty to skip): ") else {
+                        continue;
+                    };
+                    let Some(qty_s) = prompt("New quantity (or empty to skip): ") else {
+                        continue;
+                    };
+                    let Some(unit) = prompt("New unit label (\"none\" to clear, empty to skip): ")
+                    else {
+                        continue;
+                    };
+                    let Some(expiry_s) =
+                        prompt("New expiry YYYY-MM-DD (\"none\" to clear, empty to skip): ")
+                    else {
+                        continue;
+                    };
+                    let name_opt = if name.is_empty() { None } else { Some(name) };
+                    let desc_opt = if desc.is_empty() { None } else { Some(desc) };
+                    let price_opt = if price_s.is_empty() {
+                        None
+                    } else {
+                        match price_s.parse::<f64>() {
+                            Ok(v) => Some(v),
+                            Err(_) => {
+                                println!("Invalid price");
+                                None
+                            }
+                        }
+                    };
+                    let qty_opt = if qty_s.is_empty() {
+                        None
+                    } else {
+                        match qty_s.parse::<i32>() {
+                            Ok(v) => Some(v),
+                            Err(_) => {
+                                println!("Invalid quantity");
+                                None
+                            }
+                        }
+                    };
+                    // Each step returns the product as it now stands, so the
+                    // last successful one is what gets printed.
+                    let mut result = store.edit_product(id, name_opt, desc_opt, price_opt, qty_opt);
+                    if result.is_ok() {
+                        if unit.eq_ignore_ascii_case("none") {
+                            result = store.set_unit_label(id, None);
+                        } else if !unit.is_empty() {
+                            result = store.set_unit_label(id, Some(unit));
+                        }
+                    }
+                    if result.is_ok() {
+                        if expiry_s.eq_ignore_ascii_case("none") {
+                            result = store.set_expiry(id, None);
+                        } else if !expiry_s.is_empty() {
+                            match parse_date(&expiry_s) {
+                                Some(d) => result = store.set_expiry(id, Some(d)),
+                                None => println!("Invalid expiry date"),
+                            }
+                        }
+                    }
+                    match result {
+                        Ok(p) => println!("Updated: {:?}", p),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                } else {
+                    println!("Invalid id");
+                }
+                pause();
+            }
+            "4" => {
+                let Some(id_s) = prompt("Product id to delete: ") else {
+                    continue;
+                };
+                if let Ok(id) = id_s.parse::<u32>() {
+                    match store.delete_product(id) {
+                        Ok(_) => println!("Deleted product {}", id),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                } else {
+                    println!("Invalid id");
+                }
+                pause();
+            }
+            "5" => {
+                let Some(from_s) = prompt("Source product id: ") else {
+                    continue;
+                };
+                let Some(from_qty_s) = prompt("Source quantity to use: ") else {
+                    continue;
+                };
+                let Some(to_s) = prompt("Destination product id: ") else {
+                    continue;
+                };
+                let Some(to_qty_s) = prompt("Destination quantity produced: ") else {
+                    continue;
+                };
+                match (
+                    from_s.parse::<u32>(),
+                    from_qty_s.parse::<i32>(),
+                    to_s.parse::<u32>(),
+                    to_qty_s.parse::<i32>(),
+                ) {
+                    (Ok(from), Ok(from_qty), Ok(to), Ok(to_qty)) => {
+                        match store.repackage(from, to, from_qty, to_qty) {
+                            Ok(_) => println!(
+                                "Repackaged {} of product {} into {} of product {}",
+                                from_qty, from, to_qty, to
+                            ),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    }
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "6" => {
+                let Some(parent_s) = prompt("Parent product id: ") else {
+                    continue;
+                };
+                let Some(attrs_s) = prompt("Attributes (e.g. size=M, color=red): ") else {
+                    continue;
+                };
+                let Some(price_s) = prompt("Price: ") else {
+                    continue;
+                };
+                let Some(qty_s) = prompt("Quantity: ") else {
+                    continue;
+                };
+                let attrs: Option<Vec<(String, String)>> = attrs_s
+                    .split(',')
+                    .filter(|a| !a.trim().is_empty())
+                    .map(|a| {
+                        a.split_once('=')
+                            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                    })
+                    .collect();
+                match (
+                    parent_s.parse::<u32>(),
+                    attrs,
+                    price_s.parse::<f64>(),
+                    qty_s.parse::<i32>(),
+                ) {
+                    (Ok(parent), Some(attrs), Ok(price), Ok(qty)) => {
+                        match store.add_variant(parent, attrs, price, qty) {
+                            Ok(v) => println!("Variant added: {:?}", v),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    }
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "7" => {
+                println!("\nInventory by product:");
+                for (p, variants, total) in store.grouped_inventory() {
+                    let unit = p
+                        .unit_label
+                        .as_deref()
+                        .map_or(String::new(), |u| format!(" {}", u));
+                    println!("[{}] {} | total qty: {}{}", p.id, p.name, total, unit);
+                    for v in variants {
+                        let attrs: Vec<String> = v
+                            .variant_attrs
+                            .iter()
+                            .map(|(k, val)| format!("{}={}", k, val))
+                            .collect();
+                        println!(
+                            "    [{}] {} | {} | qty: {}",
+                            v.id,
+                            attrs.join(", "),
+                            store.money(v.price),
+                            v.quantity_with_unit()
+                        );
+                    }
+                }
+                pause();
+            }
+            "8" => {
+                let Some(id_s) = prompt("Product id: ") else {
+                    continue;
+                };
+                let Some(tag_s) = prompt("Tag: ") else {
+                    continue;
+                };
+                let tag_s = tag_s.to_lowercase();
+                let Ok(id) = id_s.parse::<u32>() else {
+                    println!("Invalid input");
+                    pause();
+                    continue;
+                };
+                let mut tag = tag_s.clone();
+                if !store.all_tags().contains(&tag_s) {
+                    let suggestions = store.suggest_tags(&tag_s, 3);
+                    if !suggestions.is_empty() {
+                        println!("Similar existing tags:");
+                        for (i, s) in suggestions.iter().enumerate() {
+                            println!("{}. {}", i + 1, s);
+                        }
+                        println!("0. Create new tag '{}'", tag_s);
+                        let Some(pick) = prompt("Select option: ") else {
+                            continue;
+                        };
+                        match pick.parse::<usize>() {
+                            Ok(0) => {}
+                            Ok(n) if n <= suggestions.len() => tag = suggestions[n - 1].clone(),
+                            _ => {
+                                println!("Invalid selection");
+                                pause();
+                                continue;
+                            }
+                        }
+                    }
+                }
+                match store.add_tag(id, &tag) {
+                    Ok(()) => println!("Tagged product {} with '{}'", id, tag.trim()),
+                    Err(e) => println!("Error: {}", e),
+                }
+                pause();
+            }
+            "9" => {
+                let Some(id_s) = prompt("Product id to duplicate: ") else {
+                    continue;
+                };
+                let Some(name) = prompt("Name for the copy: ") else {
+                    continue;
+                };
+                match id_s.parse::<u32>() {
+                    Ok(_) if name.is_empty() => println!("Name cannot be empty"),
+                    Ok(id) => match store.clone_product(id, name) {
+                        Ok(p) => println!("Created product {} ({}) with no stock", p.id, p.name),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid input"),
+                }
+                pause();
+            }
+            "10" => {
+                let Some(path) = prompt("Count file (CSV of id,counted): ") else {
+                    continue;
+                };
+                match store.reconcile_counts(&path) {
+                    Ok(changes) => {
+                        for (id, before, after) in &changes {
+                            let name = store.find_product(*id).map_or("", |p| p.name.as_str());
+                            println!(
+                                "[{}] {}: {} -> {} ({:+})",
+                                id,
+                                name,
+                                before,
+                                after,
+                                after - before
+                            );
+                        }
+                        println!("Reconciled {} products", changes.len());
+                    }
+                    Err(e) => println!("Error: {}", e),
+                }
+                pause();
+            }
+            "11" => {
+                let Some(id_s) = prompt("Product id: ") else {
+                    continue;
+                };
+                let Some(margin_s) = prompt("Target margin in percent: ") else {
+                    continue;
+                };
+                match (
+                    id_s.parse::<u32>(),
+                    margin_s.trim_end_matches('%').parse::<f64>(),
+                ) {
+                    (Ok(id), Ok(margin)) => match store.find_product(id) {
+                        None => println!("Product {} not found", id),
+                        Some(p) => match store.suggest_price(id, margin) {
+                            Some(price) => println!(
+                                "Suggested price for {}: {} (current {})",
+                                p.name,
+                                store.money(price),
+                                store.money(p.price)
+                            ),
+                            None if margin >= 100.0 => println!("Margin must be below 100%"),
+                            None => println!("{} has no purchase cost to price from", p.name),
+                        },
+                    },
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "12" => {
+                let Some(id_s) = prompt("Product id: ") else {
+                    continue;
+                };
+                let Some(price_s) = prompt("New price: ") else {
+                    continue;
+                };
+                let Some(units_s) = prompt("Units you expect to sell: ") else {
+                    continue;
+                };
+                match (
+                    id_s.parse::<u32>(),
+                    price_s.parse::<f64>(),
+                    units_s.parse::<i32>(),
+                ) {
+                    (Ok(id), Ok(price), Ok(units)) => match store.find_product(id) {
+                        None => println!("Product {} not found", id),
+                        Some(p) => {
+                            let current = store.simulate_price_change(id, p.price, units);
+                            let projected = store.simulate_price_change(id, price, units);
+                            println!(
+                                "{} units of {}: profit {} at {} vs {} at the current {} ({})",
+                                units,
+                                p.name,
+                                store.money(projected),
+                                store.money(price),
+                                store.money(current),
+                                store.money(p.price),
+                                store.money(projected - current)
+                            );
+                            if store.average_cost(id).is_none() {
+                                println!("Note: no purchases recorded, so cost is taken as 0");
+                            }
+                        }
+                    },
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "13" => {
+                let Some(id_s) = prompt("Product id: ") else {
+                    continue;
+                };
+                match id_s.parse::<u32>() {
+                    Ok(id) => {
+                        let Some(sku) = prompt("SKU or EAN-13 barcode (empty to clear): ") else {
+                            continue;
+                        };
+                        match store.set_sku(id, &sku) {
+                            Ok(()) => println!("SKU updated"),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    }
+                    Err(_) => println!("Invalid input"),
+                }
+                pause();
+            }
+            "14" => break,
+            _ => println!("Invalid selection"),
+        }
+        autosave(store);
+    }
+}
+
+fn sales_menu(store: &mut Store) {
+    loop {
+        println!("\n--- Sales Menu ---");
+        println!("1. Record sale");
+        println!("2. List sales");
+        println!("3. Print receipt");
+        println!("4. Reserve stock");
+        println!("5. List reservations");
+        println!("6. Release reservation");
+        println!("7. Fulfill reservation");
+        println!("8. Create invoice");
+        println!("9. Print invoice");
+        println!("10. Back");
+        let Some(choice) = prompt("Select option: ") else {
+            break;
+        };
+        match choice.as_str() {
+            "1" => {
+                let Some(pid) = select_product(store) else {
+                    continue;
+                };
+                let Some(qty_s) = prompt("Quantity: ") else {
+                    continue;
+                };
+                let Some(price_s) = prompt("Sale price per unit: ") else {
+                    continue;
+                };
+                match (qty_s.parse::<i32>(), price_s.parse::<f64>()) {
+                    (Ok(qty), Ok(price))
+                        if store.is_probable_duplicate_sale(pid, qty, price, Local::now())
+                            && !prompt(
+                                "Same as the sale just recorded. Record it again? (y/N): ",
+                            )
+                            .is_some_and(|answer| answer.eq_ignore_ascii_case("y")) =>
+                    {
+                        println!("Sale cancelled")
+                    }
+                    (Ok(qty), Ok(price)) if !confirm_amount(store, price * qty as f64) => {
+                        println!("Sale cancelled")
+                    }
+                    (Ok(qty), Ok(price)) => match store.record_sale(pid, qty, price) {
+                        Ok(sale) => {
+                            println!("Recorded sale: {:?}", sale);
+                            let profit = sale.sale_price * sale.quantity as f64;
+                            println!("Total sale amount: {}", store.money(profit));
+                            if let Some(p) = store.find_product(pid).filter(|p| p.quantity < 0) {
+                                println!(
+                                    "Warning: {} is backordered, short by {}",
+                                    p.name, -p.quantity
+                                );
+                            }
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "2" => {
+                println!("\nSales history:");
+                for s in &store.sales {
+                    if let Some(prod) = store.find_product(s.product_id) {
+                        println!(
+                            "[{}] {} {} x{} @ {} each = {} at {}",
+                            s.id,
+                            s.receipt_number(&store.settings.receipt_prefix),
+                            prod.name,
+                            s.quantity,
+                            store.money(s.sale_price),
+                            store.money(s.sale_price * s.quantity as f64),
+                            s.time
+                        );
+                    }
+                }
+                println!("Total sales: {}", store.money(store.total_sales()));
+                pause();
+            }
+            "3" => {
+                let Some(id_s) = prompt("Sale id: ") else {
+                    continue;
+                };
+                let sale = id_s
+                    .parse::<u32>()
+                    .ok()
+                    .and_then(|id| store.sales.iter().find(|s| s.id == id));
+                match sale {
+                    Some(sale) => {
+                        for name in unknown_receipt_placeholders(&store.settings.receipt_template) {
+                            println!("Warning: unknown receipt placeholder {{{}}}", name);
+                        }
+                        println!("\n{}", store.render_receipt(sale));
+                    }
+                    None => println!("Sale not found"),
+                }
+                pause();
+            }
+            "4" => {
+                let Some(pid_s) = prompt("Product id: ") else {
+                    continue;
+                };
+                let Some(qty_s) = prompt("Quantity: ") else {
+                    continue;
+                };
+                let Some(days_s) = prompt("Hold for how many days: ") else {
+                    continue;
+                };
+                match (
+                    pid_s.parse::<u32>(),
+                    qty_s.parse::<i32>(),
+                    days_s.parse::<i64>(),
+                ) {
+                    (Ok(pid), Ok(qty), Ok(days)) if days > 0 => {
+                        let expires = Local::now() + Duration::days(days);
+                        match store.reserve(pid, qty, expires) {
+                            Ok(r) => println!(
+                                "Reservation {} holds {} until {}",
+                                r.id,
+                                r.quantity,
+                                r.expires.format("%Y-%m-%d %H:%M")
+                            ),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    }
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "5" => {
+                let released = store.release_expired(Local::now());
+                if released > 0 {
+                    println!("Released {} expired reservation(s)", released);
+                }
+                println!("\nReservations:");
+                for r in &store.reservations {
+                    let name = store
+                        .find_product(r.product_id)
+                        .map_or("?", |p| p.name.as_str());
+                    println!(
+                        "[{}] {} x{} until {} | available: {}",
+                        r.id,
+                        name,
+                        r.quantity,
+                        r.expires.format("%Y-%m-%d %H:%M"),
+                        store.available_quantity(r.product_id)
+                    );
+                }
+                pause();
+            }
+            "6" => {
+                let Some(id_s) = prompt("Reservation id: ") else {
+                    continue;
+                };
+                match id_s.parse::<u32>() {
+                    Ok(id) => match store.release(id) {
+                        Ok(r) => println!(
+                            "Released {} unit(s) of product {}",
+                            r.quantity, r.product_id
+                        ),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid input"),
+                }
+                pause();
+            }
+            "7" => {
+                let Some(id_s) = prompt("Reservation id: ") else {
+                    continue;
+                };
+                let Some(price_s) = prompt("Sale price per unit: ") else {
+                    continue;
+                };
+                match (id_s.parse::<u32>(), price_s.parse::<f64>()) {
+                    (Ok(id), Ok(price)) => match store.fulfill(id, price) {
+                        Ok(sale) => println!("Recorded sale: {:?}", sale),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "8" => {
+                let Some(ids_s) = prompt("Sale ids (comma separated): ") else {
+                    continue;
+                };
+                let Some(customer) = prompt("Customer (or empty): ") else {
+                    continue;
+                };
+                let customer = (!customer.is_empty()).then_some(customer);
+                let ids: Result<Vec<u32>, _> = ids_s
+                    .split(',')
+                    .map(|id| id.trim().parse::<u32>())
+                    .collect();
+                match ids {
+                    Ok(ids) => match store.create_invoice(&ids, customer) {
+                        Ok(invoice) => {
+                            println!(
+                                "Created invoice {} for {}",
+                                invoice.id,
+                                store.money(store.invoice_total(&invoice))
+                            );
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid input"),
+                }
+                pause();
+            }
+            "9" => {
+                let Some(id_s) = prompt("Invoice id: ") else {
+                    continue;
+                };
+                let invoice = id_s
+                    .parse::<u32>()
+                    .ok()
+                    .and_then(|id| store.invoices.iter().find(|i| i.id == id));
+                match invoice {
+                    Some(invoice) => println!("\n{}", store.render_invoice(invoice)),
+                    None => println!("Invoice not found"),
+                }
+                pause();
+            }
+            "10" => break,
+            _ => println!("Invalid selection"),
+        }
+        autosave(store);
+    }
+}
+
+fn purchases_menu(store: &mut Store) {
+    loop {
+        println!("\n--- Purchases Menu ---");
+        println!("1. Record purchase");
+        println!("2. List purchases");
+        println!("3. Back");
+        let Some(choice) = prompt("Select option: ") else {
+            break;
+        };
+        match choice.as_str() {
+            "1" => {
+                let Some(pid) = select_product(store) else {
+                    continue;
+                };
+                let Some(qty_s) = prompt("Quantity: ") else {
+                    continue;
+                };
+                let Some(price_s) = prompt("Purchase price per unit: ") else {
+                    continue;
+                };
+                let Some(lot_s) = prompt("Lot number (or empty for none): ") else {
+                    continue;
+                };
+                let lot = if lot_s.is_empty() { None } else { Some(lot_s) };
+                match (qty_s.parse::<i32>(), price_s.parse::<f64>()) {
+                    (Ok(qty), Ok(price)) if !confirm_amount(store, price * qty as f64) => {
+                        println!("Purchase cancelled")
+                    }
+                    (Ok(qty), Ok(price)) => match store.record_purchase(pid, qty, price, lot) {
+                        Ok(pur) => {
+                            println!("Recorded purchase: {:?}", pur);
+                            println!(
+                                "Total cost: {}",
+                                store.money(pur.purchase_price * pur.quantity as f64)
+                            );
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    _ => println!("Invalid input"),
+                }
+                pause();
+            }
+            "2" => {
+                println!("\nPurchase history:");
+                for p in &store.purchases {
+                    if let Some(prod) = store.find_product(p.product_id) {
+                        println!(
+                            "[{}] {} x{} @ {} each = {} at {}{}",
+                            p.id,
+                            prod.name,
+                            p.quantity,
+                            store.money(p.purchase_price),
+                            store.money(p.purchase_price * p.quantity as f64),
+                            p.time,
+                            p.lot_number
+                                .as_ref()
+                                .map(|l| format!(" (lot {})", l))
+                                .unwrap_or_default()
+                        );
+                    }
+                }
+                println!(
+                    "Total purchases cost: {}",
+                    store.money(store.total_purchases_cost())
+                );
+                pause();
+            }
+            "3" => break,
+            _ => println!("Invalid selection"),
+        }
+        autosave(store);
+    }
+}
+
+fn reports_menu(store: &Store, opts: &CliOptions) {
+    let color = opts.color;
+    loop {
+        println!("\n--- Reports Menu ---");
+        println!("1. Inventory report");
+        println!("2. Sales & Profit summary");
+        println!("3. Purchase history");
+        println!("4. Full report (all)");
+        println!("5. Expiring soon");
+        println!("6. Daily cash flow");
+        println!("7. Stock by lot");
+        println!("8. Verify stock integrity");
+        println!("9. Export to SQLite");
+        println!("10. Low margin products");
+        println!("11. Emit JSON");
+        println!("12. Products without purchases");
+        println!("13. Slow movers");
+        println!("14. Inventory aging");
+        println!("15. Write paginated report");
+        println!("16. Profit by product for a period");
+        println!("17. Export products to CSV");
+        println!("18. Backordered products");
+        println!("19. Verify CSV export round trip");
+        println!("20. Tax collected by rate");
+        println!("21. Manager last logins");
+        println!("22. Stock level histogram");
+        println!("23. Daily profit trend");
+        println!("24. Compare sales between two periods");
+        println!("25. Back");
+        let Some(choice) = prompt("Select option: ") else {
+            break;
+        };
+        match choice.as_str() {
+            "1" => {
+                println!("\n{}", bold("Inventory Report:", color));
+                let ordered = store.products_ordered(store.settings.report_order);
+                let [id_w, name_w, price_w, qty_w] = store.inventory_column_widths(&ordered);
+                // Where the description column starts: every column plus its
+                // separating space.
+                let desc_column = id_w + name_w + price_w + qty_w + 4;
+                let header = format!(
+                    "{:<id_w$} {:<name_w$} {:<price_w$} {:<qty_w$} Description",
+                    "ID", "Name", "Price", "Qty"
+                );
+                println!("{}", bold(&header, color));
+                for p in &ordered {
+                    let qty = format!("{:<qty_w$}", p.quantity_with_unit());
+                    let desc = wrap_text(&p.description, store.settings.description_width);
+                    println!(
+                        "{:<id_w$} {:<name_w$} {:<price_w$} {} {}",
+                        p.id,
+                        p.name,
+                        store.money(p.price),
+                        paint(&qty, store.stock_color(p.quantity), color),
+                        desc[0]
+                    );
+                    for line in &desc[1..] {
+                        println!("{:width$}{}", "", line, width = desc_column);
+                    }
+                }
+                if store.settings.prices_include_tax {
+                    let rate = store.settings.tax_rate;
+                    println!("\n{}", bold(&format!("VAT breakdown ({}%):", rate), color));
+                    for p in &ordered {
+                        println!(
+                            "[{}] {} — gross {} = net {} + VAT {}",
+                            p.id,
+                            p.name,
+                            store.money(p.price),
+                            store.money(p.net_price(rate, true)),
+                            store.money(p.vat_component(rate, true))
+                        );
+                    }
+                }
+                pause();
+            }
+            "2" => {
+                println!("\n{}", bold("Sales Summary:", color));
+                println!("Total Sales: {}", store.total_money(store.total_sales()));
+                println!(
+                    "Total Purchases Cost: {}",
+                    store.total_money(store.total_purchases_cost())
+                );
+                println!("Estimated Profit: {}", store.total_money(store.profit()));
+                if store.sales.iter().any(|s| s.exact_price.is_some()) {
+                    println!(
+                        "Included cash rounding: {}",
+                        store.money(store.cash_rounding_total())
+                    );
+                }
+                pause();
+            }
+            "3" => {
+                println!("\n{}", bold("Purchases:", color));
+                for p in &store.purchases {
+                    println!(
+                        "[{}] Product {} qty {} @ {} on {}",
+                        p.id,
+                        p.product_id,
+                        p.quantity,
+                        store.money(p.purchase_price),
+                        p.time
+                    );
+                }
+                pause();
+            }
+            "4" => {
+                println!();
+                for line in store.full_report_lines(color) {
+                    println!("{}", line);
+                }
+                pause();
+            }
+            "5" => {
+                let Some(days_s) = prompt("Horizon in days: ") else {
+                    continue;
+                };
+                match days_s.parse::<i64>() {
+                    Ok(days) if days >= 0 => {
+                        let horizon = Local::now() + Duration::days(days);
+                        let items = store.expiring_before(horizon);
+                        let title = format!("Expiring within {} days:", days);
+                        println!("\n{}", bold(&title, color));
+                        if items.is_empty() {
+                            println!("Nothing expires in that window.");
+                        }
+                        for p in items {
+                            if let Some(exp) = p.expiry {
+                                println!(
+                                    "[{}] {} — qty {} — expires {}",
+                                    p.id,
+                                    p.name,
+                                    p.quantity_with_unit(),
+                                    exp.format("%Y-%m-%d")
+                                );
+                            }
+                        }
+                    }
+                    _ => println!("Invalid number of days"),
+                }
+                pause();
+            }
+            "6" => {
+                println!("\n{}", bold("Daily Cash Flow:", color));
+                let header = format!(
+                    "{:<12} {:>12} {:>12} {:>12} {:>12}",
+                    "Date", "Inflow", "Outflow", "Net", "Cumulative"
+                );
+                println!("{}", bold(&header, color));
+                let flows = store.daily_flows();
+                let mut running = 0.0;
+                for (day, net) in store.daily_cashflow() {
+                    let (inflow, outflow) = flows[&day];
+                    running += net;
+                    println!(
+                        "{:<12} {:>12} {:>12} {:>12} {:>12}",
+                        day.format("%Y-%m-%d").to_string(),
+                        format_amount(inflow, &store.settings),
+                        format_amount(outflow, &store.settings),
+                        format_amount(net, &store.settings),
+                        format_amount(running, &store.settings)
+                    );
+                }
+                pause();
+            }
+            "7" => {
+                let Some(id_s) = prompt("Product id: ") else {
+                    continue;
+                };
+                let product = id_s
+                    .parse::<u32>()
+                    .ok()
+                    .and_then(|id| store.find_product(id));
+                match product {
+                    Some(p) => {
+                        let title = format!("Stock by lot for {}:", p.name);
+                        println!("\n{}", bold(&title, color));
+                        let lots = store.stock_by_lot(p.id);
+                        if lots.is_empty() {
+                            println!("No purchases recorded for this product.");
+                        }
+                        for (lot, qty) in lots {
+                            println!("{:<20} {}", lot, qty);
+                        }
+                    }
+                    None => println!("Product not found"),
+                }
+                pause();
+            }
+            "8" => {
+                println!("\n{}", bold("Stock Integrity Check:", color));
+                let header = format!(
+                    "{:<5} {:<20} {:>10} {:>10}",
+                    "ID", "Name", "Recorded", "Computed"
+                );
+                println!("{}", bold(&header, color));
+                let mut mismatches = 0;
+                for (id, recorded, computed) in store.verify_stock() {
+                    let name = store.find_product(id).map_or("", |p| p.name.as_str());
+                    let flag = if recorded == computed {
+                        String::new()
+                    } else {
+                        mismatches += 1;
+                        paint("  MISMATCH", Color::Red, color)
+                    };
+                    println!(
+                        "{:<5} {:<20} {:>10} {:>10}{}",
+                        id, name, recorded, computed, flag
+                    );
+                }
+                if mismatches == 0 {
+                    println!("{}", paint("All stock levels match.", Color::Green, color));
+                } else {
+                    println!("{} product(s) do not match their history.", mismatches);
+                }
+                pause();
+            }
+            "9" => {
+                let Some(path) = prompt("SQLite file (default store.db): ") else {
+                    continue;
+                };
+                let path = if path.is_empty() {
+                    "store.db".to_string()
+                } else {
+                    path
+                };
+                match store.export_sqlite(&path) {
+                    Ok(()) => println!("Exported to {}", path),
+                    Err(e) => println!("Error: {}", e),
+                }
+                pause();
+            }
+            "10" => {
+                let Some(threshold_s) = prompt("Margin threshold in percent: ") else {
+                    continue;
+                };
+                match threshold_s.trim_end_matches('%').parse::<f64>() {
+                    Ok(threshold) => {
+                        let title = format!("Products with margin below {}%:", threshold);
+                        println!("\n{}", bold(&title, color));
+                        let low = store.low_margin_products(threshold);
+                        if low.is_empty() {
+                            println!("None.");
+                        }
+                        for (id, margin) in low {
+                            let p = store.find_product(id).expect("listed products exist");
+                            let avg = store.average_cost(id).unwrap_or_default();
+                            let line = format!(
+                                "[{}] {} — price {} — avg cost {} — margin {:.1}%",
+                                p.id,
+                                p.name,
+                                store.money(p.price),
+                                store.money(avg),
+                                margin
+                            );
+                            println!("{}", paint(&line, Color::Red, color));
+                        }
+                        let unknown: Vec<&str> = store
+                            .products
+                            .iter()
+                            .filter(|p| store.margin_percent(p).is_none())
+                            .map(|p| p.name.as_str())
+                            .collect();
+                        if !unknown.is_empty() {
+                            println!("No cost or price data (skipped): {}", unknown.join(", "));
+                        }
+                    }
+                    Err(_) => println!("Invalid threshold"),
+                }
+                pause();
+            }
+            "11" => {
+                let Some(section_s) = prompt("Section (summary, inventory, top-sellers): ") else {
+                    continue;
+                };
+                match ReportSection::parse(&section_s) {
+                    Some(section) => {
+                        let value = store.report_json(section);
+                        match serde_json::to_string_pretty(&value) {
+                            Ok(text) => println!("{}", text),
+                            Err(e) => println!("Error: {}", e),
+                        }
+                    }
+                    None => println!("Unknown section"),
+                }
+                pause();
+            }
+            "12" => {
+                println!("\n{}", bold("Stock Without Purchase Records:", color));
+                let phantom = store.products_without_purchases();
+                if phantom.is_empty() {
+                    println!(
+                        "{}",
+                        paint("Every stocked product has a purchase.", Color::Green, color)
+                    );
+                }
+                for p in phantom {
+                    let line = format!("[{}] {} | qty: {}", p.id, p.name, p.quantity_with_unit());
+                    println!("{}", paint(&line, Color::Red, color));
+                }
+                pause();
+            }
+            "13" => {
+                let Some(n_s) = prompt("How many products (default 10): ") else {
+                    continue;
+                };
+                let n = if n_s.is_empty() {
+                    Ok(10)
+                } else {
+                    n_s.parse::<usize>()
+                };
+                match n {
+                    Ok(n) => {
+                        println!("\n{}", bold("Slow Movers:", color));
+                        println!(
+                            "{}",
+                            bold(
+                                &format!("{:<5} {:<20} {:>10}", "ID", "Name", "Units sold"),
+                                color
+                            )
+                        );
+                        for (id, sold) in store.slow_movers(n) {
+                            let name = store.find_product(id).map_or("", |p| p.name.as_str());
+                            let line = format!("{:<5} {:<20} {:>10}", id, name, sold);
+                            if sold == 0 {
+                                println!("{}", paint(&line, Color::Red, color));
+                            } else {
+                                println!("{}", line);
+                            }
+                        }
+                    }
+                    Err(_) => println!("Invalid number"),
+                }
+                pause();
+            }
+            "14" => {
+                println!(
+                    "\n{}",
+                    bold("Inventory Aging (days since last sale):", color)
+                );
+                for (id, days) in store.inventory_aging(Local::now()) {
+                    let Some(p) = store.find_product(id) else {
+                        continue;
+                    };
+                    let age = days.map_or_else(|| "never sold".to_string(), |d| d.to_string());
+                    let line = format!(
+                        "[{}] {} | qty: {} | {}",
+                        p.id,
+                        p.name,
+                        p.quantity_with_unit(),
+                        age
+                    );
+                    if days.is_none() && p.quantity > 0 {
+                        println!("{}", paint(&line, Color::Red, color));
+                    } else {
+                        println!("{}", line);
+                    }
+                }
+                pause();
+            }
+            "15" => {
+                let Some(path) = prompt("Output file (default report.txt): ") else {
+                    continue;
+                };
+                let path = if path.is_empty() {
+                    "report.txt".to_string()
+                } else {
+                    path
+                };
+                let Some(lines_s) = prompt("Lines per page (default 50): ") else {
+                    continue;
+                };
+                let lines = if lines_s.is_empty() {
+                    Ok(50)
+                } else {
+                    lines_s.parse::<usize>()
+                };
+                match lines {
+                    Ok(n) => match store.write_paginated_report(&path, n) {
+                        Ok(()) => println!("Report written to {}", path),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid number"),
+                }
+                pause();
+            }
+            "16" => {
+                let Some(start_s) = prompt("From date YYYY-MM-DD: ") else {
+                    continue;
+                };
+                let Some(end_s) = prompt("To date YYYY-MM-DD (inclusive): ") else {
+                    continue;
+                };
+                match (parse_date(&start_s), parse_date(&end_s)) {
+                    (Some(start), Some(end)) if start <= end => {
+                        let title = format!("Profit by product, {} to {}:", start_s, end_s);
+                        println!("\n{}", bold(&title, color));
+                        for (id, profit) in store.period_profits(start, end + Duration::days(1)) {
+                            let name = store.find_product(id).map_or("", |p| p.name.as_str());
+                            let amount = format!("{:>12}", store.money(profit));
+                            let amount = if profit < 0.0 {
+                                paint(&amount, Color::Red, color)
+                            } else {
+                                amount
+                            };
+                            println!("{:<5} {:<20} {}", id, name, amount);
+                        }
+                    }
+                    _ => println!("Invalid date range"),
+                }
+                pause();
+            }
+            "17" => {
+                let Some(path) = prompt("CSV file (default products.csv): ") else {
+                    continue;
+                };
+                let path = if path.is_empty() {
+                    "products.csv".to_string()
+                } else {
+                    path
+                };
+                match fs::write(&path, store.export_products_csv()) {
+                    Ok(()) => println!("Exported {} products to {}", store.products.len(), path),
+                    Err(e) => println!("Error: {}", e),
+                }
+                pause();
+            }
+            "18" => {
+                println!("\n{}", bold("Backordered Products:", color));
+                let backordered = store.backordered();
+                if backordered.is_empty() {
+                    println!("None.");
+                }
+                for p in backordered {
+                    let line = format!("[{}] {} | short by {}", p.id, p.name, -p.quantity);
+                    println!("{}", paint(&line, Color::Red, color));
+                }
+                pause();
+            }
+            "19" => {
+                match store.verify_csv_roundtrip() {
+                    Ok(()) => println!(
+                        "{}",
+                        paint("CSV export reads back identically.", Color::Green, color)
+                    ),
+                    Err(e) => println!("{}", paint(&e.to_string(), Color::Red, color)),
+                }
+                pause();
+            }
+            "20" => {
+                let Some(start_s) = prompt("From date YYYY-MM-DD: ") else {
+                    continue;
+                };
+                let Some(end_s) = prompt("To date YYYY-MM-DD (inclusive): ") else {
+                    continue;
+                };
+                match (parse_date(&start_s), parse_date(&end_s)) {
+                    (Some(start), Some(end)) if start <= end => {
+                        let title = format!("Tax by rate, {} to {}:", start_s, end_s);
+                        println!("\n{}", bold(&title, color));
+                        let header = format!("{:>8} {:>14} {:>12}", "Rate", "Taxable base", "Tax");
+                        println!("{}", bold(&header, color));
+                        let end = end + Duration::days(1);
+                        let bases = store.taxable_base_by_rate(start, end);
+                        let taxes = store.tax_collected_by_rate(start, end);
+                        if taxes.is_empty() {
+                            println!("No sales in this period.");
+                        }
+                        for ((rate_bp, base), tax) in bases.into_iter().zip(taxes.into_values()) {
+                            println!(
+                                "{:>7}% {:>14} {:>12}",
+                                rate_bp as f64 / 100.0,
+                                store.money(base),
+                                store.money(tax)
+                            );
+                        }
+                    }
+                    _ => println!("Invalid date range"),
+                }
+                pause();
+            }
+            "21" => {
+                println!("\n{}", bold("Manager last logins:", color));
+                for (username, last_login) in store.last_logins() {
+                    let when = last_login.map_or("never".to_string(), |t| {
+                        t.format("%Y-%m-%d %H:%M").to_string()
+                    });
+                    println!("{:<20} {}", username, when);
+                }
+                pause();
+            }
+            "22" => {
+                println!("\n{}", bold("Products by stock level:", color));
+                let histogram = store.stock_histogram(STOCK_HISTOGRAM_BUCKETS);
+                let max = histogram.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+                let label_width = histogram.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+                for (label, count) in histogram {
+                    let bar = "#".repeat(count * HISTOGRAM_BAR_WIDTH / max);
+                    println!("{:>width$} | {} {}", label, bar, count, width = label_width);
+                }
+                pause();
+            }
+            "23" => {
+                let series = store.daily_profit_series();
+                match (series.first(), series.last()) {
+                    (Some((first, _)), Some((last, _))) => {
+                        let title = format!("Daily profit, {} to {}:", first, last);
+                        println!("\n{}", bold(&title, color));
+                        let values: Vec<f64> = series.iter().map(|(_, v)| *v).collect();
+                        println!("{}", sparkline(&values));
+                        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                        let average = values.iter().sum::<f64>() / values.len() as f64;
+                        println!(
+                            "min {}  max {}  average {}",
+                            store.money(min),
+                            store.money(max),
+                            store.money(average)
+                        );
+                    }
+                    _ => println!("No sales yet."),
+                }
+                pause();
+            }
+            "24" => {
+                match (prompt_period("Period A"), prompt_period("Period B")) {
+                    (Some(a), Some(b)) => {
+                        println!("\n{}", bold("Units sold, period A vs period B:", color));
+                        for (id, units_a, units_b, change) in store.period_comparison(a, b) {
+                            let name = store.find_product(id).map_or("", |p| p.name.as_str());
+                            let change_s = if change.is_infinite() {
+                                format!("{:>9}", "new")
+                            } else {
+                                format!("{:>+8.1}%", change)
+                            };
+                            let change_s = if change < 0.0 {
+                                paint(&change_s, Color::Red, color)
+                            } else {
+                                paint(&change_s, Color::Green, color)
+                            };
+                            println!(
+                                "{:<5} {:<20} {:>6} {:>6} {}",
+                                id, name, units_a, units_b, change_s
+                            );
+                        }
+                    }
+                    _ => println!("Invalid date range"),
+                }
+                pause();
+            }
+            "25" => break,
+            _ => println!("Invalid selection"),
+        }
+    }
+}
+
+/// `username` is the logged-in manager, whose password option 14 changes.
+fn settings_menu(store: &mut Store, username: &str) {
+    loop {
+        let before = store.settings.clone();
+        println!("\n--- Settings Menu ---");
+        println!(
+            "1. Description wrap width (current: {})",
+            store.settings.description_width
+        );
+        println!("2. Tax rate (current: {}%)", store.settings.tax_rate);
+        println!(
+            "3. Prices include tax (current: {})",
+            yes_no(store.settings.prices_include_tax)
+        );
+        println!(
+            "4. Receipt template (current: {})",
+            store.settings.receipt_template
+        );
+        println!(
+            "5. Auto-save interval (current: every {} changes, 0 = off)",
+            store.settings.autosave_interval
+        );
+        println!(
+            "6. Number format (current: {})",
+            format_money(1234567.89, &store.settings)
+        );
+        println!("7. Reset a manager's password");
+        println!(
+            "8. Allow negative stock (current: {})",
+            yes_no(store.settings.allow_negative_stock)
+        );
+        println!(
+            "9. Currencies (base: {}, reports also in: {})",
+            store.settings.base_currency,
+            store.settings.display_currency.as_deref().unwrap_or("none")
+        );
+        println!(
+            "10. Report product order (current: by {})",
+            store.settings.report_order.label()
+        );
+        println!(
+            "11. Stock color thresholds (red at or below {}, yellow below {})",
+            store.settings.critical_stock, store.settings.warning_stock
+        );
+        if store.settings.confirm_above > 0.0 {
+            println!(
+                "12. Confirm sales and purchases above (current: {})",
+                store.money(store.settings.confirm_above)
+            );
+        } else {
+            println!("12. Confirm sales and purchases above (current: off)");
+        }
+        println!(
+            "13. Reuse ids of deleted products (current: {})",
+            yes_no(store.settings.reuse_ids)
+        );
+        println!("14. Change my password");
+        if store.settings.cash_rounding > 0.0 {
+            println!(
+                "15. Cash rounding of sale prices (current: nearest {})",
+                store.money(store.settings.cash_rounding)
+            );
+        } else {
+            println!("15. Cash rounding of sale prices (current: off)");
+        }
+        println!("16. Back");
+        let Some(choice) = prompt("Select option: ") else {
+            break;
+        };
+        match choice.as_str() {
+            "1" => {
+                let Some(width_s) = prompt("New width (0 disables wrapping): ") else {
+                    continue;
+                };
+                match width_s.parse::<usize>() {
+                    Ok(w) => {
+                        store.settings.description_width = w;
+                        println!("Description width set to {}", w);
+                    }
+                    Err(_) => println!("Invalid width"),
+                }
+            }
+            "2" => {
+                let Some(rate_s) = prompt("New tax rate in percent: ") else {
+                    continue;
+                };
+                match rate_s.parse::<f64>() {
+                    Ok(r) if r >= 0.0 => {
+                        store.settings.tax_rate = r;
+                        println!("Tax rate set to {}%", r);
+                    }
+                    _ => println!("Invalid tax rate"),
+                }
+            }
+            "3" => {
+                store.settings.prices_include_tax = !store.settings.prices_include_tax;
+                if store.settings.prices_include_tax {
+                    println!("Stored prices are now treated as tax-inclusive.");
+                } else {
+                    println!("Stored prices are now treated as net of tax.");
+                }
+                println!("Existing prices were not changed.");
+            }
+            "4" => {
+                println!("Placeholders: {{{}}}", RECEIPT_PLACEHOLDERS.join("}, {"));
+                let Some(template) = prompt("New template (\\n for a line break): ") else {
+                    continue;
+                };
+                let Some(receipt_prefix) = prompt(&format!(
+                    "Receipt number prefix (current: {}, '-' for none, empty to keep): ",
+                    store.settings.receipt_prefix
+                )) else {
+                    continue;
+                };
+                if template.is_empty() {
+                    println!("Template unchanged");
+                } else {
+                    for name in unknown_receipt_placeholders(&template) {
+                        println!(
+                            "Warning: unknown placeholder {{{}}} will be printed as-is",
+                            name
+                        );
+                    }
+                    store.settings.receipt_template = template;
+                }
+                match receipt_prefix.as_str() {
+                    "" => {}
+                    "-" => store.settings.receipt_prefix.clear(),
+                    _ => store.settings.receipt_prefix = receipt_prefix,
+                }
+            }
+            "5" => {
+                let Some(n_s) = prompt("Save after how many changes (0 disables): ") else {
+                    continue;
+                };
+                match n_s.parse::<u32>() {
+                    Ok(n) => {
+                        store.settings.autosave_interval = n;
+                        println!("Auto-save interval set to {}", n);
+                    }
+                    Err(_) => println!("Invalid number"),
+                }
+            }
+            "6" => {
+                let Some(currency) = prompt("Currency symbol (or empty to keep): ") else {
+                    continue;
+                };
+                let Some(thousands) = prompt("Thousands separator (e.g. , . or space): ") else {
+                    continue;
+                };
+                let Some(decimal) = prompt("Decimal separator (. or ,): ") else {
+                    continue;
+                };
+                let Some(decimals_s) = prompt(&format!(
+                    "Decimal places 0-{} (or empty to keep {}): ",
+                    MAX_CURRENCY_DECIMALS, store.settings.currency_decimals
+                )) else {
+                    continue;
+                };
+                if !currency.is_empty() {
+                    store.settings.currency = currency;
+                }
+                let single = |s: &str| {
+                    let mut chars = s.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => Some(c),
+                        _ => None,
+                    }
+                };
+                let thousands = if thousands == "space" {
+                    Some(' ')
+                } else {
+                    single(&thousands)
+                };
+                match (thousands, single(&decimal)) {
+                    (Some(t), Some(d)) if t != d && !d.is_ascii_digit() => {
+                        store.settings.thousands_separator = t;
+                        store.settings.decimal_separator = d;
+                        println!(
+                            "Money now looks like {}",
+                            format_money(1234567.89, &store.settings)
+                        );
+                    }
+                    _ => println!("Separators must be two different single characters"),
+                }
+                if !decimals_s.is_empty() {
+                    match decimals_s.parse::<usize>() {
+                        Ok(n) if n <= MAX_CURRENCY_DECIMALS => {
+                            store.settings.currency_decimals = n;
+                            println!(
+                                "Money now looks like {}",
+                                format_money(1234567.891, &store.settings)
+                            );
+                        }
+                        _ => println!("Invalid number of decimal places"),
+                    }
+                }
+            }
+            "7" => {
+                let Some(admin) = prompt("Admin username: ") else {
+                    continue;
+                };
+                let Some(admin_password) = prompt_password("Admin password: ") else {
+                    continue;
+                };
+                if !store.verify_password(&admin, &admin_password) {
+                    println!("Authentication failed");
+                    continue;
+                }
+                let Some(target) = prompt("Manager to reset: ") else {
+                    continue;
+                };
+                let Some(new_password) = prompt_password("New password: ") else {
+                    continue;
+                };
+                match store.reset_password(&admin, &target, &new_password) {
+                    Ok(()) => println!("Password of {} reset", target),
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+            "8" => {
+                store.settings.allow_negative_stock = !store.settings.allow_negative_stock;
+                if store.settings.allow_negative_stock {
+                    println!("Sales may now exceed stock; short products become backordered.");
+                } else {
+                    println!("Sales are now limited to available stock.");
+                }
+            }
+            "9" => {
+                let codes: Vec<&str> = EXCHANGE_RATES.iter().map(|(c, _)| *c).collect();
+                println!("Known currencies: {}", codes.join(", "));
+                let Some(base) = prompt("Base currency (or empty to keep): ") else {
+                    continue;
+                };
+                let Some(display) =
+                    prompt("Also show totals in (code, 'none', or empty to keep): ")
+                else {
+                    continue;
+                };
+                let (base, display) = (base.to_uppercase(), display.to_uppercase());
+                if !base.is_empty() {
+                    if exchange_rate(&base).is_some() {
+                        store.settings.base_currency = base;
+                    } else {
+                        println!("Unknown currency '{}'", base);
+                    }
+                }
+                match display.as_str() {
+                    "" => {}
+                    "NONE" => store.settings.display_currency = None,
+                    code if exchange_rate(code).is_some() => {
+                        store.settings.display_currency = Some(code.to_string())
+                    }
+                    code => println!("Unknown currency '{}'", code),
+                }
+            }
+            "10" => {
+                for (i, order) in ProductOrder::ALL.iter().enumerate() {
+                    println!("{}. By {}", i + 1, order.label());
+                }
+                let Some(order_s) = prompt("Select order: ") else {
+                    continue;
+                };
+                match order_s.parse::<usize>() {
+                    Ok(n) if (1..=ProductOrder::ALL.len()).contains(&n) => {
+                        store.settings.report_order = ProductOrder::ALL[n - 1];
+                        println!(
+                            "Reports now list products by {}",
+                            ProductOrder::ALL[n - 1].label()
+                        );
+                    }
+                    _ => println!("Invalid selection"),
+                }
+            }
+            "11" => {
+                let Some(critical_s) = prompt("Critical quantity (red at or below): ") else {
+                    continue;
+                };
+                let Some(warning_s) = prompt("Warning quantity (yellow below): ") else {
+                    continue;
+                };
+                match (critical_s.parse::<i32>(), warning_s.parse::<i32>()) {
+                    (Ok(c), Ok(w)) if c < w => {
+                        store.settings.critical_stock = c;
+                        store.settings.warning_stock = w;
+                        println!("Stock thresholds set to {} and {}", c, w);
+                    }
+                    (Ok(_), Ok(_)) => {
+                        println!("The warning quantity must be above the critical one")
+                    }
+                    _ => println!("Invalid quantity"),
+                }
+            }
+            "12" => {
+                let Some(amount_s) = prompt("Amount needing confirmation (0 turns it off): ")
+                else {
+                    continue;
+                };
+                match amount_s.parse::<f64>() {
+                    Ok(a) if a >= 0.0 => {
+                        store.settings.confirm_above = a;
+                        println!("Confirmation threshold set to {}", store.money(a));
+                    }
+                    _ => println!("Invalid amount"),
+                }
+            }
+            "13" => {
+                store.settings.reuse_ids = !store.settings.reuse_ids;
+                if store.settings.reuse_ids {
+                    println!("New products fill gaps left by deleted ones where no history refers to them.");
+                } else {
+                    println!("New products always get a fresh id.");
+                }
+            }
+            "14" => {
+                let Some(old) = prompt_password("Current password: ") else {
+                    continue;
+                };
+                let Some(new) = prompt_password("New password: ") else {
+                    continue;
+                };
+                let Some(repeated) = prompt_password("Repeat new password: ") else {
+                    continue;
+                };
+                if repeated != new {
+                    println!("Passwords do not match");
+                    continue;
+                }
+                match store.change_own_password(username, &old, &new) {
+                    Ok(()) => println!("Password changed"),
+                    Err(e) => println!("Error: {}", e),
+                }
+            }
+            "15" => {
+                let Some(step_s) =
+                    prompt("Round sale prices to the nearest (e.g. 0.05, 0 for off): ")
+                else {
+                    continue;
+                };
+                match step_s.parse::<f64>() {
+                    Ok(step) if step >= 0.0 => {
+                        store.settings.cash_rounding = step;
+                        if step > 0.0 {
+                            println!("Sale prices now round to the nearest {}", store.money(step));
+                        } else {
+                            println!("Cash rounding turned off");
+                        }
+                    }
+                    _ => println!("Invalid amount"),
+                }
+            }
+            "16" => break,
+            _ => println!("Invalid selection"),
+        }
+        if store.settings != before {
+            store.mark_dirty("changed settings");
+        }
+        autosave(store);
+    }
+}
+
+// First-run setup steps. Each takes the user's answer; an empty answer
+// keeps the default.
+
+fn setup_admin_password(store: &mut Store, password: &str) -> Result<(), StoreError> {
+    if password.is_empty() {
+        return Ok(());
+    }
+    if let Some(problem) = password_weakness(password) {
+        return Err(StoreError::InvalidInput(problem));
+    }
+    store.set_password(DEFAULT_ADMIN_USER, password)
+}
+
+fn setup_currency(store: &mut Store, symbol: &str) {
+    if !symbol.is_empty() {
+        store.settings.currency = symbol.to_string();
+    }
+}
+
+fn setup_tax_rate(store: &mut Store, rate: &str) -> Result<(), StoreError> {
+    if rate.is_empty() {
+        return Ok(());
+    }
+    match rate.trim_end_matches('%').parse::<f64>() {
+        Ok(r) if r >= 0.0 => {
+            store.settings.tax_rate = r;
+            Ok(())
+        }
+        _ => Err(StoreError::InvalidInput(format!(
+            "'{}' is not a tax rate",
+            rate
+        ))),
+    }
+}
+
+fn setup_starter_csv(store: &mut Store, path: &str) -> Result<usize, StoreError> {
+    if path.is_empty() {
+        return Ok(0);
+    }
+    let content =
+        fs::read_to_string(path).map_err(|e| StoreError::IoError(format!("Read error: {}", e)))?;
+    store.import_products_csv(&content)
+}
+
+/// Walks a new user through the basic settings before the first login.
+fn run_setup_wizard(store: &mut Store) {
+    println!("\nNo data file found; let's set up your store. Press Enter to keep a default.");
+
+    println!("Step 1/4: admin password for user '{}'", DEFAULT_ADMIN_USER);
+    while let Some(password) = prompt_password(&format!(
+        "New password (default '{}'): ",
+        DEFAULT_ADMIN_PASS
+    )) {
+        match setup_admin_password(store, &password) {
+            Ok(()) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    println!("Step 2/4: currency");
+    if let Some(symbol) = prompt("Currency symbol (default $): ") {
+        setup_currency(store, &symbol);
+    }
+
+    println!("Step 3/4: tax rate");
+    while let Some(input) = prompt("Tax rate in percent (default 0): ") {
+        match setup_tax_rate(store, &input) {
+            Ok(()) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    println!("Step 4/4: starter products");
+    println!("CSV columns: name,description,price,quantity");
+    while let Some(input) = prompt("CSV file to import (default none): ") {
+        match setup_starter_csv(store, &input) {
+            Ok(count) => {
+                if count > 0 {
+                    println!("Imported {} products.", count);
+                }
+                break;
+            }
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
+    match store.save_to_file() {
+        Ok(()) if store.dry_run => println!("Setup complete."),
+        Ok(()) => println!("Setup complete. Saved to {}.", DATA_FILE),
+        Err(e) => println!("Warning: could not save setup: {}", e),
+    }
+}
+
+/// Asks for manager credentials and returns the username on success.
+fn login_sequence(store: &mut Store) -> Option<String> {
+    println!("Please login as manager to continue.");
+    let username = prompt("Username: ")?;
+    let password = prompt_password("Password: ")?;
+    if store.authenticate(&username, &password) {
+        println!("Login success. Welcome, {}!", username);
+        Some(username)
+    } else {
+        println!("Login failed.");
+        log(LogLevel::Info, &format!("failed login for {}", username));
+        None
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = CliOptions::from_args(&args, io::stdout().is_terminal());
+    let _ = LOGGER.set(Logger {
+        level: opts.log_level,
+        path: LOG_FILE.to_string(),
+    });
+    log(
+        LogLevel::Debug,
+        &format!("started with arguments {:?}", args),
+    );
+    main_menu();
+    let first_run = !std::path::Path::new(DATA_FILE).exists();
+    let mut store = match Store::load_from_file() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to load data: {}. Starting with empty store.", e);
+            log(
+                LogLevel::Error,
+                &format!("failed to load {}: {}", DATA_FILE, e),
+            );
+            Store::new()
+        }
+    };
+    store.dry_run = opts.dry_run;
+    store.read_only = opts.read_only;
+    if opts.dry_run {
+        println!("[dry-run] changes will not be saved to {}", DATA_FILE);
+    }
+    if opts.read_only {
+        println!("Read-only mode: browsing and reports only");
+    }
+    if first_run && !opts.read_only {
+        run_setup_wizard(&mut store);
+    }
+    let Some(username) = login_sequence(&mut store) else {
+        println!("Exiting due to authentication failure.");
+        return;
+    };
+    ui_loop(store, &username, &opts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_edit_delete_product() {
+        let mut store = Store::new();
+        let p = store.add_product("P".into(), "D".into(), 9.99, 10).unwrap();
+        assert_eq!(p.id, 1);
+        let edited = store
+            .edit_product(p.id, Some("P2".into()), None, Some(10.0), Some(5))
+            .unwrap();
+        assert_eq!(edited.name, "P2");
+        assert_eq!(edited.price, 10.0);
+        assert_eq!(edited.quantity, 5);
+        assert!(store.delete_product(p.id).is_ok());
+        assert!(store.delete_product(999).is_err());
+    }
+
+    #[test]
+    fn purchase_and_sales() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("A".into(), "desc".into(), 5.0, 2)
+            .unwrap();
+        let pur = store.record_purchase(p.id, 10, 4.0, None).unwrap();
+        assert_eq!(pur.quantity, 10);
+        assert!((store.total_purchases_cost() - 40.0).abs() < 1e-6);
+        let sale = store.record_sale(p.id, 5, 7.0).unwrap();
+        assert_eq!(sale.quantity, 5);
+        let prod = store.find_product(p.id).unwrap();
+        assert_eq!(prod.quantity, 7); 
+    }
+
+    #[test]
+    fn auth_and_manager() {
+        let mut store = Store::new();
+        store.add_manager("test", "1234");
+        assert!(store.authenticate("test", "1234"));
+        assert!(!store.authenticate("test", "wrong"));
+    }
+
+    #[test]
+    fn admin_resets_manager_password() {
+        let mut store = Store::new();
+        store.add_manager("clerk", "old");
+        assert!(matches!(
+            store.reset_password(DEFAULT_ADMIN_USER, "clerk", "new"),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(store.admin_log.is_empty());
+        store
+            .reset_password(DEFAULT_ADMIN_USER, "clerk", "Fresh1234")
+            .unwrap();
+        assert!(store.authenticate("clerk", "Fresh1234"));
+        assert!(!store.authenticate("clerk", "old"));
+        assert_eq!(store.admin_log.len(), 1);
+        assert_eq!(store.admin_log[0].admin, DEFAULT_ADMIN_USER);
+
+        assert!(matches!(
+            store.reset_password("clerk", DEFAULT_ADMIN_USER, "Mine12345"),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(store.authenticate(DEFAULT_ADMIN_USER, DEFAULT_ADMIN_PASS));
+        assert!(matches!(
+            store.reset_password(DEFAULT_ADMIN_USER, "ghost", "Ghost1234"),
+            Err(StoreError::NotFound(_))
+        ));
+        assert_eq!(store.admin_log.len(), 1);
+    }
+
+    #[test]
+    fn unsalted_hashes_still_authenticate() {
+        let mut store = Store::new();
+        store.managers.push(Manager {
+            username: "legacy".into(),
+            password_hash: hash_password("", "pw"),
+            salt: String::new(),
+            is_admin: false,
+            last_login: None,
+        });
+        assert!(store.authenticate("legacy", "pw"));
+        assert!(!store.authenticate("legacy", "wrong"));
+
+        // A reset gives the legacy account a salt, and it can still log in.
+        store
+            .reset_password(DEFAULT_ADMIN_USER, "legacy", "Fresh1234")
+            .unwrap();
+        let legacy = store
+            .managers
+            .iter()
+            .find(|m| m.username == "legacy")
+            .unwrap();
+        assert_ne!(legacy.salt, "");
+        assert!(store.authenticate("legacy", "Fresh1234"));
+        assert!(!store.authenticate("legacy", "pw"));
+    }
+
+    #[test]
+    fn expiring_before_filters_and_sorts() {
+        let mut store = Store::new();
+        let now = Local::now();
+        let milk = store.add_product("Milk".into(), "".into(), 1.0, 3).unwrap();
+        let cheese = store
+            .add_product("Cheese".into(), "".into(), 4.0, 2)
+            .unwrap();
+        let jam = store.add_product("Jam".into(), "".into(), 3.0, 1).unwrap();
+        store.add_product("Salt".into(), "".into(), 0.5, 9).unwrap();
+        let days = |n| Some(now + Duration::days(n));
+        store.set_expiry(milk.id, days(5)).unwrap();
+        store.set_expiry(cheese.id, days(2)).unwrap();
+        store.set_expiry(jam.id, days(60)).unwrap();
+
+        let soon = store.expiring_before(now + Duration::days(7));
+        let names: Vec<&str> = soon.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Cheese", "Milk"]);
+        assert!(store.set_expiry(999, None).is_err());
+    }
+
+    #[test]
+    fn daily_cashflow_nets_each_day() {
+        let mut store = Store::new();
+        let p = store.add_product("A".into(), "".into(), 5.0, 0).unwrap();
+        let day1 = Local.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap();
+        let day2 = Local.with_ymd_and_hms(2024, 3, 2, 10, 0, 0).unwrap();
+        store.record_purchase(p.id, 10, 4.0, None).unwrap();
+        store.record_sale(p.id, 3, 7.0).unwrap();
+        store.record_purchase(p.id, 5, 4.0, None).unwrap();
+        store.purchases[0].time = day1;
+        store.sales[0].time = day1;
+        store.purchases[1].time = day2;
+
+        let flow = store.daily_cashflow();
+        assert_eq!(flow.len(), 2);
+        assert!((flow[&day1.date_naive()] - (21.0 - 40.0)).abs() < 1e-9);
+        assert!((flow[&day2.date_naive()] + 20.0).abs() < 1e-9);
+        let cumulative: f64 = flow.values().sum();
+        assert!((cumulative + 39.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wrap_text_breaks_on_words() {
+        assert_eq!(wrap_text("aaa bbb ccc", 7), vec!["aaa bbb", "ccc"]);
+        assert_eq!(wrap_text("short", 20), vec!["short"]);
+        assert_eq!(wrap_text("", 10), vec![""]);
+    }
+
+    #[test]
+    fn wrap_text_breaks_long_words() {
+        let lines = wrap_text("abcdefghij xy", 4);
+        assert_eq!(lines, vec!["abcd", "efgh", "ij", "xy"]);
+    }
+
+    #[test]
+    fn vat_split_for_inclusive_prices() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Gross".into(), "".into(), 120.0, 1)
+            .unwrap();
+        assert!((p.net_price(20.0, true) - 100.0).abs() < 1e-9);
+        assert!((p.vat_component(20.0, true) - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn vat_added_on_top_of_net_prices() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Net".into(), "".into(), 120.0, 1)
+            .unwrap();
+        assert!((p.net_price(20.0, false) - 120.0).abs() < 1e-9);
+        assert!((p.vat_component(20.0, false) - 24.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn repackage_moves_stock() {
+        let mut store = Store::new();
+        let case = store
+            .add_product("Case".into(), "12 cans".into(), 10.0, 3)
+            .unwrap();
+        let can = store.add_product("Can".into(), "".into(), 1.0, 0).unwrap();
+        store.repackage(case.id, can.id, 2, 24).unwrap();
+        assert_eq!(store.find_product(case.id).unwrap().quantity, 1);
+        assert_eq!(store.find_product(can.id).unwrap().quantity, 24);
+        assert_eq!(store.repackagings.len(), 1);
+    }
+
+    #[test]
+    fn repackage_rejects_insufficient_source() {
+        let mut store = Store::new();
+        let case = store
+            .add_product("Case".into(), "".into(), 10.0, 1)
+            .unwrap();
+        let can = store.add_product("Can".into(), "".into(), 1.0, 0).unwrap();
+        let res = store.repackage(case.id, can.id, 2, 24);
+        assert!(matches!(res, Err(StoreError::InsufficientStock(_))));
+        assert_eq!(store.find_product(case.id).unwrap().quantity, 1);
+        assert_eq!(store.find_product(can.id).unwrap().quantity, 0);
+        assert!(store.repackagings.is_empty());
+    }
+
+    #[test]
+    fn stock_by_lot_consumes_oldest_first() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Flour".into(), "".into(), 2.0, 0)
+            .unwrap();
+        store
+            .record_purchase(p.id, 10, 1.0, Some("A-1".into()))
+            .unwrap();
+        store
+            .record_purchase(p.id, 8, 1.1, Some("B-7".into()))
+            .unwrap();
+        store.record_sale(p.id, 4, 2.0).unwrap();
+        store.record_sale(p.id, 2, 2.0).unwrap();
+        assert_eq!(
+            store.stock_by_lot(p.id),
+            vec![("A-1".to_string(), 4), ("B-7".to_string(), 8)]
+        );
+        store.record_sale(p.id, 6, 2.0).unwrap();
+        assert_eq!(
+            store.stock_by_lot(p.id),
+            vec![("A-1".to_string(), 0), ("B-7".to_string(), 6)]
+        );
+    }
+
+    #[test]
+    fn receipt_template_fills_all_placeholders() {
+        let mut store = Store::new();
+        let p = store.add_product("Tea".into(), "".into(), 3.0, 5).unwrap();
+        let sale = store.record_sale(p.id, 2, 3.5).unwrap();
+        store.settings.receipt_template =
+            "#{id} {product} x{qty} @ {price} = {total}\\n{date}".to_string();
+        let receipt = store.render_receipt(&sale);
+        let date = sale.time.format("%Y-%m-%d %H:%M").to_string();
+        assert_eq!(receipt, format!("#1 Tea x2 @ 3.50 = 7.00\n{}", date));
+
+        store.settings.receipt_template = DEFAULT_RECEIPT_TEMPLATE.to_string();
+        store.settings.currency = "€".to_string();
+        let receipt = store.render_receipt(&sale);
+        assert!(receipt.ends_with("Tea x2 @ €3.50\nTOTAL €7.00"));
+    }
+
+    #[test]
+    fn receipt_template_keeps_unknown_placeholders() {
+        let mut store = Store::new();
+        let p = store.add_product("Tea".into(), "".into(), 3.0, 5).unwrap();
+        let sale = store.record_sale(p.id, 1, 3.0).unwrap();
+        store.settings.receipt_template = "{product} for {customer}".to_string();
+        assert_eq!(store.render_receipt(&sale), "Tea for {customer}");
+        assert_eq!(
+            unknown_receipt_placeholders(&store.settings.receipt_template),
+            vec!["customer".to_string()]
+        );
+    }
+
+    #[test]
+    fn color_helpers_follow_flag() {
+        assert_eq!(paint("5", Color::Red, true), "\x1b[31m5\x1b[0m");
+        assert_eq!(paint("5", Color::Red, false), "5");
+        assert!(bold("Header", true).starts_with("\x1b[1m"));
+        assert_eq!(bold("Header", false), "Header");
+        assert_eq!(paint("5", Color::Yellow, true), "\x1b[33m5\x1b[0m");
+        let store = Store::new();
+        assert_eq!(store.stock_color(LOW_STOCK_THRESHOLD), Color::Red);
+        assert_eq!(store.stock_color(LOW_STOCK_THRESHOLD + 1), Color::Yellow);
+        assert_eq!(store.stock_color(WARNING_STOCK_THRESHOLD), Color::Green);
+    }
+
+    #[test]
+    fn quantity_color_bands_and_boundaries() {
+        assert_eq!(quantity_color(-3, 2, 8), Color::Red);
+        assert_eq!(quantity_color(2, 2, 8), Color::Red);
+        assert_eq!(quantity_color(3, 2, 8), Color::Yellow);
+        assert_eq!(quantity_color(7, 2, 8), Color::Yellow);
+        assert_eq!(quantity_color(8, 2, 8), Color::Green);
+        assert_eq!(quantity_color(100, 2, 8), Color::Green);
+        // With no gap between the thresholds there is no yellow band.
+        assert_eq!(quantity_color(3, 2, 3), Color::Green);
+    }
+
+    #[test]
+    fn no_color_flag_and_tty_detection() {
+        assert!(CliOptions::from_args(&[], true).color);
+        assert!(!CliOptions::from_args(&["--no-color".to_string()], true).color);
+        assert!(!CliOptions::from_args(&[], false).color);
+        assert_eq!(CliOptions::from_args(&[], true).log_level, LogLevel::Info);
+        let debug = ["--log-level".to_string(), "debug".to_string()];
+        assert_eq!(
+            CliOptions::from_args(&debug, true).log_level,
+            LogLevel::Debug
+        );
+        assert!(!CliOptions::from_args(&[], true).dry_run);
+        assert!(CliOptions::from_args(&["--dry-run".to_string()], true).dry_run);
+    }
+
+    #[test]
+    fn verify_stock_flags_tampered_quantity() {
+        let mut store = Store::new();
+        let case = store
+            .add_product("Case".into(), "".into(), 24.0, 2)
+            .unwrap();
+        let can = store.add_product("Can".into(), "".into(), 1.0, 0).unwrap();
+        store.record_purchase(case.id, 3, 20.0, None).unwrap();
+        store.record_sale(case.id, 1, 30.0).unwrap();
+        store.repackage(case.id, can.id, 1, 24).unwrap();
+        store
+            .edit_product(can.id, None, None, None, Some(20))
+            .unwrap();
+        assert_eq!(
+            store.verify_stock(),
+            vec![(case.id, 3, 3), (can.id, 20, 20)]
+        );
+
+        store.products[0].quantity = 9;
+        assert_eq!(store.verify_stock()[0], (case.id, 9, 3));
+    }
+
+    #[test]
+    fn export_sqlite_writes_all_tables() {
+        let mut store = Store::new();
+        let a = store
+            .add_product("A".into(), "first".into(), 5.0, 10)
+            .unwrap();
+        let b = store
+            .add_product("B".into(), "second".into(), 2.0, 0)
+            .unwrap();
+        store
+            .record_purchase(a.id, 4, 3.0, Some("L1".into()))
+            .unwrap();
+        store.record_purchase(b.id, 6, 1.0, None).unwrap();
+        store.record_sale(a.id, 2, 5.0).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.db");
+        let path = path.to_str().unwrap();
+        store.export_sqlite(path).unwrap();
+        // Re-exporting replaces the file rather than duplicating rows.
+        store.export_sqlite(path).unwrap();
+
+        let conn = Connection::open(path).unwrap();
+        let count = |table: &str| -> i64 {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("products"), 2);
+        assert_eq!(count("sales"), 1);
+        assert_eq!(count("purchases"), 2);
+        let lot: String = conn
+            .query_row(
+                "SELECT lot_number FROM purchases WHERE product_id = ?1",
+                [a.id],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(lot, "L1");
+    }
+
+    #[test]
+    fn autosave_runs_once_per_interval() {
+        let mut store = Store::new();
+        store.settings.autosave_interval = 3;
+        let mut saves = 0;
+        let p = store.add_product("A".into(), "".into(), 1.0, 10).unwrap();
+        store.record_sale(p.id, 1, 1.0).unwrap();
+        assert!(store.autosave_if_due(|_| Ok(())).is_none());
+        assert!(store.record_sale(p.id, 0, 1.0).is_err());
+        store.record_purchase(p.id, 5, 0.5, None).unwrap();
+        for _ in 0..2 {
+            if let Some(result) = store.autosave_if_due(|_| {
+                saves += 1;
+                Err(StoreError::IoError("disk full".into()))
+            }) {
+                assert!(result.is_err());
+            }
+        }
+        assert_eq!(saves, 1);
+        assert_eq!(store.dirty_ops, 0);
+    }
+
+    #[test]
+    fn save_detects_external_modification() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let path = path.to_str().unwrap();
+        Store::new().save_to_path(path).unwrap();
+
+        let mut first = Store::load_from_path(path).unwrap();
+        let mut second = Store::load_from_path(path).unwrap();
+        first.add_product("A".into(), "".into(), 1.0, 1).unwrap();
+        first.save_to_path(path).unwrap();
+        first.save_to_path(path).unwrap();
+
+        second.add_product("B".into(), "".into(), 2.0, 2).unwrap();
+        let err = second.save_to_path(path).unwrap_err();
+        assert!(matches!(err, StoreError::Conflict(_)));
+        let on_disk = Store::load_from_path(path).unwrap();
+        assert_eq!(on_disk.products[0].name, "A");
+
+        let mut reloaded = Store::load_from_path(path).unwrap();
+        reloaded.add_product("B".into(), "".into(), 2.0, 2).unwrap();
+        assert!(reloaded.save_to_path(path).is_ok());
+    }
+
+    #[test]
+    fn variants_group_under_parent() {
+        let mut store = Store::new();
+        let shirt = store
+            .add_product("Shirt".into(), "Cotton".into(), 20.0, 0)
+            .unwrap();
+        let mug = store.add_product("Mug".into(), "".into(), 5.0, 3).unwrap();
+        let small = store
+            .add_variant(shirt.id, vec![("size".into(), "S".into())], 20.0, 4)
+            .unwrap();
+        let large = store
+            .add_variant(shirt.id, vec![("size".into(), "L".into())], 22.0, 6)
+            .unwrap();
+        assert_eq!(small.name, "Shirt (S)");
+        assert!(store
+            .add_variant(small.id, vec![("color".into(), "red".into())], 1.0, 1)
+            .is_err());
+
+        store.record_sale(large.id, 2, 22.0).unwrap();
+        assert_eq!(store.find_product(small.id).unwrap().quantity, 4);
+        assert_eq!(store.find_product(large.id).unwrap().quantity, 4);
+
+        let grouped = store.grouped_inventory();
+        assert_eq!(grouped.len(), 2);
+        let (parent, variants, total) = &grouped[0];
+        assert_eq!(parent.id, shirt.id);
+        let ids: Vec<u32> = variants.iter().map(|v| v.id).collect();
+        assert_eq!(ids, vec![small.id, large.id]);
+        assert_eq!(*total, 8);
+        assert_eq!(grouped[1].0.id, mug.id);
+        assert_eq!(grouped[1].2, 3);
+        assert!(store.delete_product(shirt.id).is_err());
+    }
+
+    #[test]
+    fn money_groups_thousands() {
+        let settings = Settings::default();
+        assert_eq!(format_money(0.0, &settings), "$0.00");
+        assert_eq!(format_money(999.999, &settings), "$1,000.00");
+        assert_eq!(format_money(12345.6, &settings), "$12,345.60");
+        assert_eq!(format_money(1234567.89, &settings), "$1,234,567.89");
+        assert_eq!(format_money(-1234.5, &settings), "-$1,234.50");
+        assert_eq!(format_money(-0.001, &settings), "$0.00");
+    }
+
+    #[test]
+    fn money_uses_comma_decimal_locale() {
+        let settings = Settings {
+            thousands_separator: '.',
+            decimal_separator: ',',
+            ..Settings::default()
+        };
+        assert_eq!(format_money(1234567.89, &settings), "$1.234.567,89");
+        assert_eq!(format_amount(-42.5, &settings), "-42,50");
+    }
+
+    #[test]
+    fn money_honors_currency_decimals() {
+        let with_decimals = |currency_decimals| Settings {
+            currency_decimals,
+            ..Settings::default()
+        };
+        let amount = 1234.5678;
+        assert_eq!(format_money(amount, &with_decimals(0)), "$1,235");
+        assert_eq!(format_money(amount, &with_decimals(2)), "$1,234.57");
+        assert_eq!(format_money(amount, &with_decimals(3)), "$1,234.568");
+        assert_eq!(format_money(-0.4, &with_decimals(0)), "$0");
+        assert_eq!(format_amount(0.05, &with_decimals(3)), "0.050");
+        assert_eq!(minor_units(12.345, 3), 12345);
+    }
+
+    #[test]
+    fn store_errors_display_friendly_messages() {
+        let cases = [
+            (
+                StoreError::NotFound("Product 7 not found".into()),
+                "Product 7 not found",
+            ),
+            (
+                StoreError::InsufficientStock("Tea has only 2 in stock".into()),
+                "Not enough stock: Tea has only 2 in stock",
+            ),
+            (
+                StoreError::InvalidInput("Quantity must be positive".into()),
+                "Invalid input: Quantity must be positive",
+            ),
+            (
+                StoreError::IoError("Write error: disk full".into()),
+                "Could not read or write data: Write error: disk full",
+            ),
+            (
+                StoreError::Conflict("store_data.json was saved elsewhere".into()),
+                "Save conflict: store_data.json was saved elsewhere",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn setup_steps_apply_currency_and_tax_rate() {
+        let mut store = Store::new();
+        setup_currency(&mut store, "€");
+        setup_tax_rate(&mut store, "20%").unwrap();
+        assert!(setup_admin_password(&mut store, "s3cret").is_err());
+        setup_admin_password(&mut store, "s3cret99").unwrap();
+        assert_eq!(store.settings.currency, "€");
+        assert_eq!(store.settings.tax_rate, 20.0);
+        assert_eq!(store.money(-1234.5), "-€1,234.50");
+        assert!(store.authenticate(DEFAULT_ADMIN_USER, "s3cret99"));
+        assert!(setup_tax_rate(&mut store, "-5").is_err());
+        assert_eq!(store.settings.tax_rate, 20.0);
+
+        let mut defaults = Store::new();
+        setup_currency(&mut defaults, "");
+        setup_tax_rate(&mut defaults, "").unwrap();
+        setup_admin_password(&mut defaults, "").unwrap();
+        assert_eq!(defaults.settings.currency, "$");
+        assert_eq!(defaults.settings.tax_rate, 0.0);
+        assert!(defaults.authenticate(DEFAULT_ADMIN_USER, DEFAULT_ADMIN_PASS));
+    }
+
+    #[test]
+    fn starter_csv_import() {
+        let mut store = Store::new();
+        let csv = "name,description,price,quantity\nTea,Green, loose leaf,4.50,12\n\nMug,,6,3\n";
+        assert_eq!(store.import_products_csv(csv).unwrap(), 2);
+        assert_eq!(store.products[0].description, "Green, loose leaf");
+        assert_eq!(store.products[1].quantity, 3);
+        assert!(store.import_products_csv("Bad,row,abc,1").is_err());
+        assert_eq!(store.products.len(), 2);
+    }
+
+    #[test]
+    fn low_margin_products_below_threshold() {
+        let mut store = Store::new();
+        let thin = store
+            .add_product("Thin".into(), "".into(), 10.0, 0)
+            .unwrap();
+        let fat = store.add_product("Fat".into(), "".into(), 10.0, 0).unwrap();
+        let unknown = store
+            .add_product("Unknown".into(), "".into(), 10.0, 5)
+            .unwrap();
+        store.record_purchase(thin.id, 2, 8.0, None).unwrap();
+        store.record_purchase(thin.id, 2, 10.0, None).unwrap();
+        store.record_purchase(fat.id, 1, 4.0, None).unwrap();
+
+        assert_eq!(store.average_cost(thin.id), Some(9.0));
+        assert_eq!(store.average_cost(unknown.id), None);
+        let low = store.low_margin_products(25.0);
+        assert_eq!(low.len(), 1);
+        assert_eq!(low[0].0, thin.id);
+        assert!((low[0].1 - 10.0).abs() < 1e-9);
+        assert_eq!(store.low_margin_products(70.0).len(), 2);
+
+        // With VAT-inclusive prices the margin is taken on the net price.
+        store.settings.tax_rate = 25.0;
+        store.settings.prices_include_tax = true;
+        let fat_margin = store.margin_percent(store.find_product(fat.id).unwrap());
+        assert!((fat_margin.unwrap() - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn key_action_maps_numbers_and_letters() {
+        assert_eq!(key_action('i'), Some(MainAction::Inventory));
+        assert_eq!(key_action('S'), Some(MainAction::Sales));
+        assert_eq!(key_action('3'), Some(MainAction::Purchases));
+        assert_eq!(key_action('r'), Some(MainAction::Reports));
+        assert_eq!(key_action('o'), Some(MainAction::Settings));
+        assert_eq!(key_action('q'), Some(MainAction::SaveAndExit));
+        assert_eq!(key_action('6'), Some(MainAction::SaveAndExit));
+        assert_eq!(key_action('x'), None);
+        assert_eq!(key_action('7'), None);
+        assert_eq!(key_action(' '), None);
+    }
+
+    #[test]
+    fn summary_json_has_totals() {
+        let mut store = Store::new();
+        let p = store.add_product("A".into(), "".into(), 5.0, 0).unwrap();
+        store.record_purchase(p.id, 10, 2.0, None).unwrap();
+        store.record_sale(p.id, 4, 6.5).unwrap();
+        let summary = store.report_json(ReportSection::Summary);
+        assert_eq!(
+            summary,
+            json!({ "total_sales": 26.0, "total_cost": 20.0, "profit": 6.0 })
+        );
+    }
+
+    #[test]
+    fn inventory_and_top_sellers_json() {
+        let mut store = Store::new();
+        let a = store.add_product("A".into(), "".into(), 5.0, 10).unwrap();
+        let b = store.add_product("B".into(), "".into(), 1.0, 10).unwrap();
+        store.record_sale(a.id, 1, 5.0).unwrap();
+        store.record_sale(b.id, 3, 1.0).unwrap();
+        store.record_sale(b.id, 2, 1.0).unwrap();
+
+        let inventory = store.report_json(ReportSection::Inventory);
+        assert_eq!(
+            inventory[0],
+            json!({ "id": a.id, "name": "A", "price": 5.0, "quantity": 9 })
+        );
+        let top = store.report_json(ReportSection::TopSellers);
+        assert_eq!(
+            top,
+            json!([
+                { "product_id": b.id, "name": "B", "units_sold": 5, "revenue": 5.0 },
+                { "product_id": a.id, "name": "A", "units_sold": 1, "revenue": 5.0 },
+            ])
+        );
+        assert_eq!(
+            ReportSection::parse("Top-Sellers"),
+            Some(ReportSection::TopSellers)
+        );
+        assert_eq!(ReportSection::parse("profit"), None);
+    }
+
+    #[test]
+    fn suggest_tags_prefers_prefix_then_distance() {
+        let mut store = Store::new();
+        let a = store.add_product("A".into(), "".into(), 1.0, 1).unwrap();
+        let b = store.add_product("B".into(), "".into(), 1.0, 1).unwrap();
+        store.add_tag(a.id, "clearance").unwrap();
+        store.add_tag(a.id, "Clear").unwrap();
+        store.add_tag(b.id, "clean").unwrap();
+        store.add_tag(b.id, "seasonal").unwrap();
+
+        assert_eq!(
+            store.suggest_tags("clear", 5),
+            vec!["clear", "clearance", "clean"]
+        );
+        assert_eq!(store.suggest_tags("clearnce", 1), vec!["clearance"]);
+        assert!(store.suggest_tags("xyzzy", 5).is_empty());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert!(store.add_tag(a.id, "  ").is_err());
+    }
+
+    #[test]
+    fn reservations_hold_stock_until_fulfilled_or_expired() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Sofa".into(), "".into(), 300.0, 3)
+            .unwrap();
+        let expires = Local::now() + Duration::days(7);
+        let r = store.reserve(p.id, 2, expires).unwrap();
+        assert_eq!(store.available_quantity(p.id), 1);
+        assert!(store.record_sale(p.id, 2, 300.0).is_err());
+        assert!(store.reserve(p.id, 2, expires).is_err());
+
+        let sale = store.fulfill(r.id, 280.0).unwrap();
+        assert_eq!((sale.product_id, sale.quantity), (p.id, 2));
+        assert_eq!(store.sales.len(), 1);
+        assert!(store.reservations.is_empty());
+        assert_eq!(store.available_quantity(p.id), 1);
+
+        let held = store.reserve(p.id, 1, expires).unwrap();
+        assert_eq!(store.available_quantity(p.id), 0);
+        assert_eq!(store.release_expired(expires + Duration::hours(1)), 1);
+        assert_eq!(store.available_quantity(p.id), 1);
+        assert!(store.release(held.id).is_err());
+
+        // A hold past its expiry no longer counts, even before it is released.
+        store.reservations.push(Reservation {
+            id: 99,
+            product_id: p.id,
+            quantity: 1,
+            expires: Local::now() - Duration::hours(1),
+        });
+        assert_eq!(store.reserved_quantity(p.id), 0);
+        assert_eq!(store.available_quantity(p.id), 1);
+        assert!(store.record_sale(p.id, 1, 300.0).is_ok());
+    }
+
+    #[test]
+    fn products_without_purchases_lists_phantom_stock() {
+        let mut store = Store::new();
+        let bought = store
+            .add_product("Bought".into(), "".into(), 1.0, 0)
+            .unwrap();
+        let phantom = store
+            .add_product("Phantom".into(), "".into(), 1.0, 4)
+            .unwrap();
+        store
+            .add_product("Empty".into(), "".into(), 1.0, 0)
+            .unwrap();
+        store.record_purchase(bought.id, 5, 0.5, None).unwrap();
+        let ids: Vec<u32> = store
+            .products_without_purchases()
+            .iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(ids, vec![phantom.id]);
+    }
+
+    #[test]
+    fn slow_movers_lead_with_unsold_products() {
+        let mut store = Store::new();
+        let busy = store
+            .add_product("Busy".into(), "".into(), 1.0, 20)
+            .unwrap();
+        let slow = store
+            .add_product("Slow".into(), "".into(), 1.0, 20)
+            .unwrap();
+        let idle = store
+            .add_product("Idle".into(), "".into(), 1.0, 20)
+            .unwrap();
+        let also_idle = store
+            .add_product("Also idle".into(), "".into(), 1.0, 20)
+            .unwrap();
+        store.record_sale(busy.id, 5, 1.0).unwrap();
+        store.record_sale(busy.id, 4, 1.0).unwrap();
+        store.record_sale(slow.id, 2, 1.0).unwrap();
+        assert_eq!(
+            store.slow_movers(10),
+            vec![(idle.id, 0), (also_idle.id, 0), (slow.id, 2), (busy.id, 9)]
+        );
+        assert_eq!(store.slow_movers(1), vec![(idle.id, 0)]);
+    }
+
+    #[test]
+    fn inventory_aging_by_last_sale() {
+        let mut store = Store::new();
+        let recent = store
+            .add_product("Recent".into(), "".into(), 1.0, 10)
+            .unwrap();
+        let old = store.add_product("Old".into(), "".into(), 1.0, 10).unwrap();
+        let never = store
+            .add_product("Never".into(), "".into(), 1.0, 10)
+            .unwrap();
+        let now = Local::now();
+        store.record_sale(old.id, 1, 1.0).unwrap();
+        store.record_sale(old.id, 1, 1.0).unwrap();
+        store.record_sale(recent.id, 1, 1.0).unwrap();
+        store.sales[0].time = now - Duration::days(40);
+        store.sales[1].time = now - Duration::days(30);
+        store.sales[2].time = now - Duration::days(2);
+
+        assert_eq!(store.last_sold(old.id), Some(now - Duration::days(30)));
+        assert_eq!(store.last_sold(never.id), None);
+        assert_eq!(
+            store.inventory_aging(now),
+            vec![(never.id, None), (old.id, Some(30)), (recent.id, Some(2))]
+        );
+    }
+
+    #[test]
+    fn invoices_group_sales_once() {
+        let mut store = Store::new();
+        let a = store.add_product("A".into(), "".into(), 2.0, 10).unwrap();
+        let b = store.add_product("B".into(), "".into(), 5.0, 10).unwrap();
+        let s1 = store.record_sale(a.id, 3, 2.0).unwrap();
+        let s2 = store.record_sale(b.id, 1, 5.0).unwrap();
+        let s3 = store.record_sale(b.id, 2, 5.0).unwrap();
+
+        let invoice = store
+            .create_invoice(&[s1.id, s2.id], Some("ACME".into()))
+            .unwrap();
+        assert_eq!(invoice.sale_ids, vec![s1.id, s2.id]);
+        assert_eq!(store.invoices[0].customer.as_deref(), Some("ACME"));
+        assert!((store.invoice_total(&invoice) - 11.0).abs() < 1e-9);
+        assert!(store.render_invoice(&invoice).ends_with("TOTAL $11.00"));
+
+        assert!(matches!(
+            store.create_invoice(&[s3.id, s2.id], None),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(store.create_invoice(&[s3.id, s3.id], None).is_err());
+        assert!(matches!(
+            store.create_invoice(&[99], None),
+            Err(StoreError::NotFound(_))
+        ));
+        assert_eq!(store.invoices.len(), 1);
+        assert!(store.create_invoice(&[s3.id], None).is_ok());
+    }
+
+    #[test]
+    fn paginated_report_repeats_headers() {
+        let mut store = Store::new();
+        for i in 0..12 {
+            store
+                .add_product(format!("P{}", i), "".into(), 1.0, 1)
+                .unwrap();
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.txt");
+        let path = path.to_str().unwrap();
+        let total_lines = store.full_report_lines(false).len();
+        store.write_paginated_report(path, 10).unwrap();
+        let text = fs::read_to_string(path).unwrap();
+        let pages = total_lines.div_ceil(10);
+        assert!(pages > 1);
+        assert_eq!(text.matches(PAGINATED_REPORT_TITLE).count(), pages);
+        assert_eq!(text.matches('\u{0C}').count(), pages - 1);
+        assert!(text.contains("Page 1 of 3"));
+        assert!(text.trim_end().ends_with("Page 3 of 3"));
+        assert!(store.write_paginated_report(path, 0).is_err());
+    }
+
+    #[test]
+    fn product_profit_counts_only_sales_in_window() {
+        let mut store = Store::new();
+        let a = store.add_product("A".into(), "".into(), 10.0, 0).unwrap();
+        let b = store.add_product("B".into(), "".into(), 10.0, 0).unwrap();
+        let idle = store
+            .add_product("Idle".into(), "".into(), 10.0, 5)
+            .unwrap();
+        store.record_purchase(a.id, 10, 4.0, None).unwrap();
+        store.record_purchase(b.id, 10, 6.0, None).unwrap();
+        store.record_sale(a.id, 2, 10.0).unwrap();
+        store.record_sale(a.id, 3, 10.0).unwrap();
+        store.record_sale(b.id, 1, 10.0).unwrap();
+        let start = parse_date("2024-03-01").unwrap();
+        let end = parse_date("2024-04-01").unwrap();
+        store.sales[0].time = start + Duration::days(3);
+        store.sales[1].time = end;
+        store.sales[2].time = start;
+
+        assert!((store.product_profit(a.id, start, end) - 12.0).abs() < 1e-9);
+        assert!((store.product_profit(b.id, start, end) - 4.0).abs() < 1e-9);
+        assert_eq!(store.product_profit(idle.id, start, end), 0.0);
+        assert_eq!(
+            store.period_profits(start, end),
+            vec![(a.id, 12.0), (b.id, 4.0), (idle.id, 0.0)]
+        );
+    }
+
+    #[test]
+    fn unit_label_shows_next_to_quantity() {
+        let mut store = Store::new();
+        let rice = store
+            .add_product("Rice".into(), "Basmati".into(), 3.0, 5)
+            .unwrap();
+        let pens = store
+            .add_product("Pens".into(), "Blue, fine".into(), 1.0, 7)
+            .unwrap();
+        store.set_unit_label(rice.id, Some(" kg ".into())).unwrap();
+        let rice = store.find_product(rice.id).unwrap();
+        assert_eq!(
+            store.listing_line(rice),
+            "[1] Rice - Basmati | $3.00 | qty: 5 kg"
+        );
+        let pens = store.find_product(pens.id).unwrap();
+        assert_eq!(pens.quantity_with_unit(), "7");
+
+        assert_eq!(
+            store.export_products_csv(),
+            "id,name,description,price,quantity,unit\n\
+             1,Rice,Basmati,3,5,kg\n\
+             2,Pens,\"Blue, fine\",1,7,\n"
+        );
+        store.set_unit_label(rice.id, Some("  ".into())).unwrap();
+        assert_eq!(store.find_product(1).unwrap().unit_label, None);
+    }
+
+    #[test]
+    fn negative_stock_only_when_allowed() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Chair".into(), "".into(), 50.0, 2)
+            .unwrap();
+        assert!(matches!(
+            store.record_sale(p.id, 3, 50.0),
+            Err(StoreError::InsufficientStock(_))
+        ));
+        assert!(store.backordered().is_empty());
+
+        store.settings.allow_negative_stock = true;
+        store.record_sale(p.id, 3, 50.0).unwrap();
+        let backordered = store.backordered();
+        assert_eq!(backordered.len(), 1);
+        assert_eq!((backordered[0].id, backordered[0].quantity), (p.id, -1));
+    }
+
+    #[test]
+    fn csv_export_round_trips() {
+        let mut store = Store::new();
+        store
+            .add_product("Tea".into(), "Green".into(), 4.5, 12)
+            .unwrap();
+        assert!(store.verify_csv_roundtrip().is_ok());
+
+        let odd = store
+            .add_product("Nuts, salted".into(), "The \"best\"\nsnack".into(), 0.1, 3)
+            .unwrap();
+        store
+            .set_unit_label(odd.id, Some("bag, small".into()))
+            .unwrap();
+        assert!(store.verify_csv_roundtrip().is_ok());
+        let rows = parse_csv(&store.export_products_csv()).unwrap();
+        assert_eq!(rows[2][1], "Nuts, salted");
+        assert_eq!(rows[2][2], "The \"best\"\nsnack");
+
+        assert!(parse_csv("a,\"b").is_err());
+    }
+
+    #[test]
+    fn tax_is_grouped_by_the_rate_at_time_of_sale() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Widget".into(), "".into(), 10.0, 100)
+            .unwrap();
+        store.record_sale(p.id, 1, 10.0).unwrap();
+        store.settings.tax_rate = 20.0;
+        store.record_sale(p.id, 2, 10.0).unwrap();
+        store.record_sale(p.id, 3, 10.0).unwrap();
+        store.settings.tax_rate = 5.5;
+        store.settings.prices_include_tax = true;
+        store.record_sale(p.id, 1, 21.1).unwrap();
+        let start = parse_date("2024-05-01").unwrap();
+        let end = start + Duration::days(31);
+        for s in &mut store.sales {
+            s.time = start + Duration::days(2);
+        }
+        store.record_sale(p.id, 1, 10.0).unwrap();
+
+        let tax = store.tax_collected_by_rate(start, end);
+        assert_eq!(tax.keys().copied().collect::<Vec<_>>(), vec![0, 550, 2000]);
+        assert_eq!(tax[&0], 0.0);
+        assert!((tax[&2000] - 10.0).abs() < 1e-9);
+        assert!((tax[&550] - 1.1).abs() < 1e-9);
+
+        let base = store.taxable_base_by_rate(start, end);
+        assert!((base[&0] - 10.0).abs() < 1e-9);
+        assert!((base[&2000] - 50.0).abs() < 1e-9);
+        assert!((base[&550] - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn logger_filters_by_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.log");
+        let path = path.to_str().unwrap().to_string();
+
+        let quiet = Logger {
+            level: LogLevel::Error,
+            path: path.clone(),
+        };
+        quiet.log(LogLevel::Info, "sale recorded");
+        assert!(!std::path::Path::new(&path).exists());
+        quiet.log(LogLevel::Error, "save failed");
+
+        let verbose = Logger {
+            level: LogLevel::Info,
+            path: path.clone(),
+        };
+        verbose.log(LogLevel::Info, "sale recorded");
+        verbose.log(LogLevel::Debug, "menu choice 3");
+
+        let lines: Vec<String> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" [ERROR] save failed"));
+        assert!(lines[1].ends_with(" [INFO] sale recorded"));
+        assert!(NaiveDate::parse_from_str(&lines[1][..10], "%Y-%m-%d").is_ok());
+    }
+
+    #[test]
+    fn clone_product_copies_attributes_without_stock() {
+        let mut store = Store::new();
+        let tea = store
+            .add_product("Green tea".into(), "Loose leaf".into(), 7.5, 12)
+            .unwrap();
+        store.add_tag(tea.id, "organic").unwrap();
+        store.set_unit_label(tea.id, Some("kg".into())).unwrap();
+
+        let copy = store.clone_product(tea.id, "Black tea".into()).unwrap();
+        assert_ne!(copy.id, tea.id);
+        assert_eq!(copy.name, "Black tea");
+        assert_eq!(copy.description, "Loose leaf");
+        assert_eq!(copy.price, 7.5);
+        assert_eq!(copy.quantity, 0);
+        assert_eq!(copy.tags, vec!["organic".to_string()]);
+        assert_eq!(copy.unit_label.as_deref(), Some("kg"));
+        assert_eq!(store.find_product(copy.id).unwrap().tags, copy.tags);
+        assert_eq!(store.find_product(tea.id).unwrap().quantity, 12);
+
+        assert!(matches!(
+            store.clone_product(999, "Ghost".into()),
+            Err(StoreError::NotFound(_))
+        ));
+        assert_eq!(store.products.len(), 2);
+    }
+
+    #[test]
+    fn match_products_finds_partial_names() {
+        let mut store = Store::new();
+        store
+            .add_product("Green tea".into(), "".into(), 5.0, 1)
+            .unwrap();
+        store
+            .add_product("Teapot".into(), "".into(), 20.0, 1)
+            .unwrap();
+        store
+            .add_product("Coffee".into(), "".into(), 8.0, 1)
+            .unwrap();
+        store
+            .add_product("Black Tea".into(), "".into(), 5.0, 1)
+            .unwrap();
+
+        let names = |partial: &str| -> Vec<String> {
+            store
+                .match_products(partial)
+                .iter()
+                .map(|p| p.name.clone())
+                .collect()
+        };
+        assert_eq!(names("TEA"), vec!["Teapot", "Black Tea", "Green tea"]);
+        assert_eq!(names("cof"), vec!["Coffee"]);
+        assert!(names("juice").is_empty());
+        assert!(names("  ").is_empty());
+    }
+
+    #[test]
+    fn convert_total_uses_rate_table() {
+        let mut store = Store::new();
+        store.settings.base_currency = "EUR".into();
+        store.settings.currency = "€".into();
+        let usd = store.convert_total(920.0, "USD").unwrap();
+        assert!((usd - 1000.0).abs() < 1e-9);
+        let gbp = store.convert_total(920.0, "gbp").unwrap();
+        assert!((gbp - 790.0).abs() < 1e-9);
+        assert_eq!(store.convert_total(920.0, "XYZ"), None);
+
+        assert_eq!(store.total_money(920.0), "€920.00");
+        store.settings.display_currency = Some("USD".into());
+        assert_eq!(store.total_money(920.0), "€920.00 (1,000.00 USD)");
+    }
+
+    #[test]
+    fn receipt_numbers_pad_the_id() {
+        let mut store = Store::new();
+        let p = store.add_product("Tea".into(), "".into(), 3.0, 5).unwrap();
+        let mut sale = store.record_sale(p.id, 1, 3.0).unwrap();
+        sale.time = Local.with_ymd_and_hms(2024, 3, 9, 12, 0, 0).unwrap();
+        assert_eq!(sale.receipt_number("INV"), "INV-2024-0001");
+        sale.id = 42;
+        assert_eq!(sale.receipt_number("INV"), "INV-2024-0042");
+        sale.id = 12345;
+        assert_eq!(sale.receipt_number("INV"), "INV-2024-12345");
+        assert_eq!(sale.receipt_number("SHOP/A"), "SHOP/A-2024-12345");
+        assert_eq!(sale.receipt_number(""), "2024-12345");
+
+        store.settings.receipt_prefix = "RS".into();
+        store.settings.receipt_template = "{number}: {product}".into();
+        assert_eq!(store.render_receipt(&sale), "RS-2024-12345: Tea");
+    }
+
+    #[test]
+    fn reconcile_counts_sets_counted_quantities() {
+        let mut store = Store::new();
+        let tea = store.add_product("Tea".into(), "".into(), 3.0, 10).unwrap();
+        let mug = store.add_product("Mug".into(), "".into(), 8.0, 4).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("counts.csv");
+        let path = path.to_str().unwrap();
+
+        fs::write(path, "id,counted\n1,7\n\n2,6\n").unwrap();
+        let changes = store.reconcile_counts(path).unwrap();
+        assert_eq!(changes, vec![(tea.id, 10, 7), (mug.id, 4, 6)]);
+        assert_eq!(store.find_product(tea.id).unwrap().quantity, 7);
+        assert_eq!(store.find_product(mug.id).unwrap().quantity, 6);
+        assert!(store
+            .verify_stock()
+            .iter()
+            .all(|(_, recorded, computed)| recorded == computed));
+
+        fs::write(path, "1,5\n99,3\n").unwrap();
+        match store.reconcile_counts(path) {
+            Err(StoreError::NotFound(msg)) => assert!(msg.ends_with(": 99")),
+            other => panic!("expected unknown id error, got {:?}", other),
+        }
+        assert_eq!(store.find_product(tea.id).unwrap().quantity, 7);
+
+        fs::write(path, "1,-2\n").unwrap();
+        assert!(matches!(
+            store.reconcile_counts(path),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
 
-fn sales_menu(store: &mut Store) {
-    loop {
-        println!("\n--- Sales Menu ---");
-        println!("1. Record sale");
-        println!("2. List sales");
-        println!("3. Back");
-        let choice = prompt("Select option: ");
-        match choice.as_str() {
-            "1" => {
-                let pid_s = prompt("Product id: ");
-                let qty_s = prompt("Quantity: ");
-                let price_s = prompt("Sale price per unit: ");
-                match (pid_s.parse::<u32>(), qty_s.parse::<i32>(), price_s.parse::<f64>()) {
-                    (Ok(pid), Ok(qty), Ok(price)) => match store.record_sale(pid, qty, price) {
-                        Ok(sale) => {
-                            println!("Recorded sale: {:?}", sale);
-                            let profit = sale.sale_price * sale.quantity as f64;
-                            println!("Total sale amount: ${:.2}", profit);
-                        }
-                        Err(e) => println!("Error: {:?}", e),
-                    },
-                    _ => println!("Invalid input"),
-                }
-                pause();
-            }
-            "2" => {
-                println!("\nSales history:");
-                for s in &store.sales {
-                    if let Some(prod) = store.find_product(s.product_id) {
-                        println!(
-                            "[{}] {} x{} @ ${:.2} each = ${:.2} at {}",
-                            s.id,
-                            prod.name,
-                            s.quantity,
-                            s.sale_price,
-                            s.sale_price * s.quantity as f64,
-                            s.time
-                        );
-                    }
-                }
-                println!("Total sales: ${:.2}", store.total_sales());
-                pause();
-            }
-            "3" => break,
-            _ => println!("Invalid selection"),
-        }
+    #[test]
+    fn suggest_price_hits_target_margin() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Lamp".into(), "".into(), 15.0, 0)
+            .unwrap();
+        assert_eq!(store.suggest_price(p.id, 50.0), None);
+
+        store.record_purchase(p.id, 2, 8.0, None).unwrap();
+        store.record_purchase(p.id, 2, 12.0, None).unwrap();
+        let price = store.suggest_price(p.id, 50.0).unwrap();
+        assert!((price - 20.0).abs() < 1e-9);
+        store
+            .edit_product(p.id, None, None, Some(price), None)
+            .unwrap();
+        let margin = store
+            .margin_percent(store.find_product(p.id).unwrap())
+            .unwrap();
+        assert!((margin - 50.0).abs() < 1e-9);
+
+        store.settings.prices_include_tax = true;
+        store.settings.tax_rate = 20.0;
+        assert!((store.suggest_price(p.id, 50.0).unwrap() - 24.0).abs() < 1e-9);
+        assert_eq!(store.suggest_price(p.id, 100.0), None);
     }
-}
 
-fn purchases_menu(store: &mut Store) {
-    loop {
-        println!("\n--- Purchases Menu ---");
-        println!("1. Record purchase");
-        println!("2. List purchases");
-        println!("3. Back");
-        let choice = prompt("Select option: ");
-        match choice.as_str() {
-            "1" => {
-                let pid_s = prompt("Product id: ");
-                let qty_s = prompt("Quantity: ");
-                let price_s = prompt("Purchase price per unit: ");
-                match (pid_s.parse::<u32>(), qty_s.parse::<i32>(), price_s.parse::<f64>()) {
-                    (Ok(pid), Ok(qty), Ok(price)) => match store.record_purchase(pid, qty, price) {
-                        Ok(pur) => {
-                            println!("Recorded purchase: {:?}", pur);
-                            println!("Total cost: ${:.2}", pur.purchase_price * pur.quantity as f64);
-                        }
-                        Err(e) => println!("Error: {:?}", e),
-                    },
-                    _ => println!("Invalid input"),
-                }
-                pause();
-            }
-            "2" => {
-                println!("\nPurchase history:");
-                for p in &store.purchases {
-                    if let Some(prod) = store.find_product(p.product_id) {
-                        println!(
-                            "[{}] {} x{} @ ${:.2} each = ${:.2} at {}",
-                            p.id,
-                            prod.name,
-                            p.quantity,
-                            p.purchase_price,
-                            p.purchase_price * p.quantity as f64,
-                            p.time
-                        );
-                    }
-                }
-                println!("Total purchases cost: ${:.2}", store.total_purchases_cost());
-                pause();
-            }
-            "3" => break,
-            _ => println!("Invalid selection"),
+    #[test]
+    fn simulate_price_change_projects_profit() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Lamp".into(), "".into(), 15.0, 0)
+            .unwrap();
+        store.record_purchase(p.id, 10, 10.0, None).unwrap();
+
+        assert!((store.simulate_price_change(p.id, 15.0, 100) - 500.0).abs() < 1e-9);
+        assert!((store.simulate_price_change(p.id, 18.0, 80) - 640.0).abs() < 1e-9);
+        assert!(store.simulate_price_change(p.id, 9.0, 10) < 0.0);
+        assert_eq!(store.find_product(p.id).unwrap().price, 15.0);
+        assert_eq!(store.find_product(p.id).unwrap().quantity, 10);
+
+        store.settings.prices_include_tax = true;
+        store.settings.tax_rate = 20.0;
+        assert!((store.simulate_price_change(p.id, 18.0, 10) - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inventory_column_widths_fit_the_widest_value() {
+        let mut store = Store::new();
+        assert_eq!(store.inventory_column_widths(&[]), [2, 4, 5, 3]);
+        store
+            .add_product("Espresso machine deluxe".into(), "".into(), 1234.5, 7)
+            .unwrap();
+        store
+            .add_product("Cup".into(), "".into(), 2.0, 12000)
+            .unwrap();
+        let products = store.products_ordered(ProductOrder::ById);
+        let [id_w, name_w, price_w, qty_w] = store.inventory_column_widths(&products);
+        assert_eq!(id_w, 2);
+        assert_eq!(name_w, "Espresso machine deluxe".len());
+        assert_eq!(price_w, store.money(1234.5).chars().count());
+        assert!(price_w > 8, "{}", store.money(1234.5));
+        assert_eq!(
+            qty_w,
+            products[1].quantity_with_unit().chars().count().max(3)
+        );
+    }
+
+    #[test]
+    fn products_ordered_sorts_by_each_order() {
+        let mut store = Store::new();
+        store
+            .add_product("pear".into(), "".into(), 2.0, 30)
+            .unwrap(); // value 60
+        store
+            .add_product("Apple".into(), "".into(), 10.0, 8)
+            .unwrap(); // value 80
+        store
+            .add_product("melon".into(), "".into(), 4.0, 12)
+            .unwrap(); // value 48
+        store
+            .add_product("Banana".into(), "".into(), 1.0, 12)
+            .unwrap(); // value 12
+        let ids =
+            |order| -> Vec<u32> { store.products_ordered(order).iter().map(|p| p.id).collect() };
+        assert_eq!(ids(ProductOrder::ById), vec![1, 2, 3, 4]);
+        assert_eq!(ids(ProductOrder::ByName), vec![2, 4, 3, 1]);
+        assert_eq!(ids(ProductOrder::ByQuantity), vec![1, 3, 4, 2]);
+        assert_eq!(ids(ProductOrder::ByValue), vec![2, 1, 3, 4]);
+
+        store.settings.report_order = ProductOrder::ByName;
+        let inventory = store.report_json(ReportSection::Inventory);
+        assert_eq!(inventory[0]["name"], "Apple");
+        let lines = store.full_report_lines(false);
+        assert!(lines[2].starts_with("[2] Apple"));
+    }
+
+    #[test]
+    fn authenticate_records_last_login() {
+        let mut store = Store::new();
+        store.add_manager("clerk", "pw");
+        assert_eq!(store.managers.last().unwrap().last_login, None);
+
+        assert!(!store.authenticate("clerk", "wrong"));
+        assert_eq!(store.managers.last().unwrap().last_login, None);
+        assert!(store.verify_password("clerk", "pw"));
+        assert!(!store.verify_password("clerk", "wrong"));
+        assert_eq!(store.managers.last().unwrap().last_login, None);
+        assert_eq!(store.dirty_ops, 0);
+
+        let before = Local::now();
+        assert!(store.authenticate("clerk", "pw"));
+        let login = store.managers.last().unwrap().last_login.unwrap();
+        assert!(login >= before && login <= Local::now());
+
+        let logins = store.last_logins();
+        assert_eq!(logins[0], ("clerk", Some(login)));
+        assert_eq!(logins.last().unwrap().1, None);
+    }
+
+    #[test]
+    fn stock_histogram_buckets_quantities() {
+        let mut store = Store::new();
+        for qty in [0, -2, 1, 10, 11, 50, 51, 400] {
+            store
+                .add_product(format!("P{}", qty), "".into(), 1.0, qty)
+                .unwrap();
         }
+        assert_eq!(
+            store.stock_histogram(STOCK_HISTOGRAM_BUCKETS),
+            vec![
+                ("<= 0".to_string(), 2),
+                ("1-10".to_string(), 2),
+                ("11-50".to_string(), 2),
+                ("51+".to_string(), 2),
+            ]
+        );
+        assert_eq!(
+            store.stock_histogram(&[10, 0, 1, 10]),
+            vec![
+                ("<= 0".to_string(), 2),
+                ("1".to_string(), 1),
+                ("2-10".to_string(), 1),
+                ("11+".to_string(), 4),
+            ]
+        );
+        assert_eq!(store.stock_histogram(&[]), vec![("any".to_string(), 8)]);
     }
-}
 
-fn reports_menu(store: &Store) {
-    loop {
-        println!("\n--- Reports Menu ---");
-        println!("1. Inventory report");
-        println!("2. Sales & Profit summary");
-        println!("3. Purchase history");
-        println!("4. Full report (all)");
-        println!("5. Back");
-        let choice = prompt("Select option: ");
-        match choice.as_str() {
-            "1" => {
-                println!("\nInventory Report:");
-                println!("{:<5} {:<20} {:<8} {:<6} {}", "ID", "Name", "Price", "Qty", "Description");
-                for p in &store.products {
-                    println!(
-                        "{:<5} {:<20} ${:<7.2} {:<6} {}",
-                        p.id, p.name, p.price, p.quantity, p.description
-                    );
-                }
-                pause();
-            }
-            "2" => {
-                println!("\nSales Summary:");
-                println!("Total Sales: ${:.2}", store.total_sales());
-                println!("Total Purchases Cost: ${:.2}", store.total_purchases_cost());
-                println!("Estimated Profit: ${:.2}", store.profit());
-                pause();
-            }
-            "3" => {
-                println!("\nPurchases:");
-                for p in &store.purchases {
-                    println!(
-                        "[{}] Product {} qty {} @ ${:.2} on {}",
-                        p.id, p.product_id, p.quantity, p.purchase_price, p.time
-                    );
-                }
-                pause();
-            }
-            "4" => {
-                println!("\n--- FULL REPORT ---");
-                println!("Inventory:");
-                for p in &store.products {
-                    println!(
-                        "[{}] {} — ${:.2} — qty {}",
-                        p.id, p.name, p.price, p.quantity
-                    );
-                }
-                println!("\nSales:");
-                for s in &store.sales {
-                    println!(
-                        "[{}] product {} qty {} @ ${:.2} each — total ${:.2} — {}",
-                        s.id,
-                        s.product_id,
-                        s.quantity,
-                        s.sale_price,
-                        s.sale_price * s.quantity as f64,
-                        s.time
-                    );
-                }
-                println!("\nPurchases:");
-                for p in &store.purchases {
-                    println!(
-                        "[{}] product {} qty {} @ ${:.2} each — total ${:.2} — {}",
-                        p.id,
-                        p.product_id,
-                        p.quantity,
-                        p.purchase_price,
-                        p.purchase_price * p.quantity as f64,
-                        p.time
-                    );
-                }
-                println!("\nSummary:");
-                println!("Total Sales: ${:.2}", store.total_sales());
-                println!("Total Purchases Cost: ${:.2}", store.total_purchases_cost());
-                println!("Profit: ${:.2}", store.profit());
-                pause();
-            }
-            "5" => break,
-            _ => println!("Invalid selection"),
+    #[test]
+    fn dry_run_never_writes_the_data_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let path = path.to_str().unwrap();
+        let mut store = Store::new();
+        store.dry_run = true;
+        let p = store.add_product("Tea".into(), "".into(), 3.0, 5).unwrap();
+        store.record_sale(p.id, 2, 3.0).unwrap();
+
+        store.save_to_path(path).unwrap();
+        assert!(!std::path::Path::new(path).exists());
+        assert_eq!(store.find_product(p.id).unwrap().quantity, 3);
+        assert_eq!(store.sales.len(), 1);
+        assert_eq!(store.revision, 0);
+        assert_eq!(
+            store.dry_run_changes,
+            vec![
+                format!("added product {} (Tea)", p.id),
+                "recorded sale 1".to_string()
+            ]
+        );
+
+        store.settings.autosave_interval = 1;
+        store.add_product("Mug".into(), "".into(), 8.0, 1).unwrap();
+        assert!(matches!(
+            store.autosave_if_due(|s| s.save_to_path(path)),
+            Some(Ok(()))
+        ));
+        assert!(!std::path::Path::new(path).exists());
+        assert_eq!(store.products.len(), 2);
+    }
+
+    #[test]
+    fn daily_profit_series_fills_gaps() {
+        let mut store = Store::new();
+        assert!(store.daily_profit_series().is_empty());
+        let p = store.add_product("A".into(), "".into(), 10.0, 0).unwrap();
+        store.record_purchase(p.id, 20, 4.0, None).unwrap();
+        store.record_sale(p.id, 2, 10.0).unwrap();
+        store.record_sale(p.id, 1, 10.0).unwrap();
+        store.record_sale(p.id, 1, 3.0).unwrap();
+        let day = |d| Local.with_ymd_and_hms(2024, 2, d, 10, 0, 0).unwrap();
+        store.sales[0].time = day(27);
+        store.sales[1].time = day(27);
+        store.sales[2].time = day(29);
+
+        let date = |d| NaiveDate::from_ymd_opt(2024, 2, d).unwrap();
+        assert_eq!(
+            store.daily_profit_series(),
+            vec![(date(27), 18.0), (date(28), 0.0), (date(29), -1.0)]
+        );
+        assert_eq!(sparkline(&[18.0, 0.0, -1.0]), "█▁▁");
+        assert_eq!(sparkline(&[-1.0, 6.0, 13.0]), "▁▅█");
+        assert_eq!(sparkline(&[2.0, 2.0]), "▄▄");
+    }
+
+    #[test]
+    fn period_comparison_orders_by_change() {
+        let mut store = Store::new();
+        let ids: Vec<u32> = ["Up", "Down", "New", "Gone"]
+            .iter()
+            .map(|name| {
+                store
+                    .add_product(name.to_string(), "".into(), 1.0, 100)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        let day = |d| Local.with_ymd_and_hms(2024, 3, d, 12, 0, 0).unwrap();
+        for (id, qty, d) in [
+            (ids[0], 2, 1),
+            (ids[0], 3, 20),
+            (ids[1], 10, 2),
+            (ids[1], 4, 21),
+            (ids[2], 7, 22),
+            (ids[3], 5, 3),
+        ] {
+            store.record_sale(id, qty, 1.0).unwrap();
+            store.sales.last_mut().unwrap().time = day(d);
         }
+
+        let rows = store.period_comparison((day(1), day(15)), (day(15), day(31)));
+        assert_eq!(
+            rows,
+            vec![
+                (ids[2], 0, 7, f64::INFINITY),
+                (ids[0], 2, 3, 50.0),
+                (ids[1], 10, 4, -60.0),
+                (ids[3], 5, 0, -100.0),
+            ]
+        );
+        assert!(store
+            .period_comparison((day(25), day(26)), (day(26), day(27)))
+            .is_empty());
     }
-}
 
-fn login_sequence() -> bool {
-    println!("Please login as manager to continue.");
-    let username = prompt("Username: ");
-    print!("Password: ");
-    let _ = io::stdout().flush();
-    let password = read_password().unwrap_or_else(|_| prompt("Password (fallback): "));
-    match Store::load_from_file() {
-        Ok(store) => {
-            if store.authenticate(&username, &password) {
-                println!("Login success. Welcome, {}!", username);
-                true
-            } else {
-                println!("Login failed.");
-                false
-            }
+    #[test]
+    fn confirmation_only_above_threshold() {
+        assert!(!needs_confirmation(999.99, 1000.0));
+        assert!(!needs_confirmation(1000.0, 1000.0));
+        assert!(needs_confirmation(1000.01, 1000.0));
+        assert!(!needs_confirmation(1_000_000.0, 0.0));
+        assert_eq!(Settings::default().confirm_above, 0.0);
+    }
+
+    fn script_input(lines: &str) {
+        let reader: Box<dyn BufRead> = Box::new(io::Cursor::new(lines.to_string()));
+        SCRIPTED_INPUT.with(|input| *input.borrow_mut() = Some(reader));
+    }
+
+    #[test]
+    fn menus_exit_at_end_of_input() {
+        let mut store = Store::new();
+        store.dry_run = true;
+        store
+            .add_product("Widget".into(), "".into(), 2.0, 3)
+            .unwrap();
+
+        script_input("");
+        assert_eq!(prompt("> "), None);
+
+        // List products, dismiss the pause, then run out inside the menu.
+        script_input("1\n\n");
+        inventory_menu(&mut store);
+        // Run out while picking the product for a sale.
+        script_input("1\nWid");
+        sales_menu(&mut store);
+        assert!(store.sales.is_empty());
+        script_input("9\n");
+        settings_menu(&mut store, DEFAULT_ADMIN_USER);
+        script_input("");
+        purchases_menu(&mut store);
+        reports_menu(&store, &CliOptions::from_args(&[], false));
+
+        // The main menu saves and exits.
+        script_input("2\n");
+        ui_loop(
+            store,
+            DEFAULT_ADMIN_USER,
+            &CliOptions::from_args(&[], false),
+        );
+    }
+
+    #[test]
+    fn running_out_of_input_abandons_the_operation() {
+        let mut store = Store::new();
+        store.dry_run = true;
+        let p = store
+            .add_product("Widget".into(), "Blue".into(), 2.0, 3)
+            .unwrap();
+        let settings = store.settings.clone();
+
+        // Run out after picking the product to edit.
+        script_input("3\n1\n");
+        inventory_menu(&mut store);
+        // Run out before the tag is entered.
+        script_input("8\n1\n");
+        inventory_menu(&mut store);
+        // Run out halfway through the receipt and number format settings.
+        script_input("4\nNew {total}\n");
+        settings_menu(&mut store, DEFAULT_ADMIN_USER);
+        script_input("6\n€\n.\n");
+        settings_menu(&mut store, DEFAULT_ADMIN_USER);
+        // Run out at the lot number of a purchase.
+        script_input("1\n1\n5\n1.5\n");
+        purchases_menu(&mut store);
+
+        let widget = store.find_product(p.id).unwrap();
+        assert_eq!(
+            (widget.name.as_str(), widget.description.as_str()),
+            ("Widget", "Blue")
+        );
+        assert_eq!((widget.price, widget.quantity), (2.0, 3));
+        assert!(widget.unit_label.is_none() && widget.tags.is_empty());
+        assert!(store.settings == settings);
+        assert!(store.purchases.is_empty());
+    }
+
+    #[test]
+    fn failed_sales_change_nothing() {
+        let mut store = Store::new();
+        let p = store
+            .add_product("Lamp".into(), "".into(), 40.0, 5)
+            .unwrap();
+        let long_ago = Local::now() - Duration::days(1);
+        let later = Local::now() + Duration::days(1);
+        store.reserve(p.id, 2, later).unwrap();
+        let expired = store.reserve(p.id, 1, later).unwrap();
+        store.reservations[1].expires = long_ago;
+        let held = store.reserve(p.id, 1, later).unwrap();
+        let reservations = store.reservations.clone();
+        let (next_id, dirty) = (store.next_sale_id, store.dirty_ops);
+
+        // Fails the stock check after the quantity check passed: 5 in
+        // stock, 3 still held.
+        assert!(matches!(
+            store.record_sale(p.id, 3, 40.0),
+            Err(StoreError::InsufficientStock(_))
+        ));
+        assert!(store.record_sale(p.id, 1, -40.0).is_err());
+        assert!(store.record_sale(p.id, 1, f64::NAN).is_err());
+        assert!(store.fulfill(held.id, f64::INFINITY).is_err());
+
+        assert_eq!(store.find_product(p.id).unwrap().quantity, 5);
+        assert!(store.sales.is_empty());
+        assert_eq!(store.next_sale_id, next_id);
+        assert_eq!(store.dirty_ops, dirty);
+        let ids = |rs: &[Reservation]| rs.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(&store.reservations), ids(&reservations));
+
+        // A successful sale also drops the expired reservation.
+        store.record_sale(p.id, 2, 40.0).unwrap();
+        assert!(store.reservations.iter().all(|r| r.id != expired.id));
+        assert_eq!(store.find_product(p.id).unwrap().quantity, 3);
+    }
+
+    #[test]
+    fn id_reuse_skips_ids_with_history() {
+        let mut store = Store::new();
+        for name in ["A", "B", "C", "D", "E"] {
+            store.add_product(name.into(), "".into(), 1.0, 5).unwrap();
         }
-        Err(e) => {
-            println!("Failed to load data (proceeding): {:?}", e);
-            false
+        store.record_sale(2, 1, 1.0).unwrap();
+        store.record_purchase(3, 1, 0.5, None).unwrap();
+        store.repackage(4, 5, 1, 1).unwrap();
+        for id in 1..=4 {
+            store.delete_product(id).unwrap();
         }
+
+        // Default policy: ids only go up.
+        assert_eq!(
+            store.add_product("F".into(), "".into(), 1.0, 0).unwrap().id,
+            6
+        );
+
+        store.settings.reuse_ids = true;
+        assert_eq!(
+            store.add_product("G".into(), "".into(), 1.0, 0).unwrap().id,
+            1
+        );
+        // 2 has a sale, 3 a purchase and 4 a repackaging: no gap is left.
+        assert_eq!(
+            store.add_product("H".into(), "".into(), 1.0, 0).unwrap().id,
+            7
+        );
+        assert_eq!(store.next_product_id, 8);
+        assert!(store
+            .verify_stock()
+            .iter()
+            .all(|(_, recorded, computed)| recorded == computed));
+        assert_eq!(store.sales[0].product_id, 2);
+        assert!(store.find_product(2).is_none());
     }
-}
 
-fn main() {
-    main_menu();
-    let store = match Store::load_from_file() {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("Failed to load data: {:?}. Starting with empty store.", e);
-            let mut s = Store::new();
-            s
+    #[test]
+    fn managers_change_their_own_password() {
+        let mut store = Store::new();
+        store.add_manager("clerk", "old");
+        let salt = store.managers.last().unwrap().salt.clone();
+
+        assert!(matches!(
+            store.change_own_password("clerk", "wrong", "Sturdy123"),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(store
+            .change_own_password("nobody", "old", "Sturdy123")
+            .is_err());
+        for weak in ["short1", "nodigitshere", "1234567890"] {
+            assert!(store.change_own_password("clerk", "old", weak).is_err());
         }
-    };
-    if !login_sequence() {
-        println!("Exiting due to authentication failure.");
-        return;
+        assert!(store.authenticate("clerk", "old"));
+
+        store
+            .change_own_password("clerk", "old", "Sturdy123")
+            .unwrap();
+        assert!(store.authenticate("clerk", "Sturdy123"));
+        assert!(!store.authenticate("clerk", "old"));
+        assert_ne!(store.managers.last().unwrap().salt, salt);
+        assert!(store
+            .change_own_password("clerk", "Sturdy123", "Sturdy123")
+            .is_err());
     }
-    let store = Store::load_from_file().unwrap_or_else(|_| Store::new());
-    ui_loop(store);
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn change_my_password_uses_the_logged_in_manager() {
+        let mut store = Store::new();
+        store.dry_run = true;
+        store.add_manager("clerk", "old");
+        script_input("14\nold\nSturdy123\nSturdy123\n16\n");
+        settings_menu(&mut store, "clerk");
+        assert!(store.authenticate("clerk", "Sturdy123"));
+        assert!(store.authenticate(DEFAULT_ADMIN_USER, DEFAULT_ADMIN_PASS));
+    }
 
     #[test]
-    fn add_edit_delete_product() {
+    fn ean13_skus_need_a_valid_check_digit() {
+        assert!(validate_ean13("4006381333931"));
+        assert!(validate_ean13("5901234123457"));
+        assert!(!validate_ean13("4006381333932"));
+        assert!(!validate_ean13("400638133393"));
+        assert!(!validate_ean13("40063813339A1"));
+
         let mut store = Store::new();
-        let p = store.add_product("P".into(), "D".into(), 9.99, 10);
-        assert_eq!(p.id, 1);
-        let edited = store
-            .edit_product(p.id, Some("P2".into()), None, Some(10.0), Some(5))
+        let p = store.add_product("Pen".into(), "".into(), 1.0, 1).unwrap();
+        store.set_sku(p.id, "4006381333931").unwrap();
+        assert!(matches!(
+            store.set_sku(p.id, "4006381333932"),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert_eq!(
+            store.find_product(p.id).unwrap().sku.as_deref(),
+            Some("4006381333931")
+        );
+        store.set_sku(p.id, " PEN-BLUE-01 ").unwrap();
+        assert_eq!(
+            store.find_product(p.id).unwrap().sku.as_deref(),
+            Some("PEN-BLUE-01")
+        );
+        store.set_sku(p.id, "").unwrap();
+        assert_eq!(store.find_product(p.id).unwrap().sku, None);
+        assert!(store.set_sku(99, "X").is_err());
+    }
+
+    #[test]
+    fn read_only_mode_blocks_changes() {
+        assert!(CliOptions::from_args(&["--read-only".to_string()], true).read_only);
+        assert!(!CliOptions::from_args(&[], true).read_only);
+
+        let mut store = Store::new();
+        let p = store
+            .add_product("Kettle".into(), "".into(), 25.0, 4)
             .unwrap();
-        assert_eq!(edited.name, "P2");
-        assert_eq!(edited.price, 10.0);
-        assert_eq!(edited.quantity, 5);
-        assert!(store.delete_product(p.id).is_ok());
-        assert!(store.delete_product(999).is_err());
+        let lid = store.add_product("Lid".into(), "".into(), 2.0, 0).unwrap();
+        store.read_only = true;
+        let read_only = |r: Result<(), StoreError>| matches!(r, Err(StoreError::InvalidInput(msg)) if msg.contains("read-only"));
+        assert!(read_only(store.record_sale(p.id, 1, 25.0).map(|_| ())));
+        assert!(read_only(
+            store.record_purchase(p.id, 1, 9.0, None).map(|_| ())
+        ));
+        assert!(read_only(
+            store
+                .edit_product(p.id, None, None, Some(1.0), None)
+                .map(|_| ())
+        ));
+        assert!(read_only(store.delete_product(p.id)));
+        assert!(read_only(store.save_to_path("unused.json")));
+        assert!(read_only(
+            store
+                .add_product("Mug".into(), "".into(), 5.0, 1)
+                .map(|_| ())
+        ));
+        let tomorrow = Local::now() + Duration::days(1);
+        assert!(read_only(store.reserve(p.id, 1, tomorrow).map(|_| ())));
+        assert!(read_only(store.repackage(p.id, lid.id, 1, 1)));
+        assert!(read_only(store.add_tag(p.id, "sale")));
+        assert!(read_only(store.set_sku(p.id, "KTL-1")));
+        assert!(read_only(
+            store.clone_product(p.id, "Copy".into()).map(|_| ())
+        ));
+        assert!(read_only(store.reset_password(
+            DEFAULT_ADMIN_USER,
+            DEFAULT_ADMIN_USER,
+            "Another123"
+        )));
+        assert_eq!(store.products.len(), 2);
+        assert!(store.reservations.is_empty());
+        let kettle = store.find_product(p.id).unwrap();
+        assert_eq!((kettle.price, kettle.quantity), (25.0, 4));
+        assert!(kettle.tags.is_empty() && kettle.sku.is_none());
+        assert!(store.sales.is_empty() && store.purchases.is_empty());
+
+        // The kiosk menu lists, searches and exits at end of input.
+        script_input("1\n\n2\nkett\n\n");
+        ui_loop(
+            store,
+            DEFAULT_ADMIN_USER,
+            &CliOptions::from_args(&[], false),
+        );
     }
 
     #[test]
-    fn purchase_and_sales() {
+    fn cash_rounding_of_sale_prices() {
+        assert_eq!(round_to_nearest(1.23, 0.05), 1.25);
+        assert_eq!(round_to_nearest(1.22, 0.05), 1.2);
+        assert_eq!(round_to_nearest(1.025, 0.05), 1.05);
+        assert_eq!(round_to_nearest(1.24, 0.10), 1.2);
+        assert_eq!(round_to_nearest(1.25, 0.10), 1.3);
+        assert_eq!(round_to_nearest(9.99, 0.10), 10.0);
+        assert_eq!(round_to_nearest(1.23, 0.0), 1.23);
+
         let mut store = Store::new();
-        let p = store.add_product("A".into(), "desc".into(), 5.0, 2);
-        let pur = store.record_purchase(p.id, 10, 4.0).unwrap();
-        assert_eq!(pur.quantity, 10);
-        assert!((store.total_purchases_cost() - 40.0).abs() < 1e-6);
-        let sale = store.record_sale(p.id, 5, 7.0).unwrap();
-        assert_eq!(sale.quantity, 5);
-        let prod = store.find_product(p.id).unwrap();
-        assert_eq!(prod.quantity, 7); 
+        let p = store.add_product("Gum".into(), "".into(), 1.0, 10).unwrap();
+        store.record_sale(p.id, 1, 0.99).unwrap();
+        assert_eq!(store.sales[0].exact_price, None);
+        store.settings.cash_rounding = 0.05;
+        let sale = store.record_sale(p.id, 2, 0.99).unwrap();
+        assert_eq!((sale.sale_price, sale.exact_price), (1.0, Some(0.99)));
+        store.record_sale(p.id, 1, 1.02).unwrap();
+        assert!((store.cash_rounding_total() - 0.0).abs() < 1e-9);
+        store.record_sale(p.id, 1, 1.04).unwrap();
+        assert!((store.cash_rounding_total() - 0.01).abs() < 1e-9);
+        assert!(store
+            .full_report_lines(false)
+            .contains(&"Cash rounding: $0.01".to_string()));
     }
 
     #[test]
-    fn auth_and_manager() {
+    fn rapid_identical_sales_are_flagged() {
         let mut store = Store::new();
-        store.add_manager("test", "1234");
-        assert!(store.authenticate("test", "1234"));
-        assert!(!store.authenticate("test", "wrong"));
+        let p = store.add_product("Tea".into(), "".into(), 3.0, 10).unwrap();
+        let q = store
+            .add_product("Cake".into(), "".into(), 4.0, 10)
+            .unwrap();
+        let t0 = Local.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap();
+        assert!(!store.is_probable_duplicate_sale(p.id, 1, 3.0, t0));
+        store.record_sale(p.id, 1, 3.0).unwrap();
+        store.sales[0].time = t0;
+
+        let soon = t0 + Duration::seconds(3);
+        assert!(store.is_probable_duplicate_sale(p.id, 1, 3.0, soon));
+        assert!(!store.is_probable_duplicate_sale(p.id, 2, 3.0, soon));
+        assert!(!store.is_probable_duplicate_sale(p.id, 1, 2.5, soon));
+        assert!(!store.is_probable_duplicate_sale(q.id, 1, 3.0, soon));
+        let later = t0 + Duration::seconds(DUPLICATE_SALE_WINDOW_SECS);
+        assert!(!store.is_probable_duplicate_sale(p.id, 1, 3.0, later));
+
+        // Only the previous sale counts.
+        store.record_sale(q.id, 1, 4.0).unwrap();
+        store.sales[1].time = t0 + Duration::seconds(1);
+        assert!(!store.is_probable_duplicate_sale(p.id, 1, 3.0, soon));
+    }
+
+    #[test]
+    fn edit_product_menu_applies_unit_and_expiry() {
+        let mut store = Store::new();
+        store.dry_run = true;
+        let p = store.add_product("Rice".into(), "".into(), 2.0, 3).unwrap();
+        script_input("3\n1\n\n\n2.5\n\nkg\n2030-01-31\n\n14\n");
+        inventory_menu(&mut store);
+        let rice = store.find_product(p.id).unwrap();
+        assert_eq!(rice.price, 2.5);
+        assert_eq!(rice.unit_label.as_deref(), Some("kg"));
+        assert_eq!(rice.expiry, parse_date("2030-01-31"));
     }
 }
@@ -1,4 +1,76 @@
-use std::io;
+use chrono::{DateTime, Datelike, Local};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+/// Decimal digits in account numbers unless `--account-digits` says
+/// otherwise, e.g. 4 gives 1000-9999.
+const DEFAULT_ACCOUNT_NUMBER_DIGITS: u32 = 4;
+/// Most digits `--account-digits` accepts, so every number fits a `u32`.
+const MAX_ACCOUNT_NUMBER_DIGITS: u32 = 9;
+const RECEIPT_FILE: &str = "receipts.txt";
+/// Minimum inner width of a receipt box; longer lines widen it.
+const RECEIPT_WIDTH: usize = 36;
+/// Identifies this bank in OFX statements.
+const OFX_BANK_ID: &str = "SIMPLEBANK";
+/// Daily interest charged on overdrawn balances (0.05% per day).
+const OVERDRAFT_DAILY_RATE: f64 = 0.0005;
+/// Days a new overdraft is interest-free unless an account sets its own.
+const OVERDRAFT_GRACE_DAYS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TxnKind {
+    Deposit,
+    Withdrawal,
+    Interest,
+}
+
+impl TxnKind {
+    fn label(self) -> &'static str {
+        match self {
+            TxnKind::Deposit => "Deposit",
+            TxnKind::Withdrawal => "Withdrawal",
+            TxnKind::Interest => "Interest",
+        }
+    }
+
+    /// The OFX `TRNTYPE` and the sign of the amount as seen by the holder.
+    /// Overdraft interest is charged to the holder, so it is a `FEE`; OFX's
+    /// `INT` means interest earned.
+    fn ofx_type(self) -> (&'static str, f64) {
+        match self {
+            TxnKind::Deposit => ("CREDIT", 1.0),
+            TxnKind::Withdrawal => ("DEBIT", -1.0),
+            TxnKind::Interest => ("FEE", -1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Transaction {
+    kind: TxnKind,
+    amount: f64,
+    balance_after: f64,
+    time: DateTime<Local>,
+}
+
+/// Per-account caps on single deposits and withdrawals and on the total
+/// withdrawn in one calendar day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AccountLimits {
+    max_deposit: f64,
+    max_withdraw: f64,
+    daily_withdraw: f64,
+}
+
+impl Default for AccountLimits {
+    fn default() -> Self {
+        AccountLimits {
+            max_deposit: 10_000.0,
+            max_withdraw: 2_000.0,
+            daily_withdraw: 5_000.0,
+        }
+    }
+}
 
 trait Account {
     fn deposit(&mut self, amount: f64) -> Result<(), String>;
@@ -10,6 +82,103 @@ struct BankAccount {
     account_number: u32,
     holder_name: String,
     balance: f64,
+    /// How far below zero the balance may go.
+    overdraft_limit: f64,
+    transactions: Vec<Transaction>,
+    /// When `run_interest` last processed this account.
+    last_interest_date: Option<DateTime<Local>>,
+    limits: AccountLimits,
+    /// When the balance last went below zero; `None` while it is not negative.
+    overdraft_since: Option<DateTime<Local>>,
+    /// Days after going overdrawn before interest is charged.
+    overdraft_grace_days: u32,
+    /// Round each interest charge, and the balance after it, to the cent.
+    round_interest: bool,
+}
+
+impl BankAccount {
+    fn new(account_number: u32, holder_name: &str, balance: f64) -> Self {
+        BankAccount {
+            account_number,
+            holder_name: holder_name.to_string(),
+            balance,
+            overdraft_limit: 0.0,
+            transactions: Vec::new(),
+            last_interest_date: None,
+            limits: AccountLimits::default(),
+            overdraft_since: None,
+            overdraft_grace_days: OVERDRAFT_GRACE_DAYS,
+            round_interest: true,
+        }
+    }
+
+    /// Starts the overdraft clock when the balance is negative and stops it
+    /// once the balance is back to zero or above.
+    fn update_overdraft_since(&mut self, now: DateTime<Local>) {
+        if self.balance >= 0.0 {
+            self.overdraft_since = None;
+        } else if self.overdraft_since.is_none() {
+            self.overdraft_since = Some(now);
+        }
+    }
+
+    /// How many of the `days` up to `as_of` are past the overdraft's grace
+    /// period, i.e. may be charged interest.
+    fn chargeable_overdraft_days(&self, days: u32, as_of: DateTime<Local>) -> u32 {
+        match self.overdraft_since {
+            Some(since) => {
+                let grace_end = since + chrono::Duration::days(self.overdraft_grace_days.into());
+                let past_grace = (as_of - grace_end).num_days().max(0);
+                days.min(past_grace as u32)
+            }
+            None => days,
+        }
+    }
+
+    /// Total withdrawn on the calendar day of `day`.
+    fn withdrawn_on(&self, day: DateTime<Local>) -> f64 {
+        self.transactions
+            .iter()
+            .filter(|t| t.kind == TxnKind::Withdrawal && t.time.date_naive() == day.date_naive())
+            .map(|t| t.amount)
+            .sum()
+    }
+
+    fn record(&mut self, kind: TxnKind, amount: f64) {
+        let now = Local::now();
+        self.update_overdraft_since(now);
+        self.transactions.push(Transaction {
+            kind,
+            amount,
+            balance_after: self.balance,
+            time: now,
+        });
+    }
+
+    /// Compounds `daily_rate` interest on a negative balance for `days` days
+    /// and charges the total as one interest transaction. With
+    /// `round_interest` the total is rounded once, so small overdrafts still
+    /// add up to whole cents. Non-negative balances and charges that round
+    /// to nothing are left untouched.
+    fn accrue_overdraft_interest(&mut self, daily_rate: f64, days: u32) {
+        if self.balance >= 0.0 {
+            return;
+        }
+        let interest = -self.balance * ((1.0 + daily_rate).powi(days as i32) - 1.0);
+        let interest = if self.round_interest {
+            round_cents(interest)
+        } else {
+            interest
+        };
+        if interest == 0.0 {
+            return;
+        }
+        self.balance -= interest;
+        if self.round_interest {
+            self.balance = round_cents(self.balance);
+        }
+        self.record(TxnKind::Interest, interest);
+    }
 }
 
 impl Account for BankAccount {
@@ -17,8 +186,15 @@ impl Account for BankAccount {
         if amount <= 0.0 {
             return Err("Deposit amount must be greater than zero.".to_string());
         }
+        if amount > self.limits.max_deposit {
+            return Err(format!(
+                "Deposit exceeds the maximum deposit limit of ${:.2}.",
+                self.limits.max_deposit
+            ));
+        }
 
         self.balance += amount;
+        self.record(TxnKind::Deposit, amount);
         println!(
             "Deposited ${:.2} into account {} ({}) — New balance: ${:.2}",
             amount, self.account_number, self.holder_name, self.balance
@@ -30,14 +206,28 @@ impl Account for BankAccount {
         if amount <= 0.0 {
             return Err("Withdrawal amount must be greater than zero.".to_string());
         }
-        if amount > self.balance {
+        if amount > self.limits.max_withdraw {
+            return Err(format!(
+                "Withdrawal exceeds the maximum withdrawal limit of ${:.2}.",
+                self.limits.max_withdraw
+            ));
+        }
+        let withdrawn_today = self.withdrawn_on(Local::now());
+        if withdrawn_today + amount > self.limits.daily_withdraw {
+            return Err(format!(
+                "Withdrawal exceeds the daily withdrawal limit of ${:.2} (${:.2} already withdrawn today).",
+                self.limits.daily_withdraw, withdrawn_today
+            ));
+        }
+        if amount > self.balance + self.overdraft_limit {
             return Err(format!(
-                "Insufficient funds in account {} ({}). Current balance: ${:.2}",
-                self.account_number, self.holder_name, self.balance
+                "Insufficient funds in account {} ({}). Current balance: ${:.2}, overdraft limit: ${:.2}",
+                self.account_number, self.holder_name, self.balance, self.overdraft_limit
             ));
         }
 
         self.balance -= amount;
+        self.record(TxnKind::Withdrawal, amount);
         println!(
             "Withdrew ${:.2} from account {} ({}) — New balance: ${:.2}",
             amount, self.account_number, self.holder_name, self.balance
@@ -50,72 +240,742 @@ impl Account for BankAccount {
     }
 }
 
-fn main() {
-    let mut accounts = vec![
-        BankAccount {
-            account_number: 1001,
-            holder_name: String::from("Alice"),
-            balance: 500.0,
-        },
-        BankAccount {
-            account_number: 1002,
-            holder_name: String::from("Bob"),
-            balance: 1000.0,
-        },
+/// A credit line: withdrawals draw down credit up to `credit_limit` and
+/// deposits repay the outstanding principal.
+#[derive(Debug)]
+struct LoanAccount {
+    account_number: u32,
+    holder_name: String,
+    credit_limit: f64,
+    principal: f64,
+}
+
+impl LoanAccount {
+    fn new(account_number: u32, holder_name: &str, credit_limit: f64) -> Self {
+        LoanAccount {
+            account_number,
+            holder_name: holder_name.to_string(),
+            credit_limit,
+            principal: 0.0,
+        }
+    }
+
+    fn remaining_principal(&self) -> f64 {
+        self.principal
+    }
+
+    fn available_credit(&self) -> f64 {
+        self.credit_limit - self.principal
+    }
+}
+
+impl Account for LoanAccount {
+    fn deposit(&mut self, amount: f64) -> Result<(), String> {
+        if amount <= 0.0 {
+            return Err("Repayment amount must be greater than zero.".to_string());
+        }
+        if amount > self.principal {
+            return Err(format!(
+                "Repayment of ${:.2} exceeds the ${:.2} owed on loan {} ({}).",
+                amount, self.principal, self.account_number, self.holder_name
+            ));
+        }
+
+        self.principal -= amount;
+        println!(
+            "Repaid ${:.2} on loan {} ({}) — Remaining principal: ${:.2}",
+            amount, self.account_number, self.holder_name, self.principal
+        );
+        Ok(())
+    }
+
+    fn withdraw(&mut self, amount: f64) -> Result<(), String> {
+        if amount <= 0.0 {
+            return Err("Draw-down amount must be greater than zero.".to_string());
+        }
+        if amount > self.available_credit() {
+            return Err(format!(
+                "Insufficient credit on loan {} ({}). Available credit: ${:.2}",
+                self.account_number,
+                self.holder_name,
+                self.available_credit()
+            ));
+        }
+
+        self.principal += amount;
+        println!(
+            "Drew down ${:.2} on loan {} ({}) — Remaining principal: ${:.2}",
+            amount, self.account_number, self.holder_name, self.principal
+        );
+        Ok(())
+    }
+
+    /// The amount owed, as a negative balance.
+    fn balance(&self) -> f64 {
+        -self.principal
+    }
+}
+
+/// How many decimal digits account numbers have.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AccountNumberFormat {
+    digits: u32,
+}
+
+impl AccountNumberFormat {
+    /// `None` unless `digits` is between 1 and [`MAX_ACCOUNT_NUMBER_DIGITS`].
+    fn new(digits: u32) -> Option<Self> {
+        (1..=MAX_ACCOUNT_NUMBER_DIGITS)
+            .contains(&digits)
+            .then_some(AccountNumberFormat { digits })
+    }
+
+    /// Smallest and largest valid account number.
+    fn bounds(self) -> (u32, u32) {
+        (10u32.pow(self.digits - 1), 10u32.pow(self.digits) - 1)
+    }
+}
+
+impl Default for AccountNumberFormat {
+    fn default() -> Self {
+        AccountNumberFormat {
+            digits: DEFAULT_ACCOUNT_NUMBER_DIGITS,
+        }
+    }
+}
+
+fn validate_account_number(n: u32, format: AccountNumberFormat) -> bool {
+    let (min, max) = format.bounds();
+    (min..=max).contains(&n)
+}
+
+/// Returns the next free account number after the highest one in use,
+/// wrapping around to fill gaps once the top of the range is reached.
+/// `None` once every number allowed by `format` is taken.
+fn next_account_number(existing: &[u32], format: AccountNumberFormat) -> Option<u32> {
+    let (min, max) = format.bounds();
+    let start = existing
+        .iter()
+        .copied()
+        .filter(|n| validate_account_number(*n, format))
+        .max()
+        .map_or(min, |n| if n == max { min } else { n + 1 });
+    (start..=max)
+        .chain(min..start)
+        .find(|n| !existing.contains(n))
+}
+
+fn numbers_exhausted(format: AccountNumberFormat) -> String {
+    format!(
+        "No {}-digit account numbers are left; restart with a larger --account-digits.",
+        format.digits
+    )
+}
+
+/// Rounds to the nearest cent, halves away from zero: 0.125 becomes 0.13.
+/// The tiny nudge keeps amounts like 1.005, stored as 1.00499..., from
+/// rounding down.
+fn round_cents(amount: f64) -> f64 {
+    let cents = amount * 100.0;
+    (cents + cents.signum() * 1e-6).round() / 100.0
+}
+
+/// Period-end interest job. Charges overdraft interest for the days since
+/// each account was last processed (or since the start of the month on the
+/// first run), at most once per calendar month. Days within an overdraft's
+/// grace period are not charged. Returns the account number and interest
+/// charged for every account that paid interest.
+fn run_interest(accounts: &mut [BankAccount], as_of: DateTime<Local>) -> Vec<(u32, f64)> {
+    let mut charged = Vec::new();
+    for acc in accounts.iter_mut() {
+        let days = match acc.last_interest_date {
+            Some(last) if (last.year(), last.month()) == (as_of.year(), as_of.month()) => continue,
+            Some(last) => (as_of - last).num_days().max(0) as u32,
+            None => as_of.day(),
+        };
+        let before = acc.balance;
+        let days = acc.chargeable_overdraft_days(days, as_of);
+        acc.accrue_overdraft_interest(OVERDRAFT_DAILY_RATE, days);
+        acc.last_interest_date = Some(as_of);
+        if acc.balance != before {
+            charged.push((acc.account_number, before - acc.balance));
+        }
+    }
+    charged
+}
+
+/// Accounts with a negative balance, most overdrawn first. A balance of
+/// exactly zero is not overdrawn.
+fn overdrawn_accounts(accounts: &[BankAccount]) -> Vec<&BankAccount> {
+    let mut overdrawn: Vec<&BankAccount> = accounts.iter().filter(|a| a.balance < 0.0).collect();
+    overdrawn.sort_by(|a, b| a.balance.total_cmp(&b.balance));
+    overdrawn
+}
+
+/// Sum of all balances. Loans count against it, since a loan's balance
+/// is its outstanding principal as a negative amount, and so do overdrafts.
+fn net_worth(accounts: &[&dyn Account]) -> f64 {
+    accounts.iter().map(|a| a.balance()).sum()
+}
+
+fn open_account(
+    accounts: &mut Vec<BankAccount>,
+    holder_name: String,
+    format: AccountNumberFormat,
+) -> Result<u32, String> {
+    let existing: Vec<u32> = accounts.iter().map(|a| a.account_number).collect();
+    let account_number =
+        next_account_number(&existing, format).ok_or_else(|| numbers_exhausted(format))?;
+    accounts.push(BankAccount::new(account_number, &holder_name, 0.0));
+    Ok(account_number)
+}
+
+/// Moves `amount` from one account to another. If the deposit is refused,
+/// the withdrawal is undone.
+fn transfer(accounts: &mut [BankAccount], from: u32, to: u32, amount: f64) -> Result<(), String> {
+    let position = |n: u32| {
+        accounts
+            .iter()
+            .position(|a| a.account_number == n)
+            .ok_or_else(|| format!("Account number {} not found.", n))
+    };
+    let (src, dest) = (position(from)?, position(to)?);
+    if src == dest {
+        return Err("Cannot transfer to the same account.".to_string());
+    }
+    accounts[src].withdraw(amount)?;
+    if let Err(e) = accounts[dest].deposit(amount) {
+        accounts[src].balance += amount;
+        accounts[src].transactions.pop();
+        accounts[src].update_overdraft_since(Local::now());
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Moves the balance and transaction history of `source` into `dest` and
+/// closes `source`. Merged transactions keep the `balance_after` they were
+/// recorded with in the source account. Limits do not apply.
+fn merge_accounts(accounts: &mut Vec<BankAccount>, source: u32, dest: u32) -> Result<(), String> {
+    if source == dest {
+        return Err("Cannot merge an account into itself.".to_string());
+    }
+    if !accounts.iter().any(|a| a.account_number == dest) {
+        return Err(format!("Account number {} not found.", dest));
+    }
+    let src = accounts
+        .iter()
+        .position(|a| a.account_number == source)
+        .ok_or_else(|| format!("Account number {} not found.", source))?;
+    let merged = accounts.remove(src);
+    let target = accounts
+        .iter_mut()
+        .find(|a| a.account_number == dest)
+        .expect("destination checked above");
+    target.balance += merged.balance;
+    target.update_overdraft_since(Local::now());
+    target.transactions.extend(merged.transactions);
+    target.transactions.sort_by_key(|t| t.time);
+    Ok(())
+}
+
+/// Removes an account whose balance is zero and returns its closing
+/// statement: lifetime deposits and withdrawals, the final balance and the
+/// closure date. Accounts with money in them or owing money are kept.
+fn close_account(
+    accounts: &mut Vec<BankAccount>,
+    account_number: u32,
+    now: DateTime<Local>,
+) -> Result<String, String> {
+    let pos = accounts
+        .iter()
+        .position(|a| a.account_number == account_number)
+        .ok_or_else(|| format!("Account number {} not found.", account_number))?;
+    let balance = accounts[pos].balance;
+    if balance.abs() >= 0.005 {
+        return Err(format!(
+            "Account {} still has a balance of ${:.2}; withdraw or deposit it first.",
+            account_number, balance
+        ));
+    }
+    let account = accounts.remove(pos);
+    let total = |kind: TxnKind| -> f64 {
+        account
+            .transactions
+            .iter()
+            .filter(|t| t.kind == kind)
+            .map(|t| t.amount)
+            .sum()
+    };
+    Ok([
+        format!(
+            "CLOSING STATEMENT — account {} ({})",
+            account.account_number, account.holder_name
+        ),
+        format!("Closed on:         {}", now.format("%Y-%m-%d")),
+        format!("Total deposits:    ${:.2}", total(TxnKind::Deposit)),
+        format!("Total withdrawals: ${:.2}", total(TxnKind::Withdrawal)),
+        format!("Final balance:     ${:.2}", account.balance.abs()),
+    ]
+    .join("\n"))
+}
+
+/// Runs one command per line against `accounts`: `deposit <acct> <amount>`,
+/// `withdraw <acct> <amount>` or `transfer <from> <to> <amount>`. Blank
+/// lines and lines starting with `#` are skipped. Returns a result line per
+/// command; failed operations are reported and the script carries on, but
+/// a malformed line stops it with an error naming the line.
+fn run_script(accounts: &mut [BankAccount], script: &str) -> Result<Vec<String>, String> {
+    let mut results = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let malformed = || format!("Line {}: malformed command '{}'", i + 1, line);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() < 3 {
+            return Err(malformed());
+        }
+        let numbers: Vec<u32> = words[1..words.len() - 1]
+            .iter()
+            .map(|w| w.parse::<u32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| malformed())?;
+        let amount = words[words.len() - 1]
+            .parse::<f64>()
+            .map_err(|_| malformed())?;
+        let outcome = match (words[0], numbers.as_slice()) {
+            ("deposit", [n]) => match accounts.iter_mut().find(|a| a.account_number == *n) {
+                Some(a) => a.deposit(amount),
+                None => Err(format!("Account number {} not found.", n)),
+            },
+            ("withdraw", [n]) => match accounts.iter_mut().find(|a| a.account_number == *n) {
+                Some(a) => a.withdraw(amount),
+                None => Err(format!("Account number {} not found.", n)),
+            },
+            ("transfer", [from, to]) => transfer(accounts, *from, *to, amount),
+            _ => return Err(malformed()),
+        };
+        results.push(match outcome {
+            Ok(()) => format!("ok: {}", line),
+            Err(e) => format!("failed: {} — {}", line, e),
+        });
+    }
+    Ok(results)
+}
+
+/// Opens a credit line numbered from the same range as deposit accounts.
+fn open_loan(
+    accounts: &[BankAccount],
+    loans: &mut Vec<LoanAccount>,
+    holder_name: &str,
+    credit_limit: f64,
+    format: AccountNumberFormat,
+) -> Result<u32, String> {
+    let existing: Vec<u32> = accounts
+        .iter()
+        .map(|a| a.account_number)
+        .chain(loans.iter().map(|l| l.account_number))
+        .collect();
+    let account_number =
+        next_account_number(&existing, format).ok_or_else(|| numbers_exhausted(format))?;
+    loans.push(LoanAccount::new(account_number, holder_name, credit_limit));
+    Ok(account_number)
+}
+
+fn format_receipt(
+    account: &BankAccount,
+    kind: TxnKind,
+    amount: f64,
+    timestamp: DateTime<Local>,
+) -> String {
+    let rows = [
+        "TRANSACTION RECEIPT".to_string(),
+        format!("Date:    {}", timestamp.format("%Y-%m-%d %H:%M:%S")),
+        format!("Account: {}", account.account_number),
+        format!("Holder:  {}", account.holder_name),
+        format!("Action:  {}", kind.label()),
+        format!("Amount:  ${:.2}", amount),
+        format!("Balance: ${:.2}", account.balance),
     ];
+    let width = rows
+        .iter()
+        .map(|r| r.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(RECEIPT_WIDTH);
+    let border = format!("+{}+", "-".repeat(width + 2));
+    let mut out = border.clone();
+    for row in rows {
+        out.push_str(&format!("\n| {:<width$} |", row, width = width));
+    }
+    out.push('\n');
+    out.push_str(&border);
+    out
+}
+
+fn issue_receipt(account: &BankAccount, kind: TxnKind, amount: f64, save_to_file: bool) {
+    let receipt = format_receipt(account, kind, amount, Local::now());
+    println!("{}", receipt);
+    if save_to_file {
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(RECEIPT_FILE)
+            .and_then(|mut f| writeln!(f, "{}\n", receipt));
+        if let Err(e) = written {
+            println!("Could not write receipt to {}: {}", RECEIPT_FILE, e);
+        }
+    }
+}
+
+fn ofx_date(time: DateTime<Local>) -> String {
+    time.format("%Y%m%d%H%M%S").to_string()
+}
 
+/// An OFX 2 bank statement for `account`, listing every transaction and
+/// the current balance as of `now`.
+fn format_ofx(account: &BankAccount, now: DateTime<Local>) -> String {
+    let start = account.transactions.first().map_or(now, |t| t.time);
+    let mut txns = String::new();
+    for (i, txn) in account.transactions.iter().enumerate() {
+        let (trn_type, sign) = txn.kind.ofx_type();
+        txns.push_str(&format!(
+            "<STMTTRN><TRNTYPE>{}</TRNTYPE><DTPOSTED>{}</DTPOSTED>\
+             <TRNAMT>{:.2}</TRNAMT><FITID>{}-{}</FITID><NAME>{}</NAME></STMTTRN>\n",
+            trn_type,
+            ofx_date(txn.time),
+            sign * txn.amount,
+            account.account_number,
+            i + 1,
+            txn.kind.label()
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <?OFX OFXHEADER=\"200\" VERSION=\"211\" SECURITY=\"NONE\" OLDFILEUID=\"NONE\" NEWFILEUID=\"NONE\"?>\n\
+         <OFX>\n\
+         <SIGNONMSGSRSV1><SONRS><STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\
+         <DTSERVER>{now}</DTSERVER><LANGUAGE>ENG</LANGUAGE></SONRS></SIGNONMSGSRSV1>\n\
+         <BANKMSGSRSV1><STMTTRNRS><TRNUID>1</TRNUID>\
+         <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n\
+         <STMTRS><CURDEF>USD</CURDEF>\n\
+         <BANKACCTFROM><BANKID>{bank}</BANKID><ACCTID>{acct}</ACCTID><ACCTTYPE>CHECKING</ACCTTYPE></BANKACCTFROM>\n\
+         <BANKTRANLIST><DTSTART>{start}</DTSTART><DTEND>{now}</DTEND>\n\
+         {txns}\
+         </BANKTRANLIST>\n\
+         <LEDGERBAL><BALAMT>{balance:.2}</BALAMT><DTASOF>{now}</DTASOF></LEDGERBAL>\n\
+         </STMTRS></STMTTRNRS></BANKMSGSRSV1>\n\
+         </OFX>\n",
+        now = ofx_date(now),
+        bank = OFX_BANK_ID,
+        acct = account.account_number,
+        start = ofx_date(start),
+        txns = txns,
+        balance = account.balance,
+    )
+}
+
+fn export_ofx(account: &BankAccount, path: &str) -> Result<(), String> {
+    fs::write(path, format_ofx(account, Local::now()))
+        .map_err(|e| format!("Could not write {}: {}", path, e))
+}
+
+fn format_transaction(txn: &Transaction) -> String {
+    format!(
+        "  {} {:<10} ${:>10.2}  balance ${:.2}",
+        txn.time.format("%Y-%m-%d %H:%M"),
+        txn.kind.label(),
+        txn.amount,
+        txn.balance_after
+    )
+}
+
+/// Transactions of `kind` (any kind if `None`) whose amount lies within
+/// `min..=max`.
+fn filter_transactions(
+    account: &BankAccount,
+    kind: Option<TxnKind>,
+    min: f64,
+    max: f64,
+) -> Vec<&Transaction> {
+    account
+        .transactions
+        .iter()
+        .filter(|t| kind.is_none_or(|k| t.kind == k))
+        .filter(|t| (min..=max).contains(&t.amount))
+        .collect()
+}
+
+fn percent_of(balance: f64, pct: f64) -> f64 {
+    balance * pct / 100.0
+}
+
+/// Turns a percentage of `balance` into an amount for `kind`. Withdrawing
+/// more than 100% of the balance is rejected; the usual deposit and
+/// withdrawal checks still apply to the result.
+fn amount_from_percent(balance: f64, pct: f64, kind: TxnKind) -> Result<f64, String> {
+    if pct <= 0.0 {
+        return Err("Percentage must be greater than zero.".to_string());
+    }
+    if kind == TxnKind::Withdrawal && pct > 100.0 {
+        return Err("Cannot withdraw more than 100% of the balance.".to_string());
+    }
+    if balance <= 0.0 {
+        return Err(format!(
+            "A percentage of a balance of ${:.2} is not a valid amount.",
+            balance
+        ));
+    }
+    Ok(percent_of(balance, pct))
+}
+
+/// Asks for a fixed amount or a percentage of the current balance.
+fn read_amount(balance: f64, kind: TxnKind) -> Result<f64, String> {
+    println!("1. Enter an amount");
+    println!("2. Enter a percentage of the balance (${:.2})", balance);
+    print!("Choose an option (1-2): ");
+    let percent = match read_input().as_str() {
+        "1" => false,
+        "2" => true,
+        _ => return Err("Invalid option.".to_string()),
+    };
+    println!(
+        "Enter {} {}:",
+        kind.label().to_lowercase(),
+        if percent { "percentage" } else { "amount" }
+    );
+    let value = read_input()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .map_err(|_| "Invalid amount entered.".to_string())?;
+    if percent {
+        amount_from_percent(balance, value, kind)
+    } else {
+        Ok(value)
+    }
+}
+
+fn read_input() -> String {
+    let _ = io::stdout().flush();
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .expect("Failed to read input");
+    input.trim().to_string()
+}
+
+fn loan_menu(accounts: &[BankAccount], loans: &mut Vec<LoanAccount>, format: AccountNumberFormat) {
     loop {
-        println!("\n===== Banking System =====");
-        println!("1. Deposit");
-        println!("2. Withdraw");
-        println!("3. Check Balance");
-        println!("4. List Accounts");
-        println!("5. Exit");
+        println!("\n===== Loan Accounts =====");
+        println!("1. List Loans");
+        println!("2. Open Loan");
+        println!("3. Draw Down");
+        println!("4. Repay");
+        println!("5. Back");
         print!("Choose an option (1-5): ");
 
-        let mut choice = String::new();
-        io::stdin().read_line(&mut choice).expect("Failed to read input");
-        let choice = choice.trim();
+        match read_input().as_str() {
+            "1" => {
+                if loans.is_empty() {
+                    println!("No loan accounts.");
+                }
+                for loan in loans.iter() {
+                    println!(
+                        "Loan {} — {} — Owed: ${:.2} — Available: ${:.2} of ${:.2}",
+                        loan.account_number,
+                        loan.holder_name,
+                        loan.remaining_principal(),
+                        loan.available_credit(),
+                        loan.credit_limit
+                    );
+                }
+            }
+            "2" => {
+                println!("Enter holder name:");
+                let name = read_input();
+                if name.is_empty() {
+                    println!("Holder name cannot be empty.");
+                    continue;
+                }
+                println!("Enter credit limit:");
+                match read_input().parse::<f64>() {
+                    Ok(limit) if limit > 0.0 => {
+                        match open_loan(accounts, loans, &name, limit, format) {
+                            Ok(number) => println!("Opened loan {} for {}.", number, name),
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    _ => println!("Invalid credit limit entered."),
+                }
+            }
+            choice @ ("3" | "4") => {
+                println!("Enter loan number:");
+                let Ok(number) = read_input().parse::<u32>() else {
+                    println!("Invalid loan number entered.");
+                    continue;
+                };
+                let Some(loan) = loans.iter_mut().find(|l| l.account_number == number) else {
+                    println!("Loan number {} not found.", number);
+                    continue;
+                };
+                println!("Enter amount:");
+                let Ok(amount) = read_input().parse::<f64>() else {
+                    println!("Invalid amount entered.");
+                    continue;
+                };
+                let result = if choice == "3" {
+                    loan.withdraw(amount)
+                } else {
+                    loan.deposit(amount)
+                };
+                if let Err(e) = result {
+                    println!("Error: {}", e);
+                }
+            }
+            "5" => break,
+            _ => println!("Invalid option, please try again."),
+        }
+    }
+}
+
+/// Demo accounts numbered just above the smallest number in `format`.
+fn default_accounts(format: AccountNumberFormat) -> Vec<BankAccount> {
+    let (min, _) = format.bounds();
+    let mut bob = BankAccount::new(min + 2, "Bob", 1000.0);
+    bob.overdraft_limit = 250.0;
+    vec![BankAccount::new(min + 1, "Alice", 500.0), bob]
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let format = match args.iter().position(|a| a == "--account-digits") {
+        None => AccountNumberFormat::default(),
+        Some(i) => {
+            match args
+                .get(i + 1)
+                .and_then(|d| d.parse::<u32>().ok())
+                .and_then(AccountNumberFormat::new)
+            {
+                Some(format) => format,
+                None => {
+                    eprintln!(
+                        "Usage: simplebankaccount --account-digits <1-{}>",
+                        MAX_ACCOUNT_NUMBER_DIGITS
+                    );
+                    std::process::exit(2);
+                }
+            }
+        }
+    };
+    let mut accounts = default_accounts(format);
+    if let Some(i) = args.iter().position(|a| a == "--script") {
+        let Some(path) = args.get(i + 1) else {
+            eprintln!("Usage: simplebankaccount --script <file>");
+            std::process::exit(2);
+        };
+        let script = match fs::read_to_string(path) {
+            Ok(script) => script,
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path, e);
+                std::process::exit(2);
+            }
+        };
+        match run_script(&mut accounts, &script) {
+            Ok(results) => {
+                for line in results {
+                    println!("{}", line);
+                }
+                for acc in &accounts {
+                    println!(
+                        "Account {} — Balance: ${:.2}",
+                        acc.account_number, acc.balance
+                    );
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let mut loans: Vec<LoanAccount> = Vec::new();
+    let mut save_receipts = false;
+
+    loop {
+        println!("\n===== Banking System =====");
+        let items = [
+            "Deposit".to_string(),
+            "Withdraw".to_string(),
+            "Check Balance & History".to_string(),
+            "List Accounts".to_string(),
+            "Open Account".to_string(),
+            format!(
+                "Toggle saving receipts to {} (currently {})",
+                RECEIPT_FILE,
+                if save_receipts { "on" } else { "off" }
+            ),
+            "Accrue overdraft interest for N days".to_string(),
+            "Loan Accounts".to_string(),
+            "Search Transactions".to_string(),
+            "Export Statement to OFX".to_string(),
+            "Run Period-End Interest".to_string(),
+            "View/Change Account Limits".to_string(),
+            "Merge Accounts".to_string(),
+            "Close Account".to_string(),
+            "Net Worth".to_string(),
+            "Overdrawn Accounts".to_string(),
+            "Exit".to_string(),
+        ];
+        for (i, item) in items.iter().enumerate() {
+            println!("{}. {}", i + 1, item);
+        }
+        print!("Choose an option (1-{}): ", items.len());
+
+        let choice = read_input();
 
-        match choice {
+        match choice.as_str() {
             "1" => {
-                if let Some(account) = select_account(&mut accounts) {
-                    println!("Enter deposit amount:");
-                    let mut amount_input = String::new();
-                    io::stdin().read_line(&mut amount_input).expect("Failed to read input");
-                    if let Ok(amount) = amount_input.trim().parse::<f64>() {
-                        match account.deposit(amount) {
-                            Ok(_) => println!("Deposit successful!"),
+                if let Some(account) = select_account(&mut accounts, format) {
+                    match read_amount(account.balance(), TxnKind::Deposit) {
+                        Ok(amount) => match account.deposit(amount) {
+                            Ok(_) => {
+                                println!("Deposit successful!");
+                                issue_receipt(account, TxnKind::Deposit, amount, save_receipts);
+                            }
                             Err(e) => println!("Error: {}", e),
-                        }
-                    } else {
-                        println!("Invalid amount entered.");
+                        },
+                        Err(e) => println!("Error: {}", e),
                     }
                 }
             }
             "2" => {
-                if let Some(account) = select_account(&mut accounts) {
-                    println!("Enter withdrawal amount:");
-                    let mut amount_input = String::new();
-                    io::stdin().read_line(&mut amount_input).expect("Failed to read input");
-                    if let Ok(amount) = amount_input.trim().parse::<f64>() {
-                        match account.withdraw(amount) {
-                            Ok(_) => println!("Withdrawal successful!"),
+                if let Some(account) = select_account(&mut accounts, format) {
+                    match read_amount(account.balance(), TxnKind::Withdrawal) {
+                        Ok(amount) => match account.withdraw(amount) {
+                            Ok(_) => {
+                                println!("Withdrawal successful!");
+                                issue_receipt(account, TxnKind::Withdrawal, amount, save_receipts);
+                            }
                             Err(e) => println!("Error: {}", e),
-                        }
-                    } else {
-                        println!("Invalid amount entered.");
+                        },
+                        Err(e) => println!("Error: {}", e),
                     }
                 }
             }
             "3" => {
-                if let Some(account) = select_account(&mut accounts) {
+                if let Some(account) = select_account(&mut accounts, format) {
                     println!(
                         "Account {} ({}) balance: ${:.2}",
                         account.account_number,
                         account.holder_name,
                         account.balance()
                     );
+                    for txn in &account.transactions {
+                        println!("{}", format_transaction(txn));
+                    }
                 }
             }
             "4" => {
@@ -128,6 +988,210 @@ fn main() {
                 }
             }
             "5" => {
+                println!("Enter holder name:");
+                let name = read_input();
+                if name.is_empty() {
+                    println!("Holder name cannot be empty.");
+                } else {
+                    match open_account(&mut accounts, name.clone(), format) {
+                        Ok(number) => println!("Opened account {} for {}.", number, name),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
+            "6" => {
+                save_receipts = !save_receipts;
+                println!(
+                    "Saving receipts is now {}.",
+                    if save_receipts { "on" } else { "off" }
+                );
+            }
+            "7" => {
+                println!("Enter number of days:");
+                match read_input().parse::<u32>() {
+                    Ok(days) => {
+                        let as_of = Local::now() + chrono::Duration::days(days.into());
+                        for acc in accounts.iter_mut() {
+                            let before = acc.balance;
+                            let days = acc.chargeable_overdraft_days(days, as_of);
+                            acc.accrue_overdraft_interest(OVERDRAFT_DAILY_RATE, days);
+                            if acc.balance != before {
+                                println!(
+                                    "Account {} ({}) charged ${:.2} interest — New balance: ${:.2}",
+                                    acc.account_number,
+                                    acc.holder_name,
+                                    before - acc.balance,
+                                    acc.balance
+                                );
+                            }
+                        }
+                    }
+                    Err(_) => println!("Invalid number of days."),
+                }
+            }
+            "8" => loan_menu(&accounts, &mut loans, format),
+            "9" => {
+                if let Some(account) = select_account(&mut accounts, format) {
+                    println!("Type (deposit, withdrawal, interest, or empty for any):");
+                    let kind = match read_input().to_lowercase().as_str() {
+                        "" => None,
+                        "deposit" => Some(TxnKind::Deposit),
+                        "withdrawal" => Some(TxnKind::Withdrawal),
+                        "interest" => Some(TxnKind::Interest),
+                        _ => {
+                            println!("Unknown transaction type.");
+                            continue;
+                        }
+                    };
+                    println!("Minimum amount (empty for none):");
+                    let min = read_input();
+                    println!("Maximum amount (empty for none):");
+                    let max = read_input();
+                    let bound = |s: &str, default: f64| {
+                        if s.is_empty() {
+                            Ok(default)
+                        } else {
+                            s.parse::<f64>()
+                        }
+                    };
+                    match (bound(&min, f64::MIN), bound(&max, f64::MAX)) {
+                        (Ok(min), Ok(max)) => {
+                            let found = filter_transactions(account, kind, min, max);
+                            println!("{} matching transaction(s):", found.len());
+                            for txn in found {
+                                println!("{}", format_transaction(txn));
+                            }
+                        }
+                        _ => println!("Invalid amount entered."),
+                    }
+                }
+            }
+            "10" => {
+                if let Some(account) = select_account(&mut accounts, format) {
+                    let default_path = format!("statement-{}.ofx", account.account_number);
+                    println!("File name (empty for {}):", default_path);
+                    let path = read_input();
+                    let path = if path.is_empty() { default_path } else { path };
+                    match export_ofx(account, &path) {
+                        Ok(()) => println!(
+                            "Exported {} transaction(s) to {}.",
+                            account.transactions.len(),
+                            path
+                        ),
+                        Err(e) => println!("Error: {}", e),
+                    }
+                }
+            }
+            "11" => {
+                let charged = run_interest(&mut accounts, Local::now());
+                if charged.is_empty() {
+                    println!("No interest charged this period.");
+                }
+                for (number, interest) in charged {
+                    println!("Account {} charged ${:.2} interest.", number, interest);
+                }
+            }
+            "12" => {
+                if let Some(account) = select_account(&mut accounts, format) {
+                    let limits = &mut account.limits;
+                    let fields = [
+                        ("Maximum deposit", &mut limits.max_deposit),
+                        ("Maximum withdrawal", &mut limits.max_withdraw),
+                        ("Daily withdrawal limit", &mut limits.daily_withdraw),
+                    ];
+                    for (label, value) in fields {
+                        println!("{}: ${:.2} (new value, or empty to keep):", label, value);
+                        let input = read_input();
+                        if input.is_empty() {
+                            continue;
+                        }
+                        match input.parse::<f64>() {
+                            Ok(v) if v > 0.0 => *value = v,
+                            _ => println!("Invalid amount; {} unchanged.", label.to_lowercase()),
+                        }
+                    }
+                    println!(
+                        "Interest-free overdraft days: {} (new value, or empty to keep):",
+                        account.overdraft_grace_days
+                    );
+                    let input = read_input();
+                    if !input.is_empty() {
+                        match input.parse::<u32>() {
+                            Ok(days) => account.overdraft_grace_days = days,
+                            Err(_) => println!("Invalid number of days; grace period unchanged."),
+                        }
+                    }
+                    println!(
+                        "Round interest to the cent: {} (y/n, or empty to keep):",
+                        if account.round_interest { "yes" } else { "no" }
+                    );
+                    match read_input().to_lowercase().as_str() {
+                        "" => {}
+                        "y" | "yes" => account.round_interest = true,
+                        "n" | "no" => account.round_interest = false,
+                        _ => println!("Invalid answer; rounding unchanged."),
+                    }
+                }
+            }
+            "13" => {
+                println!("Account to close (source):");
+                let source = read_input().parse::<u32>();
+                println!("Account to merge into (destination):");
+                let dest = read_input().parse::<u32>();
+                match (source, dest) {
+                    (Ok(source), Ok(dest)) => match merge_accounts(&mut accounts, source, dest) {
+                        Ok(()) => {
+                            let balance = accounts
+                                .iter()
+                                .find(|a| a.account_number == dest)
+                                .map_or(0.0, |a| a.balance);
+                            println!(
+                                "Merged account {} into {} — New balance: ${:.2}",
+                                source, dest, balance
+                            );
+                        }
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    _ => println!("Invalid account number entered."),
+                }
+            }
+            "14" => {
+                println!("Account to close:");
+                match read_input().parse::<u32>() {
+                    Ok(n) => match close_account(&mut accounts, n, Local::now()) {
+                        Ok(statement) => println!("{}", statement),
+                        Err(e) => println!("Error: {}", e),
+                    },
+                    Err(_) => println!("Invalid account number entered."),
+                }
+            }
+            "15" => {
+                let all: Vec<&dyn Account> = accounts
+                    .iter()
+                    .map(|a| a as &dyn Account)
+                    .chain(loans.iter().map(|l| l as &dyn Account))
+                    .collect();
+                let owed: f64 = loans.iter().map(|l| l.remaining_principal()).sum();
+                println!(
+                    "Accounts:  ${:.2}",
+                    accounts.iter().map(|a| a.balance()).sum::<f64>()
+                );
+                println!("Loans:    -${:.2}", owed);
+                println!("Net worth: ${:.2}", net_worth(&all));
+            }
+            "16" => {
+                let overdrawn = overdrawn_accounts(&accounts);
+                if overdrawn.is_empty() {
+                    println!("No accounts are overdrawn.");
+                }
+                for acc in overdrawn {
+                    println!(
+                        "Account {} — {} — Overdrawn: ${:.2} of ${:.2} allowed",
+                        acc.account_number, acc.holder_name, -acc.balance, acc.overdraft_limit
+                    );
+                }
+            }
+            "17" => {
                 println!("Goodbye!");
                 break;
             }
@@ -136,12 +1200,16 @@ fn main() {
     }
 }
 
-fn select_account<'a>(accounts: &'a mut Vec<BankAccount>) -> Option<&'a mut BankAccount> {
+fn select_account(
+    accounts: &mut [BankAccount],
+    format: AccountNumberFormat,
+) -> Option<&mut BankAccount> {
     println!("Enter account number:");
-    let mut acc_input = String::new();
-    io::stdin().read_line(&mut acc_input).expect("Failed to read input");
-
-    if let Ok(acc_number) = acc_input.trim().parse::<u32>() {
+    if let Ok(acc_number) = read_input().parse::<u32>() {
+        if !validate_account_number(acc_number, format) {
+            println!("Account numbers must be {} digits long.", format.digits);
+            return None;
+        }
         for acc in accounts.iter_mut() {
             if acc.account_number == acc_number {
                 println!(
@@ -158,3 +1226,508 @@ fn select_account<'a>(accounts: &'a mut Vec<BankAccount>) -> Option<&'a mut Bank
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn account_number_validation() {
+        let four = AccountNumberFormat::default();
+        assert!(validate_account_number(1001, four));
+        assert!(validate_account_number(9999, four));
+        assert!(!validate_account_number(999, four));
+        assert!(!validate_account_number(10000, four));
+    }
+
+    #[test]
+    fn generator_avoids_existing_numbers() {
+        let four = AccountNumberFormat::default();
+        assert_eq!(next_account_number(&[], four), Some(1000));
+        assert_eq!(next_account_number(&[1001, 1002], four), Some(1003));
+        assert_eq!(next_account_number(&[9999, 1000], four), Some(1001));
+        let mut existing = vec![1001, 1002];
+        for _ in 0..50 {
+            let n = next_account_number(&existing, four).unwrap();
+            assert!(validate_account_number(n, four));
+            assert!(!existing.contains(&n));
+            existing.push(n);
+        }
+    }
+
+    #[test]
+    fn receipt_lists_transaction_details() {
+        let mut account = BankAccount::new(1001, "Alice", 500.0);
+        account.withdraw(125.5).unwrap();
+        let receipt = format_receipt(&account, TxnKind::Withdrawal, 125.5, Local::now());
+        assert!(receipt.contains("Action:  Withdrawal"));
+        assert!(receipt.contains("Amount:  $125.50"));
+        assert!(receipt.contains("Balance: $374.50"));
+        assert!(receipt.contains("Holder:  Alice"));
+        assert!(receipt.starts_with('+') && receipt.ends_with('+'));
+
+        let long = BankAccount::new(1003, "Maximiliane Alexandrina von Hohenberg", 0.0);
+        let receipt = format_receipt(&long, TxnKind::Deposit, 1.0, Local::now());
+        let widths: Vec<usize> = receipt.lines().map(|l| l.chars().count()).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]));
+        assert!(receipt.contains("| Holder:  Maximiliane Alexandrina von Hohenberg |"));
+    }
+
+    #[test]
+    fn overdraft_interest_compounds_daily() {
+        let mut account = BankAccount::new(1002, "Bob", 100.0);
+        account.overdraft_limit = 500.0;
+        account.withdraw(300.0).unwrap();
+        account.accrue_overdraft_interest(0.01, 2);
+        // -200 * 1.01 * 1.01
+        assert!((account.balance - -204.02).abs() < 1e-9);
+        let interest: Vec<&Transaction> = account
+            .transactions
+            .iter()
+            .filter(|t| t.kind == TxnKind::Interest)
+            .collect();
+        assert_eq!(interest.len(), 1);
+        assert!((interest[0].amount - 4.02).abs() < 1e-9);
+    }
+
+    #[test]
+    fn small_overdrafts_are_charged_once_per_accrual() {
+        let mut account = BankAccount::new(1002, "Bob", 0.0);
+        account.overdraft_limit = 100.0;
+        account.withdraw(5.0).unwrap();
+
+        // A single day's interest on $5 rounds to nothing and is not recorded.
+        account.accrue_overdraft_interest(OVERDRAFT_DAILY_RATE, 1);
+        assert_eq!(account.balance, -5.0);
+        assert_eq!(account.transactions.len(), 1);
+
+        // 5 * (1.0005^30 - 1) = 0.0755...
+        account.accrue_overdraft_interest(OVERDRAFT_DAILY_RATE, 30);
+        assert_eq!(account.balance, -5.08);
+        let interest: Vec<f64> = account
+            .transactions
+            .iter()
+            .filter(|t| t.kind == TxnKind::Interest)
+            .map(|t| t.amount)
+            .collect();
+        assert_eq!(interest, vec![0.08]);
+    }
+
+    #[test]
+    fn overdraft_interest_skips_positive_balances() {
+        let mut account = BankAccount::new(1001, "Alice", 500.0);
+        account.accrue_overdraft_interest(0.01, 30);
+        assert_eq!(account.balance, 500.0);
+        assert!(account.transactions.is_empty());
+    }
+
+    #[test]
+    fn withdrawals_respect_overdraft_limit() {
+        let mut account = BankAccount::new(1001, "Alice", 50.0);
+        assert!(account.withdraw(60.0).is_err());
+        account.overdraft_limit = 20.0;
+        assert!(account.withdraw(60.0).is_ok());
+        assert!((account.balance - -10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn loan_draw_down_respects_credit_limit() {
+        let mut loan = LoanAccount::new(1003, "Carol", 1000.0);
+        assert!(loan.withdraw(600.0).is_ok());
+        assert_eq!(loan.remaining_principal(), 600.0);
+        assert_eq!(loan.balance(), -600.0);
+        assert!(loan.withdraw(500.0).is_err());
+        assert_eq!(loan.remaining_principal(), 600.0);
+    }
+
+    #[test]
+    fn loan_partial_repayment_reduces_principal() {
+        let mut loan = LoanAccount::new(1003, "Carol", 1000.0);
+        loan.withdraw(600.0).unwrap();
+        assert!(loan.deposit(250.0).is_ok());
+        assert_eq!(loan.remaining_principal(), 350.0);
+        assert_eq!(loan.available_credit(), 650.0);
+    }
+
+    #[test]
+    fn loan_rejects_over_repayment() {
+        let mut loan = LoanAccount::new(1003, "Carol", 1000.0);
+        loan.withdraw(100.0).unwrap();
+        assert!(loan.deposit(150.0).is_err());
+        assert_eq!(loan.remaining_principal(), 100.0);
+    }
+
+    #[test]
+    fn open_loan_avoids_deposit_account_numbers() {
+        let format = AccountNumberFormat::default();
+        let accounts = vec![BankAccount::new(1001, "Alice", 0.0)];
+        let mut loans = Vec::new();
+        assert_eq!(
+            open_loan(&accounts, &mut loans, "Carol", 500.0, format),
+            Ok(1002)
+        );
+        assert_eq!(
+            open_loan(&accounts, &mut loans, "Dan", 500.0, format),
+            Ok(1003)
+        );
+    }
+
+    #[test]
+    fn percent_of_balance() {
+        assert_eq!(percent_of(500.0, 50.0), 250.0);
+        assert_eq!(percent_of(80.0, 12.5), 10.0);
+        assert_eq!(
+            amount_from_percent(200.0, 150.0, TxnKind::Deposit),
+            Ok(300.0)
+        );
+    }
+
+    #[test]
+    fn withdrawing_over_100_percent_is_rejected() {
+        assert!(amount_from_percent(500.0, 100.5, TxnKind::Withdrawal).is_err());
+        assert_eq!(
+            amount_from_percent(500.0, 100.0, TxnKind::Withdrawal),
+            Ok(500.0)
+        );
+        assert!(amount_from_percent(-50.0, 10.0, TxnKind::Withdrawal).is_err());
+    }
+
+    fn account_with_history() -> BankAccount {
+        let mut account = BankAccount::new(1001, "Alice", 0.0);
+        account.deposit(100.0).unwrap();
+        account.deposit(40.0).unwrap();
+        account.withdraw(25.0).unwrap();
+        account.withdraw(100.0).unwrap();
+        account
+    }
+
+    #[test]
+    fn filter_transactions_by_kind() {
+        let account = account_with_history();
+        let deposits = filter_transactions(&account, Some(TxnKind::Deposit), f64::MIN, f64::MAX);
+        assert_eq!(deposits.len(), 2);
+        assert!(deposits.iter().all(|t| t.kind == TxnKind::Deposit));
+        assert_eq!(
+            filter_transactions(&account, None, f64::MIN, f64::MAX).len(),
+            4
+        );
+    }
+
+    #[test]
+    fn filter_transactions_by_inclusive_range() {
+        let account = account_with_history();
+        let amounts: Vec<f64> = filter_transactions(&account, None, 40.0, 100.0)
+            .iter()
+            .map(|t| t.amount)
+            .collect();
+        assert_eq!(amounts, vec![100.0, 40.0, 100.0]);
+        let withdrawals = filter_transactions(&account, Some(TxnKind::Withdrawal), 0.0, 50.0);
+        assert_eq!(withdrawals.len(), 1);
+        assert_eq!(withdrawals[0].amount, 25.0);
+    }
+
+    #[test]
+    fn open_account_uses_generator() {
+        let format = AccountNumberFormat::default();
+        let mut accounts = Vec::new();
+        let first = open_account(&mut accounts, "Carol".to_string(), format).unwrap();
+        let second = open_account(&mut accounts, "Dave".to_string(), format).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[1].holder_name, "Dave");
+    }
+
+    #[test]
+    fn account_number_format_is_configurable_and_can_run_out() {
+        assert_eq!(AccountNumberFormat::new(0), None);
+        assert_eq!(
+            AccountNumberFormat::new(MAX_ACCOUNT_NUMBER_DIGITS + 1),
+            None
+        );
+        let six = AccountNumberFormat::new(6).unwrap();
+        assert_eq!(six.bounds(), (100_000, 999_999));
+        assert!(
+            default_accounts(six)
+                .iter()
+                .all(|a| validate_account_number(a.account_number, six))
+        );
+
+        let one = AccountNumberFormat::new(1).unwrap();
+        let mut accounts = Vec::new();
+        for _ in 1..=9 {
+            open_account(&mut accounts, "Eve".to_string(), one).unwrap();
+        }
+        assert_eq!(next_account_number(&[1, 2, 3, 4, 5, 6, 7, 8, 9], one), None);
+        assert!(open_account(&mut accounts, "Frank".to_string(), one).is_err());
+        let mut loans = Vec::new();
+        assert!(open_loan(&accounts, &mut loans, "Grace", 100.0, one).is_err());
+        assert_eq!(accounts.len(), 9);
+        assert!(loans.is_empty());
+    }
+
+    #[test]
+    fn ofx_statement_lists_transactions_and_balance() {
+        let account = account_with_history();
+        let ofx = format_ofx(&account, Local::now());
+        for tag in [
+            "<OFX>",
+            "<BANKACCTFROM>",
+            "<BANKTRANLIST>",
+            "<LEDGERBAL>",
+            "</OFX>",
+        ] {
+            assert!(ofx.contains(tag), "missing {}", tag);
+        }
+        assert!(ofx.starts_with("<?xml"));
+        assert_eq!(ofx.matches("<STMTTRN>").count(), account.transactions.len());
+        assert!(ofx.contains(&format!("<BALAMT>{:.2}</BALAMT>", account.balance)));
+        assert!(ofx.contains("<TRNTYPE>DEBIT</TRNTYPE>"));
+        assert_eq!(TxnKind::Interest.ofx_type(), ("FEE", -1.0));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("statement.ofx");
+        let path = path.to_str().unwrap();
+        export_ofx(&account, path).unwrap();
+        let written = fs::read_to_string(path).unwrap();
+        assert_eq!(
+            written.matches("<STMTTRN>").count(),
+            account.transactions.len()
+        );
+    }
+
+    /// `balance` after `days` of daily compounded overdraft interest,
+    /// rounded once.
+    fn accrued(balance: f64, days: u32) -> f64 {
+        let owed = (0..days).fold(-balance, |owed, _| owed * (1.0 + OVERDRAFT_DAILY_RATE));
+        round_cents(balance - round_cents(owed + balance))
+    }
+
+    #[test]
+    fn run_interest_applies_once_per_month() {
+        let date = |month, day| Local.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap();
+        let mut bob = BankAccount::new(1002, "Bob", 0.0);
+        bob.overdraft_limit = 500.0;
+        bob.withdraw(200.0).unwrap();
+        bob.overdraft_since = Some(date(1, 1));
+        let mut accounts = vec![BankAccount::new(1001, "Alice", 100.0), bob];
+
+        let charged = run_interest(&mut accounts, date(3, 31));
+        assert_eq!(charged.len(), 1);
+        assert_eq!(charged[0].0, 1002);
+        let after_first = accounts[1].balance;
+        let expected = accrued(-200.0, 31);
+        assert!((after_first - expected).abs() < 1e-9);
+        assert_eq!(accounts[0].last_interest_date, Some(date(3, 31)));
+
+        assert!(run_interest(&mut accounts, date(3, 31)).is_empty());
+        assert_eq!(accounts[1].balance, after_first);
+
+        let charged = run_interest(&mut accounts, date(4, 30));
+        assert_eq!(charged.len(), 1);
+        let expected = accrued(after_first, 30);
+        assert!((accounts[1].balance - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn deposits_and_withdrawals_respect_limits() {
+        let mut account = BankAccount::new(1003, "Dana", 0.0);
+        account.limits = AccountLimits {
+            max_deposit: 1000.0,
+            max_withdraw: 300.0,
+            daily_withdraw: 500.0,
+        };
+
+        let err = account.deposit(1000.01).unwrap_err();
+        assert!(err.contains("maximum deposit limit of $1000.00"), "{}", err);
+        account.deposit(1000.0).unwrap();
+
+        let err = account.withdraw(301.0).unwrap_err();
+        assert!(
+            err.contains("maximum withdrawal limit of $300.00"),
+            "{}",
+            err
+        );
+        account.withdraw(300.0).unwrap();
+        let err = account.withdraw(250.0).unwrap_err();
+        assert!(err.contains("daily withdrawal limit of $500.00"), "{}", err);
+        account.withdraw(200.0).unwrap();
+        assert_eq!(account.balance(), 500.0);
+
+        // Yesterday's withdrawals do not count towards today's limit.
+        for txn in &mut account.transactions {
+            txn.time -= chrono::Duration::days(1);
+        }
+        account.withdraw(300.0).unwrap();
+    }
+
+    #[test]
+    fn script_runs_commands_in_order() {
+        let mut accounts = default_accounts(AccountNumberFormat::default());
+        let script = "# month end\n\ndeposit 1001 100\nwithdraw 1002 50\ntransfer 1001 1002 25\nwithdraw 1001 9000\n";
+        let results = run_script(&mut accounts, script).unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(results[3].starts_with("failed: withdraw 1001 9000"));
+        assert_eq!(accounts[0].balance(), 575.0);
+        assert_eq!(accounts[1].balance(), 975.0);
+    }
+
+    #[test]
+    fn script_stops_at_malformed_line() {
+        let mut accounts = default_accounts(AccountNumberFormat::default());
+        let script = "deposit 1001 100\ndeposit 1001 ten\ndeposit 1001 100\n";
+        assert_eq!(
+            run_script(&mut accounts, script),
+            Err("Line 2: malformed command 'deposit 1001 ten'".to_string())
+        );
+        assert_eq!(accounts[0].balance(), 600.0);
+        assert!(run_script(&mut accounts, "transfer 1001 25").is_err());
+        assert!(run_script(&mut accounts, "close 1001").is_err());
+        assert!(run_script(&mut accounts, "deposit").is_err());
+        assert_eq!(accounts[0].balance(), 600.0);
+    }
+
+    #[test]
+    fn merge_moves_balance_and_history() {
+        let mut accounts = default_accounts(AccountNumberFormat::default());
+        accounts[0].deposit(50.0).unwrap();
+        accounts[1].withdraw(20.0).unwrap();
+        merge_accounts(&mut accounts, 1001, 1002).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].account_number, 1002);
+        assert_eq!(accounts[0].balance(), 1530.0);
+        assert_eq!(accounts[0].transactions.len(), 2);
+
+        assert_eq!(
+            merge_accounts(&mut accounts, 1002, 1002),
+            Err("Cannot merge an account into itself.".to_string())
+        );
+        assert!(merge_accounts(&mut accounts, 1001, 1002).is_err());
+        assert!(merge_accounts(&mut accounts, 1002, 1001).is_err());
+        assert_eq!(accounts.len(), 1);
+    }
+
+    #[test]
+    fn overdraft_interest_waits_for_grace_period() {
+        let date = |month, day| Local.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap();
+        let mut account = BankAccount::new(1002, "Bob", 100.0);
+        account.overdraft_limit = 500.0;
+        account.withdraw(300.0).unwrap();
+        assert!(account.overdraft_since.is_some());
+        account.overdraft_since = Some(date(3, 28));
+        account.overdraft_grace_days = 5;
+        let mut accounts = vec![account];
+
+        // Overdrawn 3 days at month end: still within grace.
+        assert!(run_interest(&mut accounts, date(3, 31)).is_empty());
+        assert_eq!(accounts[0].balance, -200.0);
+
+        // By April 30 the grace period ended 28 days earlier.
+        let charged = run_interest(&mut accounts, date(4, 30));
+        assert_eq!(charged.len(), 1);
+        let expected = accrued(-200.0, 28);
+        assert!((accounts[0].balance - expected).abs() < 1e-9);
+
+        accounts[0].deposit(500.0).unwrap();
+        assert_eq!(accounts[0].overdraft_since, None);
+    }
+
+    #[test]
+    fn closing_statement_lists_lifetime_totals() {
+        let mut accounts = default_accounts(AccountNumberFormat::default());
+        assert_eq!(
+            close_account(&mut accounts, 1001, Local::now()),
+            Err(
+                "Account 1001 still has a balance of $500.00; withdraw or deposit it first."
+                    .to_string()
+            )
+        );
+        accounts[0].deposit(250.0).unwrap();
+        accounts[0].withdraw(750.0).unwrap();
+
+        let closed_on = Local.with_ymd_and_hms(2024, 6, 30, 17, 0, 0).unwrap();
+        let statement = close_account(&mut accounts, 1001, closed_on).unwrap();
+        assert!(statement.contains("account 1001 (Alice)"));
+        assert!(statement.contains("Closed on:         2024-06-30"));
+        assert!(statement.contains("Total deposits:    $250.00"));
+        assert!(statement.contains("Total withdrawals: $750.00"));
+        assert!(statement.contains("Final balance:     $0.00"));
+        assert_eq!(accounts.len(), 1);
+        assert!(close_account(&mut accounts, 1001, closed_on).is_err());
+    }
+
+    #[test]
+    fn interest_is_rounded_to_the_cent() {
+        assert_eq!(round_cents(33.3), 33.3);
+        assert_eq!(round_cents(0.125), 0.13);
+        assert_eq!(round_cents(1.005), 1.01);
+        assert_eq!(round_cents(0.10005), 0.1);
+        assert_eq!(round_cents(-0.125), -0.13);
+
+        // $1000 overdrawn at 3.33% a day: 33.30, then 34.4089 -> 34.41.
+        let mut account = BankAccount::new(1002, "Bob", 0.0);
+        account.overdraft_limit = 5000.0;
+        account.withdraw(1000.0).unwrap();
+        account.accrue_overdraft_interest(0.0333, 2);
+        assert_eq!(account.balance, -1067.71);
+
+        // Many small accruals leave a whole number of cents.
+        let mut account = BankAccount::new(1002, "Bob", 0.0);
+        account.overdraft_limit = 5000.0;
+        account.withdraw(1234.56).unwrap();
+        account.accrue_overdraft_interest(OVERDRAFT_DAILY_RATE, 365);
+        let cents = account.balance * 100.0;
+        assert!((cents - cents.round()).abs() < 1e-6);
+        let charged: f64 = account
+            .transactions
+            .iter()
+            .filter(|t| t.kind == TxnKind::Interest)
+            .map(|t| t.amount)
+            .sum();
+        assert!((account.balance - (-1234.56 - charged)).abs() < 1e-6);
+
+        account.round_interest = false;
+        account.accrue_overdraft_interest(0.0333, 1);
+        let cents = account.balance * 100.0;
+        assert!((cents - cents.round()).abs() > 1e-6);
+    }
+
+    #[test]
+    fn net_worth_subtracts_loan_principal() {
+        let checking = BankAccount::new(1001, "Alice", 1200.0);
+        let savings = BankAccount::new(1002, "Alice", 5000.0);
+        let mut loan = LoanAccount::new(1003, "Alice", 10000.0);
+        loan.withdraw(3500.0).unwrap();
+        assert_eq!(net_worth(&[&checking, &savings, &loan]), 2700.0);
+
+        let mut overdrawn = BankAccount::new(1004, "Bob", 0.0);
+        overdrawn.overdraft_limit = 100.0;
+        overdrawn.withdraw(40.0).unwrap();
+        assert_eq!(net_worth(&[&checking, &overdrawn]), 1160.0);
+        assert_eq!(net_worth(&[]), 0.0);
+    }
+
+    #[test]
+    fn overdrawn_accounts_excludes_zero_and_positive() {
+        let mut accounts = vec![
+            BankAccount::new(1001, "Alice", 50.0),
+            BankAccount::new(1002, "Bob", 0.0),
+            BankAccount::new(1003, "Carol", 0.0),
+            BankAccount::new(1004, "Dave", 10.0),
+        ];
+        for acc in accounts.iter_mut() {
+            acc.overdraft_limit = 500.0;
+        }
+        accounts[2].withdraw(30.0).unwrap();
+        accounts[3].withdraw(210.0).unwrap();
+        accounts[0].withdraw(50.0).unwrap();
+
+        let numbers: Vec<u32> = overdrawn_accounts(&accounts)
+            .iter()
+            .map(|a| a.account_number)
+            .collect();
+        assert_eq!(numbers, vec![1004, 1003]);
+        assert_eq!(accounts[0].balance(), 0.0);
+        assert!(overdrawn_accounts(&[]).is_empty());
+    }
+}